@@ -1,7 +1,7 @@
 use anyhow::*;
 use glob::glob;
 use std::fs::{read_to_string, write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 struct ShaderData {
     src: String,
@@ -54,6 +54,21 @@ fn main() -> Result<()> {
         .collect::<Result<Vec<_>>>()?;
 
     let mut compiler = shaderc::Compiler::new().context("Unable to create shader compiler")?;
+    let mut options = shaderc::CompileOptions::new().context("Unable to create shader compile options")?;
+
+    // `#include "name.glsl"` pulls in a file next to the shader including it, for
+    // code several shaders share. The .glsl files aren't compiled on their own.
+    options.set_include_callback(|requested, _include_type, requesting, _depth| {
+        let path = Path::new(requesting).with_file_name(requested);
+        let content = read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(shaderc::ResolvedInclude {
+            resolved_name: path.to_str().unwrap().to_string(),
+            content,
+        })
+    });
+    for include in glob("./src/**/*.glsl")? {
+        println!("cargo:rerun-if-changed={}", include?.as_os_str().to_str().unwrap());
+    }
 
     // This can't be parallelized. The [shaderc::Compiler] is not
     // thread safe. Also, it creates a lot of resources. You could
@@ -69,7 +84,7 @@ fn main() -> Result<()> {
             shader.kind,
             &shader.src_path.to_str().unwrap(),
             "main",
-        None,
+            Some(&options),
         )?;
         write(shader.spv_path, compiled.as_binary_u8())?;
     }
@@ -1,34 +1,2304 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use cgmath::{Decomposed, InnerSpace, Quaternion, Vector2, Vector3};
 use futures::executor::block_on;
-use crate::{camera::Camera, model::Mesh};
+use log::{debug, error, info, warn};
+use wgpu::util::DeviceExt;
+use crate::{batching::InstanceBatches, benchmark::FrameStats, camera::Camera, controller::{Controller, KeyBindings}, debug_lines::DebugLines, deferred::{Deferred, DeferredLayouts, PointLight}, error_scope::ErrorScopes, gizmo::Gizmo, gpu_memory::{self, GpuReport}, hud::Hud, mesh_cache::MeshCache, model::{Aabb, AsVertexPrimitive, GridUvMode, Material, Mesh, MeshReport, Model, Vertex}, morph::MorphTarget, oit::{Oit, Translucent}, outline::Outline, pipeline::PipelineBuilder, post_process::{Bloom, Fxaa, HDR_FORMAT, ToneMap, ToneMapPass}, ray::Ray, render_item::{DrawRenderItem, RenderItem}, scene::Scene, sh, terrain::{Terrain, TerrainMaterial}, texture::Texture, timer::Timer, wireframe::Wireframe};
+
+use winit::{dpi::{LogicalSize, PhysicalSize}, event::*, event_loop::{ControlFlow, EventLoop}, platform::run_return::EventLoopExtRunReturn, window::{Window, WindowBuilder}};
+
+/// The meshes number keys 1 to 5 switch to, see `State::show_primitive`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Primitive {
+    Brick,
+    Sphere,
+    GeoSphere,
+    Torus,
+    Grid,
+}
+
+impl Primitive {
+    fn from_key(key: VirtualKeyCode) -> Option<Self> {
+        match key {
+            VirtualKeyCode::Key1 => Some(Primitive::Brick),
+            VirtualKeyCode::Key2 => Some(Primitive::Sphere),
+            VirtualKeyCode::Key3 => Some(Primitive::GeoSphere),
+            VirtualKeyCode::Key4 => Some(Primitive::Torus),
+            VirtualKeyCode::Key5 => Some(Primitive::Grid),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Primitive::Brick => "brick",
+            Primitive::Sphere => "sphere",
+            Primitive::GeoSphere => "geo_sphere",
+            Primitive::Torus => "torus",
+            Primitive::Grid => "grid",
+        }
+    }
+
+    fn mesh(self) -> Mesh {
+        match self {
+            Primitive::Brick => Mesh::brick(1.0, 1.0, 1.0, 0),
+            Primitive::Sphere => Mesh::sphere(1.0, 32, 16),
+            Primitive::GeoSphere => Mesh::geo_sphere(1.0, 3),
+            Primitive::Torus => Mesh::from_sdf(
+                Aabb::new(Vector3::new(-1.1, -0.4, -1.1), Vector3::new(1.1, 0.4, 1.1)),
+                48,
+                |p| Vector2::new(Vector2::new(p.x, p.z).magnitude() - 0.7, p.y).magnitude() - 0.3,
+            ),
+            Primitive::Grid => Mesh::grid(2.0, 2.0, 8, 8, GridUvMode::Stretch),
+        }
+    }
+}
+
+/// Eye and target framing `bounds` for `camera`: its center, seen from far enough
+/// along +z for the bounding sphere to fit the vertical field of view.
+fn framing(camera: &Camera, bounds: &Aabb) -> (Vector3<f32>, Vector3<f32>) {
+    let radius = 0.5 * bounds.size().magnitude();
+    let half_fov = (1.0 / camera.get_proj().y.y).atan();
+    let target = bounds.center();
+    (target + Vector3::unit_z() * (radius / half_fov.sin()), target)
+}
+
+// The sample counts the MSAA key cycles through. wgpu 0.7 can't ask the adapter
+// which sample counts a format supports, so only the ones Vulkan, Metal and DX12
+// all guarantee for render targets are used.
+const SUPPORTED_SAMPLE_COUNTS: [u32; 2] = [1, 4];
+
+/// The highest supported sample count not above `requested`.
+fn supported_sample_count(requested: u32) -> u32 {
+    SUPPORTED_SAMPLE_COUNTS.iter().copied().filter(|&count| count <= requested).max().unwrap_or(1)
+}
+
+/// Indices of `items` in the order the scene pass draws them, grouped so the
+/// fewest pipeline and bind group changes happen between draws. The sort is
+/// stable, keyed by, most significant first:
+///
+/// 1. pipeline, the default one before those registered by name, in name order,
+///    and single-sided items before double-sided ones, see `ScenePipelines::get`;
+/// 2. material, the scene's diffuse bind group before the items' own, which are
+///    kept together, see `RenderItem::with_material`;
+/// 3. mesh, the item's own position in `items`, as no two items share buffers.
+///
+/// Translucent meshes aren't render items, `Oit` draws them afterwards in any order.
+fn draw_order(items: &[RenderItem], sorted: bool) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    if sorted {
+        order.sort_by_key(|&i| {
+            let material = items[i].material.as_ref().map(Rc::as_ptr);
+            (items[i].pipeline.as_deref(), items[i].double_sided, material)
+        });
+    }
+    order
+}
+
+// Of the depth range the wireframe's edges are pulled toward the camera by.
+const WIREFRAME_DEPTH_BIAS: f32 = 0.0005;
+
+// Seconds of a `with_morph_target` cycle, to the target and back.
+const MORPH_PERIOD: f32 = 4.0;
+
+// The layer `Application::with_validation` turns on.
+const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+// Background of the scene pass when it clears the color.
+const CLEAR_COLOR: wgpu::Color = wgpu::Color { r: 0.1, g: 0.2, b: 0.3, a: 1.0 };
+
+/// What the forward scene pass clears before drawing, see `Application::with_clear_mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClearMode {
+    ColorAndDepth,
+    // Keep the color, e.g. a skybox drawn before, and draw the geometry over it.
+    DepthOnly,
+    // Keep both, to add to what an earlier pass drew.
+    None,
+}
+
+impl Default for ClearMode {
+    fn default() -> Self {
+        ClearMode::ColorAndDepth
+    }
+}
+
+impl ClearMode {
+    fn color_load(self) -> wgpu::LoadOp<wgpu::Color> {
+        match self {
+            ClearMode::ColorAndDepth => wgpu::LoadOp::Clear(CLEAR_COLOR),
+            ClearMode::DepthOnly | ClearMode::None => wgpu::LoadOp::Load,
+        }
+    }
+
+    fn depth_load(self) -> wgpu::LoadOp<f32> {
+        match self {
+            ClearMode::ColorAndDepth | ClearMode::DepthOnly => wgpu::LoadOp::Clear(1.0),
+            ClearMode::None => wgpu::LoadOp::Load,
+        }
+    }
+}
+
+/// When the event loop draws, see `Application::with_redraw_mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedrawMode {
+    // Draw continuously, as fast as the present mode lets frames through.
+    Poll,
+    // Sleep until an event, then draw once, or keep drawing while something animates.
+    Wait,
+}
 
-use winit::{dpi::LogicalSize, event::*, event_loop::{ControlFlow, EventLoop}, window::{self, Window, WindowBuilder}};
+impl Default for RedrawMode {
+    fn default() -> Self {
+        RedrawMode::Poll
+    }
+}
+
+/// What the forward scene pass shows instead of the lit scene, one at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugView {
+    Lit,
+    // World space normals as colors.
+    Normals,
+    // Red where the tangent frame is right handed, blue where mirrored texture
+    // coordinates make it left handed, gray where it's undefined.
+    TangentHandedness,
+}
+
+impl Default for DebugView {
+    fn default() -> Self {
+        DebugView::Lit
+    }
+}
+
+impl DebugView {
+    /// Switch to `view`, or back to the lit scene if it's already shown.
+    pub fn toggle(self, view: DebugView) -> Self {
+        if self == view { DebugView::Lit } else { view }
+    }
+}
 
 pub struct Application {
     pub meshs: Vec<Mesh>,
+    // OBJs laid out after `meshs`, see `with_scene_directory`.
+    pub scene_directory: Option<PathBuf>,
+    // Drawn after the scene, see `with_model`.
+    pub models: Vec<Model>,
     pub camera: Camera,
+    // Eye and target the camera starts at, see `with_camera_look_at`.
+    pub camera_look_at: Option<(Vector3<f32>, Vector3<f32>)>,
     pub size: LogicalSize<u32>,
+    pub power_preference: wgpu::PowerPreference,
+    pub present_mode: wgpu::PresentMode,
+    // Used as is instead of the negotiated format, see `negotiate_swap_chain`.
+    pub surface_format: Option<wgpu::TextureFormat>,
+    pub hud_font_size: f32,
+    pub hud_color: [f32; 4],
+    pub bloom_enabled: bool,
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+    pub fxaa_enabled: bool,
+    // Upload each vertex attribute into its own buffer instead of interleaving them.
+    pub deinterleave_vertices: bool,
+    pub gizmo_enabled: bool,
+    pub debug_view: DebugView,
+    // Draw the ray of the last left click, to check picking.
+    pub ray_debug_enabled: bool,
+    pub ray_length: f32,
+    pub key_bindings: KeyBindings,
+    // List `key_bindings` over the scene, toggled by `KeyBindings::help`.
+    pub help_enabled: bool,
+    // Render the scene into an `HDR_FORMAT` target and tonemap it with `tone_map`.
+    pub hdr_enabled: bool,
+    pub tone_map: ToneMap,
+    // Ambient environment as SH radiance coefficients, see `sh::project_equirect`.
+    pub environment_sh: [Vector3<f32>; 9],
+    // Shade the scene with `point_lights` through a G-buffer, see `Deferred`. Fixed
+    // at startup, and MSAA doesn't apply to it.
+    pub deferred_enabled: bool,
+    pub point_lights: Vec<PointLight>,
+    // MSAA samples per pixel for the scene, see `with_sample_count`.
+    pub sample_count: u32,
+    pub validation: bool,
+    pub trace_path: Option<PathBuf>,
+    // Count texture and render item memory for `gpu_report`.
+    pub gpu_memory_tracking: bool,
+    pub clear_mode: ClearMode,
+    // Of the scene's depth texture, see `with_depth_format`.
+    pub depth_format: wgpu::TextureFormat,
+    // Outline the gizmo's item, needs a `depth_format` with a stencil.
+    pub outline_enabled: bool,
+    pub outline_color: [f32; 4],
+    // In pixels.
+    pub outline_width: f32,
+    // Drawn with its own material after the meshes, see `with_terrain`.
+    pub terrain: Option<Terrain>,
+    // Push each mesh's model matrix instead of binding it where the adapter can,
+    // see `with_push_constants`.
+    pub push_constants: bool,
+    pub redraw_mode: RedrawMode,
+    // Drawn with weighted blended OIT over the forward scene when `oit_enabled`.
+    pub translucent: Vec<Translucent>,
+    pub oit_enabled: bool,
+    // What the first mesh keeps blending toward and back, see `with_morph_target`.
+    pub morph_target: Option<Mesh>,
+    // Edges over the shaded meshes, X toggles it.
+    pub wireframe_enabled: bool,
+    // Group the meshes' draws by pipeline, see `with_draw_sorting`.
+    pub draw_sorting: bool,
+    // Equal meshes drawn at least this often are instanced, see `with_instancing_threshold`.
+    pub instancing_threshold: usize,
+    // Leave instances outside the view out of the instanced draws, see `with_instance_culling`.
+    pub instance_culling: bool,
+    // Vertex shaders by name, see `with_pipeline`.
+    pub pipelines: HashMap<String, wgpu::ShaderModuleDescriptor<'static>>,
+    // Names of the pipelines `meshs` are drawn with, by mesh index.
+    pub mesh_pipelines: HashMap<usize, String>,
+    // Lay down the meshes' depth before shading them, see `with_depth_prepass`.
+    pub depth_prepass: bool,
 }
 
 impl Application {
-    pub fn run(&self) {
+    pub fn new(meshs: Vec<Mesh>, camera: Camera, size: LogicalSize<u32>) -> Self {
+        Self {
+            meshs,
+            scene_directory: None,
+            models: Vec::new(),
+            camera,
+            camera_look_at: None,
+            size,
+            // Prefer the discrete GPU on laptops so benchmarks are comparable.
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            present_mode: wgpu::PresentMode::Fifo,
+            surface_format: None,
+            hud_font_size: 20.0,
+            hud_color: [1.0, 1.0, 1.0, 1.0],
+            bloom_enabled: false,
+            bloom_threshold: 0.8,
+            bloom_intensity: 1.0,
+            fxaa_enabled: false,
+            deinterleave_vertices: false,
+            gizmo_enabled: false,
+            debug_view: DebugView::default(),
+            ray_debug_enabled: false,
+            ray_length: 10.0,
+            key_bindings: KeyBindings::default(),
+            help_enabled: false,
+            hdr_enabled: false,
+            tone_map: ToneMap::default(),
+            // White all around, which leaves the albedo as it is.
+            environment_sh: sh::uniform(Vector3::new(1.0, 1.0, 1.0)),
+            deferred_enabled: false,
+            // Red, green and blue lights around the origin.
+            point_lights: vec![
+                PointLight { position: Vector3::new(2.0, 1.0, 0.0), color: Vector3::new(8.0, 1.0, 1.0), radius: 6.0 },
+                PointLight { position: Vector3::new(-1.0, 1.0, 1.7), color: Vector3::new(1.0, 8.0, 1.0), radius: 6.0 },
+                PointLight { position: Vector3::new(-1.0, 1.0, -1.7), color: Vector3::new(1.0, 1.0, 8.0), radius: 6.0 },
+            ],
+            sample_count: 1,
+            validation: false,
+            trace_path: None,
+            gpu_memory_tracking: false,
+            clear_mode: ClearMode::default(),
+            depth_format: Texture::DEPTH_STENCIL_FORMAT,
+            outline_enabled: false,
+            outline_color: [1.0, 0.6, 0.1, 1.0],
+            outline_width: 3.0,
+            terrain: None,
+            push_constants: true,
+            redraw_mode: RedrawMode::default(),
+            translucent: Vec::new(),
+            oit_enabled: false,
+            morph_target: None,
+            wireframe_enabled: false,
+            draw_sorting: true,
+            instancing_threshold: 4,
+            instance_culling: true,
+            pipelines: HashMap::new(),
+            mesh_pipelines: HashMap::new(),
+            depth_prepass: false,
+        }
+    }
+
+    /// Add every OBJ directly in `dir`, laid out on the ground by
+    /// `Scene::from_directory` and drawn after `meshs`. If `dir` can't be listed
+    /// the scene is left out with a warning.
+    pub fn with_scene_directory(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.scene_directory = Some(dir.into());
+        self
+    }
+
+    /// Add `model`'s meshes, placed by its `Model::world_transform` and drawn
+    /// after the scene, all with its material override when it has one and with
+    /// their own material otherwise. The override applies as it is now, the model
+    /// can't be changed once added.
+    pub fn with_model(mut self, model: Model) -> Self {
+        self.models.push(model);
+        self
+    }
+
+    /// Start with the camera at `eye` looking at `target`, and return there on
+    /// `KeyBindings::reset_camera`. Without it the camera frames the first mesh's
+    /// bounding sphere, or the scene's without meshes, or keeps its own transform
+    /// when there's neither.
+    pub fn with_camera_look_at(mut self, eye: Vector3<f32>, target: Vector3<f32>) -> Self {
+        self.camera_look_at = Some((eye, target));
+        self
+    }
+
+    /// Ask for `present_mode`, Fifo by default. wgpu falls back to Fifo, which
+    /// every surface supports, when the surface lacks it.
+    pub fn with_present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    /// Use `format` for the swap chain instead of the adapter's preferred sRGB one.
+    /// It isn't checked beforehand, creating the swap chain fails if the surface
+    /// doesn't support it. The shaders write linear colors, so a non-sRGB format
+    /// makes the image darker.
+    pub fn with_surface_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.surface_format = Some(format);
+        self
+    }
+
+    /// Render the scene with `sample_count` MSAA samples per pixel, M switches between
+    /// 1 and 4 at runtime. Other counts fall back to the next lower one of those, the
+    /// only ones every backend is guaranteed to support.
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// Turn on the Vulkan validation layers, which report API misuse such as bad
+    /// bindings or shader interface mismatches through the log. This needs the
+    /// Vulkan SDK's `VK_LAYER_KHRONOS_validation` installed, other backends ignore it.
+    ///
+    /// wgpu 0.7 has no instance flags for them, so the layer is added to
+    /// `VK_INSTANCE_LAYERS`, which the Vulkan loader reads when the instance is
+    /// created. That changes the environment of the whole process, so it's done
+    /// once at the start of `run` or `benchmark`, before the instance exists. With
+    /// validation on, those must be called before any other thread is started,
+    /// as setting a variable while another thread reads the environment is
+    /// undefined behavior on most platforms.
+    pub fn with_validation(mut self, validation: bool) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Record every wgpu call into the `path` directory, needs the `trace` feature.
+    ///
+    /// The trace is a `trace.ron` file plus the buffer and shader data it refers to.
+    /// Replay it with the `player` from a checkout of the wgpu release matching this
+    /// crate's, `cargo run --features winit --bin play -- <path>` in its `player`
+    /// directory, to step through the frames or inspect the calls in the `.ron` file.
+    pub fn with_trace(mut self, path: impl Into<PathBuf>) -> Self {
+        self.trace_path = Some(path.into());
+        self
+    }
+
+    /// Keep count of the memory of the textures and render items the crate creates,
+    /// for `gpu_report` and the report key, e.g. to spot leaks over a long run.
+    pub fn with_gpu_memory_tracking(mut self, enabled: bool) -> Self {
+        self.gpu_memory_tracking = enabled;
+        self
+    }
+
+    /// Choose what the forward scene pass clears, both color and depth by default.
+    /// Whatever isn't cleared is loaded from the frame before, or from an earlier
+    /// pass drawing into the same targets this frame. A fresh swap chain frame's
+    /// contents are undefined, so keeping its color only makes sense when
+    /// something draws it first. The deferred path clears its G-buffer regardless.
+    pub fn with_clear_mode(mut self, clear_mode: ClearMode) -> Self {
+        self.clear_mode = clear_mode;
+        self
+    }
+
+    /// Use `format` for the scene's depth texture, `Texture::DEPTH_STENCIL_FORMAT`
+    /// by default. If the adapter can't create it, the scene falls back to
+    /// `Texture::DEPTH_FORMAT`, and without a stencil there is no outline.
+    pub fn with_depth_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.depth_format = format;
+        self
+    }
+
+    /// Outline the item the gizmo moves with a `width` pixel rim of `color`, O
+    /// toggles it at runtime. Only the forward path draws it, see `Outline`.
+    pub fn with_outline(mut self, color: [f32; 4], width: f32) -> Self {
+        self.outline_enabled = true;
+        self.outline_color = color;
+        self.outline_width = width;
+        self
+    }
+
+    /// Add `terrain` to the scene, drawn with a `TerrainMaterial` by the forward
+    /// pass only. If its layers can't be uploaded it's left out with a warning.
+    pub fn with_terrain(mut self, terrain: Terrain) -> Self {
+        self.terrain = Some(terrain);
+        self
+    }
+
+    /// Add a translucent mesh, drawn with order-independent transparency once
+    /// `with_oit` turns it on.
+    pub fn with_translucent(mut self, translucent: Translucent) -> Self {
+        self.translucent.push(translucent);
+        self
+    }
+
+    /// Draw the `with_translucent` meshes over the forward scene in any order with
+    /// weighted blended transparency, see `Oit`, off by default. The deferred path
+    /// has no depth for them to be tested against, they're left out there.
+    pub fn with_oit(mut self, enabled: bool) -> Self {
+        self.oit_enabled = enabled;
+        self
+    }
+
+    /// Blend the first mesh toward `target` and back again in the vertex shader
+    /// with a `MorphTarget`, one cycle every `MORPH_PERIOD` seconds. `target` must
+    /// have the first mesh's vertices in the same order, it's left out with a
+    /// warning otherwise. The forward path only, and the morph stops once a
+    /// primitive replaces the mesh.
+    pub fn with_morph_target(mut self, target: Mesh) -> Self {
+        self.morph_target = Some(target);
+        self
+    }
+
+    /// Start with every mesh's edges drawn over it, X toggles them at runtime.
+    /// Only the forward path draws them, see `Wireframe`.
+    pub fn with_wireframe(mut self, enabled: bool) -> Self {
+        self.wireframe_enabled = enabled;
+        self
+    }
+
+    /// Draw the meshes grouped by pipeline, material and then mesh, on by default,
+    /// so the scene pass only switches state where the next item needs it. Off,
+    /// they're drawn in the order given, e.g. to compare frame times.
+    pub fn with_draw_sorting(mut self, enabled: bool) -> Self {
+        self.draw_sorting = enabled;
+        self
+    }
+
+    /// Draw meshes given at least `threshold` times, equal by `Mesh::content_hash`,
+    /// with a single instanced draw in the lit view, 4 by default, see
+    /// `InstanceBatches`. `usize::MAX` draws every mesh on its own.
+    pub fn with_instancing_threshold(mut self, threshold: usize) -> Self {
+        self.instancing_threshold = threshold;
+        self
+    }
+
+    /// Test each instance of the instanced draws against the view frustum every
+    /// frame and only draw the ones in view, on by default. The number left out
+    /// shows next to the frame rate.
+    pub fn with_instance_culling(mut self, enabled: bool) -> Self {
+        self.instance_culling = enabled;
+        self
+    }
+
+    /// Register a lit pipeline as `name`, drawing with `vertex_shader` in place of
+    /// `shader.vert`, e.g. `wgpu::include_spirv!` of a shader that sways or
+    /// billboards the mesh. It takes the same vertex inputs and bind groups and
+    /// passes `shader.frag` the same outputs. Registering a name again replaces
+    /// its shader.
+    ///
+    /// Meshes are drawn with it once `with_mesh_pipeline` picks it, and render
+    /// items once `RenderItem::with_pipeline` does. Everything else uses the
+    /// default pipeline, as does every item in the debug views, the deferred path
+    /// and the overlays. A shader that fails to load is left out with a warning,
+    /// and its items drawn with the default.
+    pub fn with_pipeline(mut self, name: &str, vertex_shader: wgpu::ShaderModuleDescriptor<'static>) -> Self {
+        self.pipelines.insert(name.to_string(), vertex_shader);
+        self
+    }
+
+    /// Draw the mesh at `index` of `meshs` with the pipeline registered as `name`
+    /// by `with_pipeline`.
+    pub fn with_mesh_pipeline(mut self, index: usize, name: &str) -> Self {
+        self.mesh_pipelines.insert(index, name.to_string());
+        self
+    }
+
+    /// Draw the meshes' depth alone first in the lit view, then shade them testing
+    /// for equal depth with depth writes off, so each pixel is shaded once however
+    /// many surfaces overlap it. Off by default: it pays off with heavy fragment
+    /// shaders and overdraw, and costs a second vertex pass otherwise.
+    ///
+    /// It covers the meshes drawn with the default pipeline, which bind their
+    /// model matrix instead of pushing it while it's on, see `with_push_constants`.
+    /// Instanced meshes, those with a pipeline of their own and the overlays are
+    /// drawn after it as usual. Only the forward path has it.
+    pub fn with_depth_prepass(mut self, enabled: bool) -> Self {
+        self.depth_prepass = enabled;
+        self
+    }
+
+    /// Draw the meshes in the lit view with their model matrix pushed per draw
+    /// instead of bound from a uniform buffer, on by default. It only applies when
+    /// the adapter has `Features::PUSH_CONSTANTS` and a `max_push_constant_size`
+    /// of at least `RenderItem::PUSH_CONSTANT_SIZE`, the uniform buffers are used
+    /// otherwise. The debug views, the deferred path and the overlays always bind.
+    pub fn with_push_constants(mut self, enabled: bool) -> Self {
+        self.push_constants = enabled;
+        self
+    }
+
+    /// Draw continuously with `RedrawMode::Poll`, the default, or only after input
+    /// and resizes with `RedrawMode::Wait`, leaving the GPU idle while nothing
+    /// changes. Waiting still draws every frame while something animates, a held
+    /// movement key, a drag or a camera shake, and the time spent idle isn't
+    /// counted in the next frame's delta. `run_frames` always polls.
+    pub fn with_redraw_mode(mut self, mode: RedrawMode) -> Self {
+        self.redraw_mode = mode;
+        self
+    }
+
+    /// Approximate texture and buffer memory alive right now, all zero unless
+    /// tracking was turned on with `with_gpu_memory_tracking`.
+    pub fn gpu_report(&self) -> GpuReport {
+        GpuReport::current()
+    }
+
+    /// Open the window and run the demo until it's closed, blocking the thread.
+    /// A wrapper around `run_async`.
+    pub fn run(self) {
+        block_on(self.run_async())
+    }
+
+    /// `run` for callers with an executor of their own: the adapter and device
+    /// requests are awaited on it instead of blocking.
+    ///
+    /// Windowing still has to happen on the main thread on most platforms, macOS
+    /// and iOS enforce it and winit panics elsewhere. So this future must be
+    /// polled on the main thread, e.g. by a current-thread runtime's `block_on`,
+    /// not spawned onto a worker. Once the device is ready it hands the thread to
+    /// winit's event loop, which never returns, and the process exits when the
+    /// window closes, so anything that has to run afterwards won't.
+    pub async fn run_async(self) {
         let event_loop = EventLoop::new();
         let window = WindowBuilder::new()
             .with_inner_size(self.size)
             .build(&event_loop)
             .unwrap();
-        
+
+        let mut state = State::new(&window, self).await;
+        let mut timer = Timer::new();
+        timer.reset();
+        // Whether a window event arrived since the last redraw, for `RedrawMode::Wait`.
+        let mut window_event = false;
+
+        event_loop.run(move |event, _, control_flow| {
+            if let Event::WindowEvent { window_id, .. } = event {
+                window_event |= window_id == window.id();
+            }
+
+            match event {
+                Event::WindowEvent {
+                    ref event,
+                    window_id,
+                } if window_id == window.id() && !state.input(event) => match event {
+                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    WindowEvent::Resized(physical_size) => {
+                        state.pending_size = Some(*physical_size);
+                    }
+                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        state.pending_size = Some(**new_inner_size);
+                    }
+                    _ => {}
+                },
+
+                Event::DeviceEvent {
+                    ref event,
+                    ..
+                } => {
+                    state.device_input(event);
+                }
+
+                Event::RedrawRequested(_) => {
+                    // Minimizing on Windows resizes the window to 0x0, which wgpu
+                    // can't make targets of. Nothing is resized or drawn while it
+                    // is, the size stays pending until the restore replaces it.
+                    if state.pending_size.map_or(false, |size| size.width == 0 || size.height == 0) {
+                        return;
+                    }
+                    // Dragging a window edge sends many sizes a frame, only the last is applied.
+                    // The resize was a window event, so a redraw follows even in
+                    // `RedrawMode::Wait` and the final size isn't left pending.
+                    if let Some(size) = state.pending_size.take() {
+                        if size != state.size {
+                            state.resize(size);
+                        }
+                    }
+                    timer.tick();
+                    state.update(timer.delta_time());
+                    match state.render() {
+                        Ok(_) => {}
+                        // Recreate the swap chain if lost
+                        Err(wgpu::SwapChainError::Lost) => {
+                            warn!("Swap chain lost, recreating it");
+                            state.resize(state.size);
+                        }
+                        Err(wgpu::SwapChainError::OutOfMemory) => {
+                            error!("Out of memory getting the next frame, exiting");
+                            *control_flow = ControlFlow::Exit;
+                        }
+                        Err(e) => warn!("Dropped a frame: {:?}", e),
+                    }
+                }
+
+                Event::MainEventsCleared => {
+                    if let Some(title) = state.pending_title.take() {
+                        window.set_title(&title);
+                    }
+                    let animating = state.animating();
+                    match state.redraw_mode {
+                        RedrawMode::Poll => window.request_redraw(),
+                        RedrawMode::Wait if animating || window_event => {
+                            // Coming back from sleep, the idle time isn't part of the frame.
+                            if *control_flow == ControlFlow::Wait {
+                                timer.tick();
+                            }
+                            window.request_redraw();
+                        }
+                        RedrawMode::Wait => {}
+                    }
+                    window_event = false;
+                    // Keep the loop spinning while animating, it'd sleep past the next frame otherwise.
+                    if *control_flow != ControlFlow::Exit {
+                        *control_flow = match state.redraw_mode {
+                            RedrawMode::Wait if !animating => ControlFlow::Wait,
+                            _ => ControlFlow::Poll,
+                        };
+                    }
+                }
+
+                _ => {}
+            }
+        });
+    }
+
+    /// Render `frames` frames as fast as possible and return their timings.
+    ///
+    /// The swap chain uses `PresentMode::Immediate` so vsync doesn't cap the frame
+    /// rate, and the device is polled after each submit so every sample covers the
+    /// GPU work of its frame, not just the command recording.
+    pub fn benchmark(mut self, frames: u32) -> FrameStats {
+        self.present_mode = wgpu::PresentMode::Immediate;
+
+        let mut event_loop = EventLoop::new();
+        let window = WindowBuilder::new()
+            .with_inner_size(self.size)
+            .build(&event_loop)
+            .unwrap();
+
+        let mut state = block_on(State::new(&window, self));
+        let mut frame_times = Vec::with_capacity(frames as usize);
+        let mut timer = Timer::new();
+        timer.reset();
+
+        event_loop.run_return(|event, _, control_flow| {
+            *control_flow = ControlFlow::Poll;
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+
+                Event::MainEventsCleared => {
+                    state.update(timer.delta_time());
+                    match state.render() {
+                        Ok(_) => {}
+                        Err(wgpu::SwapChainError::Lost) => {
+                            warn!("Swap chain lost, recreating it");
+                            state.resize(state.size);
+                        }
+                        Err(wgpu::SwapChainError::OutOfMemory) => {
+                            error!("Out of memory getting the next frame, exiting");
+                            *control_flow = ControlFlow::Exit;
+                        }
+                        Err(e) => warn!("Dropped a frame: {:?}", e),
+                    }
+                    state.device.poll(wgpu::Maintain::Wait);
+
+                    timer.tick();
+                    frame_times.push(timer.delta_time());
+                    if frame_times.len() >= frames as usize {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+
+                _ => {}
+            }
+        });
+
+        FrameStats::from_frame_times(&frame_times)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct Uniforms {
+    view_proj: [[f32; 4]; 4],
+}
+
+unsafe impl bytemuck::Zeroable for Uniforms {}
+unsafe impl bytemuck::Pod for Uniforms {}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct EnvironmentUniforms {
+    // std140 pads each array element to a vec4.
+    sh: [[f32; 4]; 9],
+}
+
+unsafe impl bytemuck::Zeroable for EnvironmentUniforms {}
+unsafe impl bytemuck::Pod for EnvironmentUniforms {}
+
+/// What the scene pipelines are built from, kept to rebuild them when the sample
+/// count changes.
+struct ScenePipelineSources {
+    layout: wgpu::PipelineLayout,
+    vs_module: wgpu::ShaderModule,
+    fs_module: wgpu::ShaderModule,
+    normal_vs_module: wgpu::ShaderModule,
+    normal_fs_module: wgpu::ShaderModule,
+    handedness_vs_module: wgpu::ShaderModule,
+    handedness_fs_module: wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    // Of the depth texture, the configured one or the fallback.
+    depth_format: wgpu::TextureFormat,
+    deinterleave_vertices: bool,
+    // Set when the device takes push constants.
+    push: Option<PushSources>,
+    instanced: InstancedSources,
+    // The `Application::with_pipeline` vertex shaders by name.
+    custom_vs_modules: Vec<(String, wgpu::ShaderModule)>,
+    // Set when `Application::with_depth_prepass` is on.
+    prepass: Option<PrepassSources>,
+}
+
+/// What the depth prepass pipelines are built from.
+struct PrepassSources {
+    // Reads the position alone, see `Vertex::position_desc`.
+    vs_module: wgpu::ShaderModule,
+    fs_module: wgpu::ShaderModule,
+}
+
+/// The depth prepass and the lit pipelines shading after it.
+struct PrepassPipelines {
+    depth: wgpu::RenderPipeline,
+    depth_double_sided: wgpu::RenderPipeline,
+    // `ScenePipelines::lit` and `lit_double_sided` testing for equal depth
+    // without writing it.
+    lit: wgpu::RenderPipeline,
+    lit_double_sided: wgpu::RenderPipeline,
+}
+
+impl PrepassPipelines {
+    fn depth(&self, double_sided: bool) -> &wgpu::RenderPipeline {
+        if double_sided { &self.depth_double_sided } else { &self.depth }
+    }
+
+    fn lit(&self, double_sided: bool) -> &wgpu::RenderPipeline {
+        if double_sided { &self.lit_double_sided } else { &self.lit }
+    }
+}
+
+/// What the lit pipeline drawing `InstanceBatches` is built from.
+struct InstancedSources {
+    // The scene's layout with `object_layout` in place of the object's.
+    layout: wgpu::PipelineLayout,
+    object_layout: wgpu::BindGroupLayout,
+    vs_module: wgpu::ShaderModule,
+}
+
+/// What the lit pipelines taking the object from push constants are built from.
+struct PushSources {
+    // The scene's layout with an empty group in place of the object's.
+    layout: wgpu::PipelineLayout,
+    vs_module: wgpu::ShaderModule,
+    // Bound at group 2 while drawing with them.
+    empty_bind_group: wgpu::BindGroup,
+}
+
+/// The forward scene pipeline of every `DebugView`. They share their inputs, so
+/// they can be swapped without touching the buffers.
+struct ScenePipelines {
+    lit: wgpu::RenderPipeline,
+    // `lit` without back face culling, for double-sided items.
+    lit_double_sided: wgpu::RenderPipeline,
+    // `lit` and `lit_double_sided` with the object pushed, when the device can.
+    pushed: Option<(wgpu::RenderPipeline, wgpu::RenderPipeline)>,
+    // `lit` for items built with instance storage.
+    instanced: wgpu::RenderPipeline,
+    // `lit` and `lit_double_sided` of each `Application::with_pipeline` by name.
+    custom: HashMap<String, (wgpu::RenderPipeline, wgpu::RenderPipeline)>,
+    prepass: Option<PrepassPipelines>,
+    normals: wgpu::RenderPipeline,
+    tangent_handedness: wgpu::RenderPipeline,
+}
+
+impl ScenePipelines {
+    /// The pipeline for `view`, drawing back faces too for a `double_sided` item
+    /// in the lit view.
+    fn get(&self, view: DebugView, double_sided: bool) -> &wgpu::RenderPipeline {
+        match view {
+            DebugView::Lit if double_sided => &self.lit_double_sided,
+            DebugView::Lit => &self.lit,
+            DebugView::Normals => &self.normals,
+            DebugView::TangentHandedness => &self.tangent_handedness,
+        }
+    }
+
+    /// `get` for an item drawn with the pipeline registered as `name`, `None`
+    /// outside the lit view or when there's no such pipeline.
+    fn get_custom(&self, view: DebugView, name: &str, double_sided: bool) -> Option<&wgpu::RenderPipeline> {
+        match (view, self.custom.get(name)) {
+            (DebugView::Lit, Some((lit, lit_double_sided))) => Some(if double_sided { lit_double_sided } else { lit }),
+            _ => None,
+        }
+    }
+
+    /// `get` for drawing with `draw_item_pushed`, `None` when there's no such
+    /// pipeline for `view` or the device can't push constants.
+    fn get_pushed(&self, view: DebugView, double_sided: bool) -> Option<&wgpu::RenderPipeline> {
+        match (view, &self.pushed) {
+            (DebugView::Lit, Some((lit, lit_double_sided))) => Some(if double_sided { lit_double_sided } else { lit }),
+            _ => None,
+        }
+    }
+}
+
+impl ScenePipelineSources {
+    fn build(&self, device: &wgpu::Device, error_scopes: &ErrorScopes, sample_count: u32) -> anyhow::Result<ScenePipelines> {
+        let vertex_layouts = if self.deinterleave_vertices { Vertex::deinterleaved_desc() } else { vec![Vertex::desc()] };
+
+        let build_with = |layout, label, vs_module, fs_module, cull_mode| {
+            let mut builder = PipelineBuilder::new(layout, vs_module, fs_module, self.format)
+                .label(label)
+                .cull_mode(cull_mode)
+                .depth_format(Some(self.depth_format))
+                .sample_count(sample_count);
+            for vertex_layout in &vertex_layouts {
+                builder = builder.vertex_layout(vertex_layout.clone());
+            }
+            builder.try_build(device, error_scopes)
+        };
+        let build = |label, vs_module, fs_module, cull_mode| build_with(&self.layout, label, vs_module, fs_module, cull_mode);
+
+        let pushed = match &self.push {
+            Some(push) => Some((
+                build_with(&push.layout, "Pushed Render Pipeline", &push.vs_module, &self.fs_module, wgpu::CullMode::Back)?,
+                build_with(&push.layout, "Pushed Double-Sided Render Pipeline", &push.vs_module, &self.fs_module, wgpu::CullMode::None)?,
+            )),
+            None => None,
+        };
+
+        let prepass = match &self.prepass {
+            Some(prepass) => {
+                // The position alone, interleaved or from its own buffer in slot 0.
+                let position_layout = if self.deinterleave_vertices { Vertex::deinterleaved_desc().remove(0) } else { Vertex::position_desc() };
+                let build_depth = |label, cull_mode| {
+                    PipelineBuilder::new(&self.layout, &prepass.vs_module, &prepass.fs_module, self.format)
+                        .label(label)
+                        .vertex_layout(position_layout.clone())
+                        .color_write_mask(wgpu::ColorWrite::empty())
+                        .cull_mode(cull_mode)
+                        .depth_format(Some(self.depth_format))
+                        .sample_count(sample_count)
+                        .try_build(device, error_scopes)
+                };
+                let build_equal = |label, cull_mode| {
+                    let mut builder = PipelineBuilder::new(&self.layout, &self.vs_module, &self.fs_module, self.format)
+                        .label(label)
+                        .cull_mode(cull_mode)
+                        .depth_format(Some(self.depth_format))
+                        .depth_compare(wgpu::CompareFunction::Equal)
+                        .depth_write(false)
+                        .sample_count(sample_count);
+                    for vertex_layout in &vertex_layouts {
+                        builder = builder.vertex_layout(vertex_layout.clone());
+                    }
+                    builder.try_build(device, error_scopes)
+                };
+                Some(PrepassPipelines {
+                    depth: build_depth("Depth Prepass Pipeline", wgpu::CullMode::Back)?,
+                    depth_double_sided: build_depth("Double-Sided Depth Prepass Pipeline", wgpu::CullMode::None)?,
+                    lit: build_equal("Equal Depth Render Pipeline", wgpu::CullMode::Back)?,
+                    lit_double_sided: build_equal("Equal Depth Double-Sided Render Pipeline", wgpu::CullMode::None)?,
+                })
+            }
+            None => None,
+        };
+
+        let mut custom = HashMap::new();
+        for (name, vs_module) in &self.custom_vs_modules {
+            custom.insert(name.clone(), (
+                build("Custom Render Pipeline", vs_module, &self.fs_module, wgpu::CullMode::Back)?,
+                build("Custom Double-Sided Render Pipeline", vs_module, &self.fs_module, wgpu::CullMode::None)?,
+            ));
+        }
+
+        Ok(ScenePipelines {
+            lit: build("Render Pipeline", &self.vs_module, &self.fs_module, wgpu::CullMode::Back)?,
+            lit_double_sided: build("Double-Sided Render Pipeline", &self.vs_module, &self.fs_module, wgpu::CullMode::None)?,
+            pushed,
+            instanced: build_with(
+                &self.instanced.layout,
+                "Instanced Render Pipeline",
+                &self.instanced.vs_module,
+                &self.fs_module,
+                wgpu::CullMode::Back,
+            )?,
+            custom,
+            prepass,
+            normals: build("Normal Pipeline", &self.normal_vs_module, &self.normal_fs_module, wgpu::CullMode::Back)?,
+            tangent_handedness: build(
+                "Handedness Pipeline",
+                &self.handedness_vs_module,
+                &self.handedness_fs_module,
+                wgpu::CullMode::Back,
+            )?,
+        })
+    }
+}
+
+/// The layout of the diffuse texture bound at set 0 of the scene pipelines, which
+/// a `Material` replaces, see `create_material_bind_group`.
+fn create_texture_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(
+        &wgpu::BindGroupLayoutDescriptor {
+            label: Some("texture_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        }
+    )
+}
+
+/// A bind group for `layout`, the diffuse texture's, sampling `material`'s color
+/// from a single texel in place of the texture.
+fn create_material_bind_group(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    material: &Material,
+) -> wgpu::BindGroup {
+    let texel = image::RgbaImage::from_pixel(1, 1, image::Rgba(material.base_color));
+    let texture = Texture::from_image(device, queue, &image::DynamicImage::ImageRgba8(texel), Some("material_texture")).unwrap();
+    device.create_bind_group(
+        &wgpu::BindGroupDescriptor {
+            label: Some("material_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        }
+    )
+}
+
+/// Render items of `model`'s meshes, labelled as the `index`th model's and placed
+/// by its `Model::world_transform`. With a material override they all share one
+/// bind group of it, see `RenderItem::with_material`. Without one they're drawn
+/// with the scene's diffuse texture like any other item.
+fn model_items(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    object_layout: &wgpu::BindGroupLayout,
+    texture_layout: &wgpu::BindGroupLayout,
+    model: &Model,
+    index: usize,
+    deinterleave_vertices: bool,
+) -> Vec<RenderItem> {
+    let transform = model.world_transform();
+    // One bind group for all the model's meshes, so they're drawn together.
+    let material = model.material_override
+        .map(|material| Rc::new(create_material_bind_group(device, queue, texture_layout, &material)));
+    model.meshes
+        .iter()
+        .enumerate()
+        .map(|(i, mesh)| {
+            let label = format!("model {} mesh {}", index, i);
+            let mut item = if deinterleave_vertices {
+                RenderItem::from_mesh_deinterleaved(device, object_layout, mesh, &label)
+            } else {
+                RenderItem::from_mesh(device, object_layout, mesh, &label)
+            };
+            item.transform = transform;
+            item.write_transform(queue);
+            match &material {
+                Some(material) => item.with_material(Rc::clone(material)),
+                None => item,
+            }
+        })
+        .collect()
+}
+
+/// The sRGB counterpart of `format`, for the formats swap chains come in.
+fn srgb_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        format => format,
+    }
+}
+
+/// Choose the swap chain configuration, kept in `State::swap_chain_desc` and only
+/// resized afterwards.
+///
+/// wgpu 0.7 can't list what a surface supports, it only names a preferred format,
+/// so the negotiation is limited to what it exposes:
+/// - format: `Application::surface_format` when set, otherwise the sRGB version
+///   of the preferred format, since the shaders write linear colors and rely on
+///   the target encoding them.
+/// - present mode: `Application::present_mode`. An unsupported one is replaced by
+///   Fifo inside wgpu, with a warning in its own log.
+/// - alpha: always opaque, wgpu 0.7 has no composite alpha setting.
+fn negotiate_swap_chain(
+    adapter: &wgpu::Adapter,
+    surface: &wgpu::Surface,
+    app: &Application,
+    size: PhysicalSize<u32>,
+) -> wgpu::SwapChainDescriptor {
+    let preferred = adapter.get_swap_chain_preferred_format(surface);
+    let format = match app.surface_format {
+        Some(format) => format,
+        None => srgb_format(preferred),
+    };
+    if !format.describe().srgb {
+        warn!("Swap chain format {:?} isn't sRGB, the image will look too dark", format);
+    }
+
+    let swap_chain_desc = wgpu::SwapChainDescriptor {
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        format,
+        width: size.width,
+        height: size.height,
+        present_mode: app.present_mode,
+    };
+    info!(
+        "Swap chain format {:?} (preferred {:?}), present mode {:?}, opaque alpha",
+        swap_chain_desc.format, preferred, swap_chain_desc.present_mode,
+    );
+    swap_chain_desc
+}
+
+struct State {
+    surface: wgpu::Surface,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    swap_chain_desc: wgpu::SwapChainDescriptor,
+    swap_chain: wgpu::SwapChain,
+    size: PhysicalSize<u32>,
+    scene_pipelines: ScenePipelines,
+    debug_view: DebugView,
+    scene_pipeline_sources: ScenePipelineSources,
+    error_scopes: ErrorScopes,
+    sample_count: u32,
+    clear_mode: ClearMode,
+    // Replaces the forward scene pass when set.
+    deferred: Option<Deferred>,
+    // The scene is drawn here and resolved into its usual target while MSAA is on.
+    msaa_target: Option<Texture>,
+    depth_texture: Texture,
+    // The scene is rendered here instead of the frame while a post-process is on.
+    scene_target: Texture,
+    // Holds the scene before tonemapping when HDR is on. The scene pipelines are
+    // built for its format then, so it can't be toggled at runtime.
+    hdr_target: Option<Texture>,
+    tone_map: ToneMapPass,
+    bloom: Bloom,
+    bloom_enabled: bool,
+    // Holds the image FXAA reads, either the scene itself or the bloom composite.
+    fxaa_target: Texture,
+    fxaa: Fxaa,
+    fxaa_enabled: bool,
+    diffuse_bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    environment_bind_group: wgpu::BindGroup,
+    render_items: Vec<RenderItem>,
+    // Indices into `render_items` for the scene pass, see `draw_order`.
+    draw_order: Vec<usize>,
+    draw_sorting: bool,
+    // Of the items drawn once per equal mesh in the lit view.
+    batches: InstanceBatches,
+    object_bind_group_layout: wgpu::BindGroupLayout,
+    deinterleave_vertices: bool,
+    primitive: Option<Primitive>,
+    // Buffers of the primitives shown so far, a handful at most, never evicted.
+    primitive_cache: MeshCache,
+    // Of the meshes shown, by label, for the mesh report key.
+    mesh_reports: Vec<(String, MeshReport)>,
+    // Set when the window title should change, applied by the event loop.
+    pending_title: Option<String>,
+    // The latest size the window was resized to, applied before the next frame.
+    pending_size: Option<PhysicalSize<u32>>,
+    // Moves the first render item while there is no picking to choose one.
+    gizmo: Gizmo,
+    gizmo_enabled: bool,
+    terrain: Option<(TerrainMaterial, RenderItem)>,
+    // Translucent meshes over the forward scene, `None` when off or deferred.
+    oit: Option<Oit>,
+    // Drawing the first render item in place of the scene pipelines, and the
+    // seconds it has been playing.
+    morph: Option<(MorphTarget, f32)>,
+    // Around the gizmo's item, `None` when the depth texture has no stencil.
+    outline: Option<Outline>,
+    outline_enabled: bool,
+    // With one edge item per render item, in the same order.
+    wireframe: Wireframe,
+    wireframe_items: Vec<RenderItem>,
+    wireframe_enabled: bool,
+    debug_lines: DebugLines,
+    ray_debug_enabled: bool,
+    clicked_ray: Option<Ray>,
+    hud: Hud,
+    help_enabled: bool,
+    frame_time: f32,
+    redraw_mode: RedrawMode,
+    camera: Camera,
+    // Where `KeyBindings::reset_camera` puts the camera back.
+    camera_home: Decomposed<Vector3<f32>, Quaternion<f32>>,
+    controller: Controller,
+}
+
+impl State {
+    async fn new(window: &Window, app: Application) -> Self {
+        let size = window.inner_size();
+        gpu_memory::set_enabled(app.gpu_memory_tracking);
+
+        // wgpu 0.7 has no instance flags, the Vulkan loader picks the layers up
+        // from the environment. Setting it is only sound while no other thread
+        // can read it: this runs before the instance exists, and the app starts
+        // no threads of its own before it, `ResourceLoader` workers included.
+        if app.validation {
+            info!("Enabling the Vulkan validation layers");
+            let mut layers: Vec<PathBuf> = std::env::var_os("VK_INSTANCE_LAYERS")
+                .map(|layers| std::env::split_paths(&layers).collect())
+                .unwrap_or_default();
+            if !layers.iter().any(|layer| layer.as_os_str() == VALIDATION_LAYER) {
+                layers.push(PathBuf::from(VALIDATION_LAYER));
+                match std::env::join_paths(layers) {
+                    Ok(layers) => std::env::set_var("VK_INSTANCE_LAYERS", layers),
+                    Err(e) => warn!("Leaving the validation layers out: {}", e),
+                }
+            }
+        }
         let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
-        let (adapter, device, queue) = block_on(async {
-            let adapter = instance.request_adapter(
-                &Default::default(),
-            ).await.unwrap();
+        let surface = unsafe { instance.create_surface(window) };
+        let adapter = instance.request_adapter(
+            &wgpu::RequestAdapterOptions {
+                power_preference: app.power_preference,
+                compatible_surface: Some(&surface),
+            },
+        ).await.unwrap();
+        let adapter_info = adapter.get_info();
+        info!("Using adapter {} ({:?}, {:?})", adapter_info.name, adapter_info.device_type, adapter_info.backend);
+
+        if let Some(path) = &app.trace_path {
+            info!("Tracing wgpu calls to {}", path.display());
+            std::fs::create_dir_all(path).unwrap();
+        }
+        let push_constants = app.push_constants
+            && adapter.features().contains(wgpu::Features::PUSH_CONSTANTS)
+            && adapter.limits().max_push_constant_size >= RenderItem::PUSH_CONSTANT_SIZE;
+        info!("Object data {}", if push_constants { "pushed per draw" } else { "bound from uniform buffers" });
+        let device_desc = if push_constants {
+            wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::PUSH_CONSTANTS,
+                limits: wgpu::Limits {
+                    max_push_constant_size: RenderItem::PUSH_CONSTANT_SIZE,
+                    ..Default::default()
+                },
+            }
+        } else {
+            Default::default()
+        };
+        let (device, queue) = adapter.request_device(
+            &device_desc,
+            app.trace_path.as_deref(),
+        ).await.unwrap();
+        let error_scopes = ErrorScopes::install(&device);
 
-            let (device, queue) = adapter.request_device(
-                &Default::default(),
-                None,
-            ).await.unwrap();
+        let swap_chain_desc = negotiate_swap_chain(&adapter, &surface, &app, size);
+        let swap_chain = device.create_swap_chain(&surface, &swap_chain_desc);
 
-            (adapter, device, queue)
+        let diffuse_texture = Texture::from_bytes(
+            &device,
+            &queue,
+            include_bytes!("resources/textures/happy-tree.png"),
+            "happy-tree.png",
+        ).unwrap();
+
+        let texture_bind_group_layout = create_texture_bind_group_layout(&device);
+
+        let diffuse_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("diffuse_bind_group"),
+                layout: &texture_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+                    },
+                ],
+            }
+        );
+
+        let uniforms = Uniforms {
+            view_proj: app.camera.get_view_proj().into(),
+        };
+        let uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[uniforms]),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            }
+        );
+
+        let uniform_bind_group_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("uniform_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            }
+        );
+
+        let uniform_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("uniform_bind_group"),
+                layout: &uniform_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            }
+        );
+
+        let environment_uniforms = EnvironmentUniforms {
+            sh: app.environment_sh.map(|c| [c.x, c.y, c.z, 0.0]),
+        };
+        let environment_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Environment Buffer"),
+                contents: bytemuck::cast_slice(&[environment_uniforms]),
+                usage: wgpu::BufferUsage::UNIFORM,
+            }
+        );
+
+        let environment_bind_group_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("environment_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            }
+        );
+
+        let environment_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("environment_bind_group"),
+                layout: &environment_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: environment_buffer.as_entire_binding(),
+                    },
+                ],
+            }
+        );
+
+        let object_bind_group_layout = RenderItem::create_object_layout(&device);
+
+        let render_pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[
+                    &texture_bind_group_layout,
+                    &uniform_bind_group_layout,
+                    &object_bind_group_layout,
+                    &environment_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let create_shader_module = |desc| error_scopes.create_shader_module(&device, &desc).unwrap();
+        let vs_module = create_shader_module(wgpu::include_spirv!("resources/shaders/shader.vert.spv"));
+        let fs_module = create_shader_module(wgpu::include_spirv!("resources/shaders/shader.frag.spv"));
+
+        let push = if push_constants {
+            let empty_layout = device.create_bind_group_layout(
+                &wgpu::BindGroupLayoutDescriptor {
+                    label: Some("empty_bind_group_layout"),
+                    entries: &[],
+                }
+            );
+            let empty_bind_group = device.create_bind_group(
+                &wgpu::BindGroupDescriptor {
+                    label: Some("empty_bind_group"),
+                    layout: &empty_layout,
+                    entries: &[],
+                }
+            );
+            let layout = device.create_pipeline_layout(
+                &wgpu::PipelineLayoutDescriptor {
+                    label: Some("Pushed Render Pipeline Layout"),
+                    bind_group_layouts: &[
+                        &texture_bind_group_layout,
+                        &uniform_bind_group_layout,
+                        &empty_layout,
+                        &environment_bind_group_layout,
+                    ],
+                    push_constant_ranges: &[RenderItem::push_constant_range()],
+                }
+            );
+            Some(PushSources {
+                layout,
+                vs_module: create_shader_module(wgpu::include_spirv!("resources/shaders/shader_push.vert.spv")),
+                empty_bind_group,
+            })
+        } else {
+            None
+        };
+
+        let instanced_object_layout = RenderItem::create_instanced_object_layout(&device);
+        let instanced = InstancedSources {
+            layout: device.create_pipeline_layout(
+                &wgpu::PipelineLayoutDescriptor {
+                    label: Some("Instanced Render Pipeline Layout"),
+                    bind_group_layouts: &[
+                        &texture_bind_group_layout,
+                        &uniform_bind_group_layout,
+                        &instanced_object_layout,
+                        &environment_bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                }
+            ),
+            object_layout: instanced_object_layout,
+            vs_module: create_shader_module(wgpu::include_spirv!("resources/shaders/instanced.vert.spv")),
+        };
+
+        let custom_vs_modules = app.pipelines
+            .iter()
+            .filter_map(|(name, desc)| match error_scopes.create_shader_module(&device, desc) {
+                Ok(module) => Some((name.clone(), module)),
+                Err(e) => {
+                    warn!("Leaving pipeline {} out: {:#}", name, e);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let sample_count = supported_sample_count(app.sample_count);
+        if sample_count != app.sample_count {
+            warn!("{}x MSAA may not be supported, using {}x", app.sample_count, sample_count);
+        }
+        let depth_texture = error_scopes.scoped(|| {
+            Texture::create_depth_texture_with_format(&device, &swap_chain_desc, sample_count, app.depth_format, "depth_texture")
         });
+        let (depth_texture, depth_format) = match depth_texture {
+            Ok(depth_texture) => (depth_texture, app.depth_format),
+            Err(error) => {
+                warn!("Depth format {:?} unavailable, using {:?}: {:#}", app.depth_format, Texture::DEPTH_FORMAT, error);
+                let depth_texture = Texture::create_depth_texture(&device, &swap_chain_desc, sample_count, "depth_texture");
+                (depth_texture, Texture::DEPTH_FORMAT)
+            }
+        };
+
+        let scene_pipeline_sources = ScenePipelineSources {
+            layout: render_pipeline_layout,
+            vs_module,
+            fs_module,
+            normal_vs_module: create_shader_module(wgpu::include_spirv!("resources/shaders/normal.vert.spv")),
+            normal_fs_module: create_shader_module(wgpu::include_spirv!("resources/shaders/normal.frag.spv")),
+            handedness_vs_module: create_shader_module(wgpu::include_spirv!("resources/shaders/handedness.vert.spv")),
+            handedness_fs_module: create_shader_module(wgpu::include_spirv!("resources/shaders/handedness.frag.spv")),
+            format: if app.hdr_enabled { HDR_FORMAT } else { swap_chain_desc.format },
+            depth_format,
+            deinterleave_vertices: app.deinterleave_vertices,
+            push,
+            instanced,
+            custom_vs_modules,
+            prepass: if app.depth_prepass {
+                Some(PrepassSources {
+                    vs_module: create_shader_module(wgpu::include_spirv!("resources/shaders/depth_prepass.vert.spv")),
+                    fs_module: create_shader_module(wgpu::include_spirv!("resources/shaders/depth_prepass.frag.spv")),
+                })
+            } else {
+                None
+            },
+        };
+        let scene_pipelines = scene_pipeline_sources.build(&device, &error_scopes, sample_count).unwrap();
+        let msaa_target = Self::create_msaa_target(&device, &swap_chain_desc, scene_pipeline_sources.format, sample_count);
+
+        let deferred = if app.deferred_enabled {
+            let layouts = DeferredLayouts {
+                texture: &texture_bind_group_layout,
+                uniform: &uniform_bind_group_layout,
+                object: &object_bind_group_layout,
+                environment: &environment_bind_group_layout,
+            };
+            Some(Deferred::new(&device, &swap_chain_desc, scene_pipeline_sources.format, layouts, app.deinterleave_vertices, app.point_lights))
+        } else {
+            None
+        };
+
+        let gizmo = Gizmo::new(&device, swap_chain_desc.format, &uniform_bind_group_layout, &object_bind_group_layout);
+        let terrain = app.terrain.as_ref().and_then(|terrain| {
+            let material = TerrainMaterial::new(
+                &device,
+                &queue,
+                terrain,
+                scene_pipeline_sources.format,
+                depth_format,
+                sample_count,
+                app.deinterleave_vertices,
+                &uniform_bind_group_layout,
+                &object_bind_group_layout,
+                &environment_bind_group_layout,
+            );
+            let item = if app.deinterleave_vertices {
+                RenderItem::from_mesh_deinterleaved(&device, &object_bind_group_layout, &terrain.mesh, "terrain")
+            } else {
+                RenderItem::from_mesh(&device, &object_bind_group_layout, &terrain.mesh, "terrain")
+            };
+            match material {
+                Ok(material) => Some((material, item)),
+                Err(e) => {
+                    warn!("Leaving the terrain out: {:#}", e);
+                    None
+                }
+            }
+        });
+        let oit = match (app.oit_enabled, &deferred) {
+            (true, Some(_)) => {
+                warn!("Translucent meshes aren't drawn by the deferred path, OIT is off");
+                None
+            }
+            (true, None) if !app.translucent.is_empty() => Some(Oit::new(
+                &device,
+                &queue,
+                &swap_chain_desc,
+                scene_pipeline_sources.format,
+                depth_format,
+                sample_count,
+                app.deinterleave_vertices,
+                &uniform_bind_group_layout,
+                &object_bind_group_layout,
+                &environment_bind_group_layout,
+                &app.translucent,
+            )),
+            _ => None,
+        };
+        let morph = match (app.morph_target.as_ref(), app.meshs.first()) {
+            (Some(target), Some(base)) if target.vertices.len() == base.vertices.len() => Some((
+                MorphTarget::new(
+                    &device,
+                    base,
+                    target,
+                    &diffuse_texture,
+                    scene_pipeline_sources.format,
+                    depth_format,
+                    sample_count,
+                    app.deinterleave_vertices,
+                    &uniform_bind_group_layout,
+                    &object_bind_group_layout,
+                    &environment_bind_group_layout,
+                ),
+                0.0,
+            )),
+            (Some(target), base) => {
+                warn!(
+                    "Leaving the morph out: the target has {} vertices, the first mesh {}",
+                    target.vertices.len(),
+                    base.map_or(0, |base| base.vertices.len()),
+                );
+                None
+            }
+            _ => None,
+        };
+        let outline = if Texture::has_stencil(depth_format) {
+            Some(Outline::new(
+                &device,
+                scene_pipeline_sources.format,
+                depth_format,
+                sample_count,
+                app.deinterleave_vertices,
+                &uniform_bind_group_layout,
+                &object_bind_group_layout,
+                app.outline_color,
+                app.outline_width,
+            ))
+        } else {
+            if app.outline_enabled {
+                warn!("Depth format {:?} has no stencil, outlines are off", depth_format);
+            }
+            None
+        };
+
+        let wireframe = Wireframe::new(
+            &device,
+            scene_pipeline_sources.format,
+            depth_format,
+            sample_count,
+            &uniform_bind_group_layout,
+            &object_bind_group_layout,
+            [0.0, 0.0, 0.0, 1.0],
+            WIREFRAME_DEPTH_BIAS,
+        );
+        let scene = app.scene_directory.as_ref().and_then(|dir| {
+            match Scene::from_directory(&device, &queue, &object_bind_group_layout, dir, app.deinterleave_vertices) {
+                Ok(scene) => Some(scene),
+                Err(e) => {
+                    warn!("Leaving the scene out: {:#}", e);
+                    None
+                }
+            }
+        });
+        let (scene_items, scene_labels, scene_meshes, scene_bounds) = match scene {
+            Some(Scene { items, labels, meshes, bounds }) => (items, labels, meshes, bounds),
+            None => (Vec::new(), Vec::new(), Vec::new(), Aabb::empty()),
+        };
+        // Every item's mesh by index, `meshs`, the scene's, then the models'.
+        let model_meshes = app.models.iter().flat_map(|model| &model.meshes);
+        let all_meshes: Vec<&Mesh> = app.meshs.iter().chain(&scene_meshes).chain(model_meshes).collect();
+        let model_labels = app.models
+            .iter()
+            .enumerate()
+            .flat_map(|(i, model)| (0..model.meshes.len()).map(move |j| format!("model {} mesh {}", i, j)));
+        let labels: Vec<String> = (0..app.meshs.len()).map(|i| format!("mesh {}", i)).chain(scene_labels).chain(model_labels).collect();
+
+        let wireframe_items = all_meshes
+            .iter()
+            .zip(&labels)
+            .map(|(mesh, label)| Wireframe::edges(&device, &object_bind_group_layout, mesh, &format!("{} edges", label)))
+            .collect();
+
+        let mut debug_lines = DebugLines::new(&device, swap_chain_desc.format, &uniform_bind_group_layout);
+        debug_lines.ray_length = app.ray_length;
+
+        let mesh_reports = all_meshes
+            .iter()
+            .zip(&labels)
+            .map(|(mesh, label)| (label.clone(), mesh.report()))
+            .collect();
+        let mut render_items = app.meshs
+            .iter()
+            .enumerate()
+            .map(|(i, mesh)| {
+                let label = format!("mesh {}", i);
+                let item = if app.deinterleave_vertices {
+                    RenderItem::from_mesh_deinterleaved(&device, &object_bind_group_layout, mesh, &label)
+                } else {
+                    RenderItem::from_mesh(&device, &object_bind_group_layout, mesh, &label)
+                };
+                match app.mesh_pipelines.get(&i) {
+                    Some(name) if scene_pipelines.custom.contains_key(name) => item.with_pipeline(name),
+                    Some(name) => {
+                        warn!("No pipeline {}, drawing mesh {} with the default", name, i);
+                        item
+                    }
+                    None => item,
+                }
+            })
+            .collect::<Vec<_>>();
+        render_items.extend(scene_items);
+        for (i, model) in app.models.iter().enumerate() {
+            render_items.extend(model_items(&device, &queue, &object_bind_group_layout, &texture_bind_group_layout, model, i, app.deinterleave_vertices));
+        }
+        let draw_order = draw_order(&render_items, app.draw_sorting);
+        let mut batches = InstanceBatches::new(app.instancing_threshold, app.instance_culling);
+        // The morph draws the first item with its own pipeline.
+        let batched_meshes: Vec<Option<&Mesh>> = all_meshes
+            .iter()
+            .enumerate()
+            .map(|(i, &mesh)| Some(mesh).filter(|_| i > 0 || morph.is_none()))
+            .collect();
+        batches.rebuild(
+            &device,
+            &object_bind_group_layout,
+            &scene_pipeline_sources.instanced.object_layout,
+            &batched_meshes,
+            &render_items,
+            app.deinterleave_vertices,
+        );
+
+        let hud = Hud::new(&device, swap_chain_desc.format, app.hud_font_size, app.hud_color);
+
+        let scene_target = Texture::create_render_target(&device, size.width, size.height, swap_chain_desc.format, "scene_target");
+        let hdr_target = Texture::create_render_target(&device, size.width, size.height, HDR_FORMAT, "hdr_target");
+        let tone_map = ToneMapPass::new(&device, &hdr_target, swap_chain_desc.format, app.tone_map);
+        let hdr_target = if app.hdr_enabled { Some(hdr_target) } else { None };
+        let bloom = Bloom::new(
+            &device,
+            &scene_target,
+            swap_chain_desc.format,
+            size.width,
+            size.height,
+            app.bloom_threshold,
+            app.bloom_intensity,
+        );
+        let fxaa_target = Texture::create_render_target(&device, size.width, size.height, swap_chain_desc.format, "fxaa_target");
+        let fxaa = Fxaa::new(&device, &fxaa_target, swap_chain_desc.format, size.width, size.height);
+
+        let mut camera = app.camera;
+        let first_bounds = app.meshs
+            .first()
+            .map(Mesh::bounding_box)
+            .or(Some(scene_bounds))
+            .filter(|bounds| !bounds.is_empty());
+        let look_at = app.camera_look_at.or_else(|| first_bounds.map(|bounds| framing(&camera, &bounds)));
+        if let Some((eye, target)) = look_at {
+            camera.look_at(eye, target);
+        }
+        let camera_home = *camera.transform();
+
+        Self {
+            surface,
+            device,
+            queue,
+            swap_chain_desc,
+            swap_chain,
+            size,
+            scene_pipelines,
+            debug_view: app.debug_view,
+            scene_pipeline_sources,
+            error_scopes,
+            deferred,
+            sample_count,
+            clear_mode: app.clear_mode,
+            msaa_target,
+            depth_texture,
+            scene_target,
+            hdr_target,
+            tone_map,
+            bloom,
+            bloom_enabled: app.bloom_enabled,
+            fxaa_target,
+            fxaa,
+            fxaa_enabled: app.fxaa_enabled,
+            diffuse_bind_group,
+            uniform_buffer,
+            uniform_bind_group,
+            environment_bind_group,
+            render_items,
+            draw_order,
+            draw_sorting: app.draw_sorting,
+            batches,
+            object_bind_group_layout,
+            deinterleave_vertices: app.deinterleave_vertices,
+            primitive: None,
+            primitive_cache: MeshCache::new(app.deinterleave_vertices),
+            mesh_reports,
+            pending_title: None,
+            pending_size: None,
+            gizmo,
+            gizmo_enabled: app.gizmo_enabled,
+            terrain,
+            oit,
+            morph,
+            outline_enabled: app.outline_enabled && outline.is_some(),
+            outline,
+            wireframe,
+            wireframe_items,
+            wireframe_enabled: app.wireframe_enabled,
+            debug_lines,
+            ray_debug_enabled: app.ray_debug_enabled,
+            clicked_ray: None,
+            hud,
+            help_enabled: app.help_enabled,
+            frame_time: 0.0,
+            redraw_mode: app.redraw_mode,
+            camera,
+            camera_home,
+            controller: Controller {
+                bindings: app.key_bindings,
+                ..Controller::new(2.0)
+            },
+        }
+    }
+
+    fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        debug!("Resizing to {}x{}", new_size.width, new_size.height);
+        self.size = new_size;
+        self.swap_chain_desc.width = new_size.width;
+        self.swap_chain_desc.height = new_size.height;
+        self.swap_chain = self.device.create_swap_chain(&self.surface, &self.swap_chain_desc);
+        self.msaa_target = Self::create_msaa_target(&self.device, &self.swap_chain_desc, self.scene_pipeline_sources.format, self.sample_count);
+        self.depth_texture = Texture::create_depth_texture_with_format(
+            &self.device,
+            &self.swap_chain_desc,
+            self.sample_count,
+            self.scene_pipeline_sources.depth_format,
+            "depth_texture",
+        );
+        if let Some(deferred) = &mut self.deferred {
+            deferred.resize(&self.device, &self.swap_chain_desc);
+        }
+        if let Some(oit) = &mut self.oit {
+            oit.resize(&self.device, &self.swap_chain_desc);
+        }
+        self.scene_target = Texture::create_render_target(&self.device, new_size.width, new_size.height, self.swap_chain_desc.format, "scene_target");
+        if self.hdr_target.is_some() {
+            let hdr_target = Texture::create_render_target(&self.device, new_size.width, new_size.height, HDR_FORMAT, "hdr_target");
+            self.tone_map.resize(&self.device, &hdr_target);
+            self.hdr_target = Some(hdr_target);
+        }
+        self.bloom.resize(&self.device, &self.scene_target, new_size.width, new_size.height);
+        self.fxaa_target = Texture::create_render_target(&self.device, new_size.width, new_size.height, self.swap_chain_desc.format, "fxaa_target");
+        self.fxaa.resize(&self.device, &self.fxaa_target, new_size.width, new_size.height);
+    }
+
+    fn create_msaa_target(
+        device: &wgpu::Device,
+        swap_chain_desc: &wgpu::SwapChainDescriptor,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Option<Texture> {
+        if sample_count > 1 {
+            Some(Texture::create_multisampled_render_target(
+                device,
+                swap_chain_desc.width,
+                swap_chain_desc.height,
+                format,
+                sample_count,
+                "msaa_target",
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Rebuild the scene pipelines, the MSAA target and the depth texture for
+    /// `requested` samples per pixel, or the next lower supported count.
+    ///
+    /// The replaced pipelines and textures are dropped here, wgpu frees them once
+    /// the frames already submitted are done with them. If wgpu rejects any of the
+    /// new ones the current count is kept.
+    fn set_sample_count(&mut self, requested: u32) {
+        let sample_count = supported_sample_count(requested);
+        if sample_count != requested {
+            warn!("{}x MSAA may not be supported, using {}x", requested, sample_count);
+        }
+        if sample_count == self.sample_count {
+            return;
+        }
+
+        let device = &self.device;
+        let swap_chain_desc = &self.swap_chain_desc;
+        let format = self.scene_pipeline_sources.format;
+        let depth_format = self.scene_pipeline_sources.depth_format;
+        let rebuilt = self.scene_pipeline_sources.build(device, &self.error_scopes, sample_count).and_then(|pipelines| {
+            let targets = self.error_scopes.scoped(|| (
+                Self::create_msaa_target(device, swap_chain_desc, format, sample_count),
+                Texture::create_depth_texture_with_format(device, swap_chain_desc, sample_count, depth_format, "depth_texture"),
+            ))?;
+            Ok((pipelines, targets))
+        });
+        let (scene_pipelines, (msaa_target, depth_texture)) = match rebuilt {
+            Ok(rebuilt) => rebuilt,
+            Err(error) => {
+                warn!("Keeping {}x MSAA: {:#}", self.sample_count, error);
+                return;
+            }
+        };
+        self.scene_pipelines = scene_pipelines;
+        self.msaa_target = msaa_target;
+        self.depth_texture = depth_texture;
+        if let Some((material, _)) = &mut self.terrain {
+            material.set_sample_count(&self.device, sample_count);
+        }
+        if let Some(outline) = &mut self.outline {
+            outline.set_sample_count(&self.device, sample_count);
+        }
+        if let Some(oit) = &mut self.oit {
+            oit.set_sample_count(&self.device, &self.swap_chain_desc, sample_count);
+        }
+        if let Some((morph, _)) = &mut self.morph {
+            morph.set_sample_count(&self.device, sample_count);
+        }
+        self.wireframe.set_sample_count(&self.device, sample_count);
+        self.sample_count = sample_count;
+        info!("Switched to {}x MSAA", sample_count);
+    }
+
+    /// Step to the next count of `SUPPORTED_SAMPLE_COUNTS`.
+    fn cycle_sample_count(&mut self) {
+        let position = SUPPORTED_SAMPLE_COUNTS.iter().position(|&count| count == self.sample_count).unwrap_or(0);
+        self.set_sample_count(SUPPORTED_SAMPLE_COUNTS[(position + 1) % SUPPORTED_SAMPLE_COUNTS.len()]);
+    }
+
+    fn input(&mut self, event: &WindowEvent) -> bool {
+        if let WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } = event {
+            self.clicked_ray = Some(self.cursor_ray());
+        }
+
+        match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(keycode),
+                    ..
+                },
+                ..
+            } => self.key_pressed(*keycode) || self.controller.process_events(event),
+            // A press on a handle starts a gizmo drag instead of turning the camera.
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.gizmo_enabled => {
+                let ray = self.cursor_ray();
+                self.gizmo.begin_drag(&ray, &self.camera) || self.controller.process_events(event)
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } if self.gizmo.end_drag() => true,
+            WindowEvent::CursorMoved { .. } if self.gizmo.is_dragging() => {
+                self.controller.process_events(event);
+                let ray = self.cursor_ray();
+                self.gizmo.drag(&ray);
+                self.render_items[0].transform.disp = self.gizmo.position;
+                true
+            }
+            _ => self.controller.process_events(event),
+        }
+    }
+
+    /// Run the application action bound to `keycode`, returns whether there was one.
+    fn key_pressed(&mut self, keycode: VirtualKeyCode) -> bool {
+        let bindings = self.controller.bindings.clone();
+        if keycode == bindings.bloom {
+            self.bloom_enabled = !self.bloom_enabled;
+        } else if keycode == bindings.fxaa {
+            self.fxaa_enabled = !self.fxaa_enabled;
+        } else if keycode == bindings.gizmo {
+            self.gizmo_enabled = !self.gizmo_enabled && !self.render_items.is_empty();
+            self.gizmo.end_drag();
+        } else if keycode == bindings.outline {
+            if self.outline.is_some() {
+                self.outline_enabled = !self.outline_enabled;
+            } else {
+                warn!("Outlines need a depth format with a stencil, see Application::with_depth_format");
+            }
+        } else if keycode == bindings.wireframe {
+            self.wireframe_enabled = !self.wireframe_enabled;
+        } else if keycode == bindings.shake {
+            self.camera.add_shake(0.05, 0.5);
+        } else if keycode == bindings.reset_camera {
+            self.camera.set_transform(self.camera_home);
+        } else if keycode == bindings.floor_clamp {
+            self.camera.floor_clamp_enabled = !self.camera.floor_clamp_enabled;
+            self.camera.clamp_to_floor();
+        } else if keycode == bindings.msaa {
+            self.cycle_sample_count();
+        } else if keycode == bindings.normal_view {
+            self.debug_view = self.debug_view.toggle(DebugView::Normals);
+        } else if keycode == bindings.handedness_view {
+            self.debug_view = self.debug_view.toggle(DebugView::TangentHandedness);
+        } else if keycode == bindings.click_ray {
+            self.ray_debug_enabled = !self.ray_debug_enabled;
+        } else if keycode == bindings.tone_map {
+            self.tone_map.tone_map.operator = self.tone_map.tone_map.operator.next();
+        } else if keycode == bindings.gpu_report {
+            if gpu_memory::is_enabled() {
+                println!("{}", GpuReport::current());
+            } else {
+                warn!("GPU memory tracking is off, see Application::with_gpu_memory_tracking");
+            }
+        } else if keycode == bindings.mesh_report {
+            for (label, report) in &self.mesh_reports {
+                println!("{}:\n{}", label, report);
+            }
+        } else if keycode == bindings.help {
+            self.help_enabled = !self.help_enabled;
+        } else if let Some(primitive) = Primitive::from_key(keycode) {
+            self.show_primitive(primitive);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Replace the scene with `primitive` and frame the camera on it, only
+    /// rebuilding the buffers when it isn't already shown, and reusing those of
+    /// the primitives shown before from `primitive_cache`.
+    fn show_primitive(&mut self, primitive: Primitive) {
+        if self.primitive == Some(primitive) {
+            return;
+        }
+
+        let mesh = primitive.mesh();
+        let item = self.primitive_cache.get_or_upload(&self.device, &self.object_bind_group_layout, &mesh, primitive.name());
+        self.render_items = vec![item];
+        self.draw_order = draw_order(&self.render_items, self.draw_sorting);
+        self.batches.rebuild(
+            &self.device,
+            &self.object_bind_group_layout,
+            &self.scene_pipeline_sources.instanced.object_layout,
+            &[Some(&mesh)],
+            &self.render_items,
+            self.deinterleave_vertices,
+        );
+        self.wireframe_items = vec![Wireframe::edges(&self.device, &self.object_bind_group_layout, &mesh, primitive.name())];
+        // The target's vertices were paired with the mesh replaced.
+        self.morph = None;
+        self.mesh_reports = vec![(primitive.name().to_string(), mesh.report())];
+        self.gizmo.end_drag();
+
+        let (eye, target) = framing(&self.camera, &mesh.bounding_box());
+        self.camera.look_at(eye, target);
+        self.camera_home = *self.camera.transform();
+
+        self.primitive = Some(primitive);
+        self.pending_title = Some(format!("pbr-wgpu-demo - {}", primitive.name()));
+    }
+
+    fn cursor_ray(&self) -> Ray {
+        self.camera.screen_ray(self.controller.current_cursor, (self.size.width as f32, self.size.height as f32))
+    }
+
+    fn device_input(&mut self, event: &DeviceEvent) -> bool {
+        self.controller.process_device_event(event)
+    }
+
+    /// Whether the next frames differ without any new event, held keys moving the
+    /// camera, a shake or a morph playing, so `RedrawMode::Wait` has to keep drawing.
+    fn animating(&self) -> bool {
+        self.controller.has_input() || self.camera.is_shaking() || self.morph.is_some()
+    }
+
+    fn update(&mut self, duration: f32) {
+        self.frame_time = duration;
+        self.controller.update_all(&mut [&mut self.camera], duration);
+
+        let uniforms = Uniforms {
+            view_proj: self.camera.get_view_proj().into(),
+        };
+        self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        for item in &self.render_items {
+            item.write_transform(&self.queue);
+        }
+        self.batches.update(&self.queue, &self.render_items, &self.camera);
+
+        if self.gizmo_enabled {
+            self.gizmo.position = self.render_items[0].transform.disp;
+            self.gizmo.update(&self.queue, &self.camera);
+        }
+        if let Some((material, _)) = &self.terrain {
+            material.update(&self.queue);
+        }
+        if let Some((morph, time)) = &mut self.morph {
+            *time = (*time + duration) % MORPH_PERIOD;
+            morph.weight = 0.5 - 0.5 * (*time / MORPH_PERIOD * std::f32::consts::TAU).cos();
+            morph.update(&self.queue);
+        }
+        if let Some(outline) = self.outline.as_ref().filter(|_| self.outline_enabled) {
+            outline.update(&self.queue, self.size.width, self.size.height);
+        }
+        if self.wireframe_enabled {
+            // The edges follow the items the gizmo moves.
+            for (edges, item) in self.wireframe_items.iter_mut().zip(&self.render_items) {
+                edges.transform = item.transform;
+                edges.write_transform(&self.queue);
+            }
+            self.wireframe.update(&self.queue);
+        }
+
+        self.debug_lines.clear();
+        if let Some(ray) = self.clicked_ray.filter(|_| self.ray_debug_enabled) {
+            self.debug_lines.add_ray(&ray, [1.0, 0.0, 1.0, 1.0]);
+        }
+    }
+
+    fn render(&mut self) -> Result<(), wgpu::SwapChainError> {
+        let frame = self.swap_chain.get_current_frame()?.output;
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        });
+
+        // Each pass writes into the input of the next one, the last writes the frame.
+        let post_view = if self.fxaa_enabled { &self.fxaa_target.view } else { &frame.view };
+        let tone_mapped_view = if self.bloom_enabled { &self.scene_target.view } else { post_view };
+        let scene_view = match &self.hdr_target {
+            Some(hdr_target) => &hdr_target.view,
+            None => tone_mapped_view,
+        };
+
+        if let Some(deferred) = &self.deferred {
+            deferred.render(
+                &self.queue,
+                &mut encoder,
+                scene_view,
+                &self.render_items,
+                &self.diffuse_bind_group,
+                &self.uniform_bind_group,
+                &self.environment_bind_group,
+            );
+        } else {
+            let (attachment, resolve_target) = match &self.msaa_target {
+                Some(msaa_target) => (&msaa_target.view, Some(scene_view)),
+                None => (scene_view, None),
+            };
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[
+                    wgpu::RenderPassColorAttachmentDescriptor {
+                        attachment,
+                        resolve_target,
+                        ops: wgpu::Operations {
+                            load: self.clear_mode.color_load(),
+                            store: true,
+                        },
+                    }
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                    attachment: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: self.clear_mode.depth_load(),
+                        store: true,
+                    }),
+                    // Only the outline uses it, starting from nothing marked each frame.
+                    stencil_ops: if Texture::has_stencil(self.scene_pipeline_sources.depth_format) {
+                        Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(0),
+                            store: true,
+                        })
+                    } else {
+                        None
+                    },
+                }),
+            });
+
+            render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
+            render_pass.set_bind_group(3, &self.environment_bind_group, &[]);
+            let push = self.scene_pipeline_sources.push.as_ref();
+            // Only switched when the next item in `draw_order` needs another.
+            let mut bound: Option<&wgpu::RenderPipeline> = None;
+            let mut bound_material: &wgpu::BindGroup = &self.diffuse_bind_group;
+            let batching = self.debug_view == DebugView::Lit && !self.batches.is_empty();
+            // The morphing item is drawn after the rest, its pipeline takes another set 0.
+            let drawn_later = |i: usize| (i == 0 && self.morph.is_some()) || (batching && self.batches.is_batched(i));
+            let prepass = self.scene_pipelines.prepass.as_ref().filter(|_| self.debug_view == DebugView::Lit);
+            if let Some(prepass) = prepass {
+                for &i in &self.draw_order {
+                    let item = &self.render_items[i];
+                    if drawn_later(i) || item.pipeline.is_some() {
+                        continue;
+                    }
+                    let pipeline = prepass.depth(item.double_sided);
+                    if !bound.map_or(false, |bound| std::ptr::eq(bound, pipeline)) {
+                        render_pass.set_pipeline(pipeline);
+                        bound = Some(pipeline);
+                    }
+                    render_pass.draw_item(item);
+                }
+            }
+            for &i in &self.draw_order {
+                if drawn_later(i) {
+                    continue;
+                }
+                let item = &self.render_items[i];
+                let custom = item.pipeline
+                    .as_deref()
+                    .and_then(|name| self.scene_pipelines.get_custom(self.debug_view, name, item.double_sided));
+                // The registered pipelines bind the object like the default one.
+                let (pipeline, pushed) = match (custom, prepass, self.scene_pipelines.get_pushed(self.debug_view, item.double_sided), push) {
+                    (Some(pipeline), _, _, _) => (pipeline, None),
+                    (None, Some(prepass), _, _) => (prepass.lit(item.double_sided), None),
+                    (None, None, Some(pipeline), Some(push)) => (pipeline, Some(push)),
+                    _ => (self.scene_pipelines.get(self.debug_view, item.double_sided), None),
+                };
+                if !bound.map_or(false, |bound| std::ptr::eq(bound, pipeline)) {
+                    render_pass.set_pipeline(pipeline);
+                    if let Some(push) = pushed {
+                        render_pass.set_bind_group(2, &push.empty_bind_group, &[]);
+                    }
+                    bound = Some(pipeline);
+                }
+                let material = item.material.as_deref().unwrap_or(&self.diffuse_bind_group);
+                if !std::ptr::eq(bound_material, material) {
+                    render_pass.set_bind_group(0, material, &[]);
+                    bound_material = material;
+                }
+                if pushed.is_some() {
+                    render_pass.draw_item_pushed(item);
+                } else {
+                    render_pass.draw_item(item);
+                }
+            }
+            if batching {
+                if !std::ptr::eq(bound_material, &self.diffuse_bind_group) {
+                    render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
+                }
+                render_pass.set_pipeline(&self.scene_pipelines.instanced);
+                self.batches.draw(&mut render_pass);
+            }
+            if let (Some((morph, _)), Some(item)) = (&self.morph, self.render_items.first()) {
+                morph.draw(&mut render_pass, &self.uniform_bind_group, &self.environment_bind_group, item);
+            }
+            if let Some((material, item)) = &self.terrain {
+                material.draw(&mut render_pass, &self.uniform_bind_group, &self.environment_bind_group, item);
+            }
+            if let (Some(outline), Some(item)) = (self.outline.as_ref().filter(|_| self.outline_enabled), self.render_items.first()) {
+                outline.draw(&mut render_pass, &self.uniform_bind_group, item);
+            }
+            if self.wireframe_enabled {
+                self.wireframe.draw(&mut render_pass, &self.uniform_bind_group, &self.wireframe_items);
+            }
+            drop(render_pass);
+
+            if let Some(oit) = &self.oit {
+                oit.render(&mut encoder, scene_view, &self.depth_texture.view, &self.uniform_bind_group, &self.environment_bind_group);
+            }
+        }
+
+        if self.hdr_target.is_some() {
+            self.tone_map.render(&self.queue, &mut encoder, tone_mapped_view);
+        }
+        if self.bloom_enabled {
+            self.bloom.render(&self.queue, &mut encoder, post_view);
+        }
+        if self.fxaa_enabled {
+            self.fxaa.render(&self.queue, &mut encoder, &frame.view);
+        }
+        if self.gizmo_enabled {
+            self.gizmo.render(&mut encoder, &frame.view, &self.uniform_bind_group);
+        }
+        self.debug_lines.render(&self.device, &self.queue, &mut encoder, &frame.view, &self.uniform_bind_group);
+
+        let fps = if self.frame_time > 0.0 { 1.0 / self.frame_time } else { 0.0 };
+        let culled = if self.batches.culling && !self.batches.is_empty() {
+            format!(", {} instances culled", self.batches.culled())
+        } else {
+            String::new()
+        };
+        self.hud.draw_text(10.0, 10.0, &format!(
+            "{:.1} fps ({:.2} ms), {}x MSAA, speed {:.2}{}",
+            fps,
+            self.frame_time * 1000.0,
+            self.sample_count,
+            self.controller.speed,
+            culled,
+        ));
+        self.hud.draw_text(10.0, 10.0 + self.hud.font_size, &format!("{:?}: help", self.controller.bindings.help));
+        if self.help_enabled {
+            self.draw_help();
+        }
+        self.hud.render(&self.device, &mut encoder, &frame.view, self.size.width, self.size.height);
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.hud.recall();
+
+        Ok(())
+    }
+
+    /// Queue the key bindings on a translucent panel below the frame rate. Input
+    /// still goes to the scene underneath, the panel only draws.
+    fn draw_help(&mut self) {
+        let lines: Vec<String> = self.controller.bindings
+            .describe()
+            .into_iter()
+            .map(|(keys, action)| format!("{}: {}", keys, action))
+            .collect();
+
+        let padding = 0.5 * self.hud.font_size;
+        let x = 10.0;
+        let y = 10.0 + 2.5 * self.hud.font_size;
+        let width = lines.iter().map(|line| self.hud.text_width(line)).fold(0.0, f32::max);
+        let height = lines.len() as f32 * self.hud.font_size;
+        self.hud.draw_panel(x, y, width + 2.0 * padding, height + 2.0 * padding, [0.0, 0.0, 0.0, 0.6]);
+        for (i, line) in lines.iter().enumerate() {
+            self.hud.draw_text(x + padding, y + padding + i as f32 * self.hud.font_size, line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_check::gpu::headless_device;
+
+    #[test]
+    fn clearing_the_material_override_restores_the_meshes_own() {
+        let (device, queue) = match headless_device() {
+            Some(device) => device,
+            None => return eprintln!("no adapter, skipping the material override check"),
+        };
+        let object_layout = RenderItem::create_object_layout(&device);
+        let texture_layout = create_texture_bind_group_layout(&device);
+        let mut model = Model::new(vec![Mesh::brick(1.0, 1.0, 1.0, 0), Mesh::sphere(1.0, 16, 8)]);
+
+        model.set_material_override(Some(Material::WHITE));
+        let items = model_items(&device, &queue, &object_layout, &texture_layout, &model, 0, false);
+        assert_eq!(items.len(), 2);
+        match (&items[0].material, &items[1].material) {
+            (Some(a), Some(b)) => assert!(Rc::ptr_eq(a, b), "the meshes have materials of their own"),
+            _ => panic!("a mesh is drawn without the override"),
+        }
+
+        model.set_material_override(None);
+        let items = model_items(&device, &queue, &object_layout, &texture_layout, &model, 0, false);
+        assert!(items.iter().all(|item| item.material.is_none()), "the override outlived being cleared");
     }
 }
\ No newline at end of file
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::{camera::Camera, model::Mesh, render_item::{DrawRenderItem, Instance, RenderItem}};
+
+struct Batch {
+    // Indices of the render items `item` draws, one instance each.
+    members: Vec<usize>,
+    item: RenderItem,
+    // Bounding sphere of the mesh, before the members' transforms.
+    center: Vector3<f32>,
+    radius: f32,
+    // As last uploaded, the visible ones first, to skip writing transforms that didn't change.
+    instances: Vec<Instance>,
+}
+
+/// Render items showing equal meshes, by `Mesh::content_hash`, drawn as one
+/// instanced item per mesh once at least `threshold` of them share it, each
+/// instance placed by one item's transform. Smaller groups draw one by one.
+///
+/// The instanced items are only rebuilt when `rebuild` is given a different set
+/// of meshes, moving the items only rewrites their instances in `update`. Like
+/// `MeshCache`, meshes hashing the same share the first one's normals and
+/// texture coordinates. Double-sided items aren't batched, the instanced
+/// pipeline culls back faces, and neither are items with a pipeline of their own.
+///
+/// With `culling`, `update` tests each instance's bounding sphere against the
+/// camera's frustum and packs the ones in view at the front of the instance
+/// buffer, so `draw` only submits those. `culled` counts the ones left out.
+pub struct InstanceBatches {
+    pub threshold: usize,
+    pub culling: bool,
+
+    // Of the meshes the batches were built for, by item, `None` where left out.
+    hashes: Vec<Option<u64>>,
+    batches: Vec<Batch>,
+    // By item, whether a batch draws it.
+    batched: Vec<bool>,
+    // Instances left out by the last `update`.
+    culled: usize,
+}
+
+impl InstanceBatches {
+    pub fn new(threshold: usize, culling: bool) -> Self {
+        Self {
+            threshold,
+            culling,
+            hashes: Vec::new(),
+            batches: Vec::new(),
+            batched: Vec::new(),
+            culled: 0,
+        }
+    }
+
+    /// Group `items`, showing `meshes` in the same order, unless they're the same
+    /// meshes as last time. `None` leaves an item out, e.g. one drawn another way.
+    /// The instanced items are built with `object_layout` and bound with
+    /// `instanced_layout`, see `RenderItem::with_instance_storage`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn rebuild(
+        &mut self,
+        device: &wgpu::Device,
+        object_layout: &wgpu::BindGroupLayout,
+        instanced_layout: &wgpu::BindGroupLayout,
+        meshes: &[Option<&Mesh>],
+        items: &[RenderItem],
+        deinterleave_vertices: bool,
+    ) {
+        let hashes: Vec<Option<u64>> = meshes
+            .iter()
+            .zip(items)
+            .map(|(mesh, item)| mesh.filter(|_| !item.double_sided && item.pipeline.is_none() && item.material.is_none()).map(Mesh::content_hash))
+            .collect();
+        if hashes == self.hashes {
+            return;
+        }
+
+        // In the order each mesh first appears, so the draws don't shuffle between runs.
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_of: HashMap<u64, usize> = HashMap::new();
+        for (i, hash) in hashes.iter().enumerate() {
+            if let Some(hash) = hash {
+                let group = *group_of.entry(*hash).or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+                groups[group].push(i);
+            }
+        }
+
+        let threshold = self.threshold.max(1);
+        let mut batched = vec![false; items.len()];
+        self.batches = groups
+            .into_iter()
+            .filter(|members| members.len() >= threshold)
+            .map(|members| {
+                let mesh = meshes[members[0]].expect("grouped items have a mesh");
+                let label = format!("{} instances", members.len());
+                let item = if deinterleave_vertices {
+                    RenderItem::from_mesh_deinterleaved(device, object_layout, mesh, &label)
+                } else {
+                    RenderItem::from_mesh(device, object_layout, mesh, &label)
+                };
+                let instances: Vec<Instance> = members.iter().map(|&i| Instance::new(&items[i].transform)).collect();
+                for &i in &members {
+                    batched[i] = true;
+                }
+                let bounds = mesh.bounding_box();
+                Batch {
+                    members,
+                    item: item.with_instance_storage(device, instanced_layout, &instances),
+                    center: bounds.center(),
+                    radius: bounds.size().magnitude() / 2.0,
+                    instances,
+                }
+            })
+            .collect();
+        self.batched = batched;
+        self.hashes = hashes;
+    }
+
+    /// Whether the item at `index` is drawn by `draw` rather than on its own.
+    pub fn is_batched(&self, index: usize) -> bool {
+        self.batched.get(index).copied().unwrap_or(false)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    /// Instances outside the frustum at the last `update`, 0 without `culling`.
+    pub fn culled(&self) -> usize {
+        self.culled
+    }
+
+    /// Upload the transforms of the batched `items` in view of `camera`, or all of
+    /// them without `culling`, when they differ from last time.
+    pub fn update(&mut self, queue: &wgpu::Queue, items: &[RenderItem], camera: &Camera) {
+        let planes = camera.frustum_planes();
+        let culling = self.culling;
+        self.culled = 0;
+        for batch in &mut self.batches {
+            let instances: Vec<Instance> = batch.members
+                .iter()
+                .map(|&i| &items[i].transform)
+                .filter(|transform| {
+                    let center = transform.disp + transform.rot * (batch.center * transform.scale);
+                    !culling || Camera::sphere_in_frustum(&planes, center, batch.radius * transform.scale.abs())
+                })
+                .map(Instance::new)
+                .collect();
+            self.culled += batch.members.len() - instances.len();
+            if instances != batch.instances {
+                batch.item.write_instances(queue, &instances);
+                batch.instances = instances;
+            }
+        }
+    }
+
+    /// Draw every batch's instances in view with a pipeline taking the instanced
+    /// object layout at group 2, already set on `render_pass` along with the other groups.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        for batch in self.batches.iter().filter(|batch| !batch.instances.is_empty()) {
+            render_pass.draw_item_instanced(&batch.item, 0..batch.instances.len() as u32);
+        }
+    }
+}
\ No newline at end of file
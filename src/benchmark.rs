@@ -0,0 +1,61 @@
+use std::fmt;
+
+/// Frame time statistics collected by `Application::benchmark`, in milliseconds.
+pub struct FrameStats {
+    pub frames: usize,
+    pub min: f32,
+    pub avg: f32,
+    pub max: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+}
+
+impl FrameStats {
+    /// Build the report from per-frame durations in seconds.
+    pub fn from_frame_times(frame_times: &[f32]) -> Self {
+        let mut sorted: Vec<f32> = frame_times.iter().map(|t| t * 1000.0).collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let frames = sorted.len();
+        if frames == 0 {
+            return Self {
+                frames,
+                min: 0.0,
+                avg: 0.0,
+                max: 0.0,
+                p50: 0.0,
+                p95: 0.0,
+                p99: 0.0,
+            };
+        }
+
+        // Nearest-rank percentile.
+        let percentile = |p: f32| {
+            let rank = (p / 100.0 * frames as f32).ceil() as usize;
+            sorted[rank.clamp(1, frames) - 1]
+        };
+
+        Self {
+            frames,
+            min: sorted[0],
+            avg: sorted.iter().sum::<f32>() / frames as f32,
+            max: sorted[frames - 1],
+            p50: percentile(50.0),
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+        }
+    }
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "frames: {}", self.frames)?;
+        writeln!(f, "min: {:.3} ms", self.min)?;
+        writeln!(f, "avg: {:.3} ms", self.avg)?;
+        writeln!(f, "max: {:.3} ms", self.max)?;
+        writeln!(f, "p50: {:.3} ms", self.p50)?;
+        writeln!(f, "p95: {:.3} ms", self.p95)?;
+        write!(f, "p99: {:.3} ms", self.p99)
+    }
+}
\ No newline at end of file
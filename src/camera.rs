@@ -1,21 +1,122 @@
+use std::cell::Cell;
 use std::fmt::Debug;
 
-use cgmath::{Matrix4, One, PerspectiveFov, Quaternion, Rad, Rotation3, Transform, Vector3};
-use cgmath::{Decomposed, Deg};
+use cgmath::{InnerSpace, Matrix3, Matrix4, One, PerspectiveFov, Point3, Quaternion, Rad, Rotation3, SquareMatrix, Transform, Vector2, Vector3, Vector4, Zero};
+use cgmath::{Decomposed, Deg, EuclideanSpace};
 
 use crate::controller::{ControllerUpdate, Controller};
+use crate::model::Aabb;
+use crate::orbit_camera::OrbitCamera;
+use crate::ray::Ray;
 
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, -0.5, -1.0,
-    0.0, 0.0, -0.5, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
 );
 
+// Orbit distance of `orbit_from_current` when the view ray hits nothing.
+const ORBIT_FALLBACK_DISTANCE: f32 = 5.0;
+// Closest pivot `orbit_from_current` picks, the orbit's own zoom limit.
+const ORBIT_MIN_DISTANCE: f32 = 0.1;
+
+// How often the shake offsets change direction, in cycles per second.
+const SHAKE_FREQUENCY: f32 = 15.0;
+// Largest rotation offset of a shake, in degrees per unit of amplitude.
+const SHAKE_ROTATION: f32 = 5.0;
+
+// Vertical field of view range `dolly_zoom` keeps to, in degrees. Narrower
+// loses depth precision to the far plane, wider heads for a degenerate lens.
+const DOLLY_MIN_FOV: f32 = 1.0;
+const DOLLY_MAX_FOV: f32 = 160.0;
+
+/// Element `index` of the Halton sequence in `base`, in [0, 1): the digits of
+/// `index` in `base` mirrored around the radix point. Consecutive indices spread
+/// evenly over the interval whatever the count, 0 gives 0.
+pub fn halton(index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    let mut index = index;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// Sub-pixel offset number `index` for `Camera::set_jitter` in a `width` by
+/// `height` viewport, from the Halton sequences in bases 2 and 3, within half a
+/// pixel of the center either way. Start at 1 and cycle over a handful of
+/// indices, e.g. 8 or 16, so the samples cover each pixel evenly.
+pub fn halton_jitter(index: u32, width: u32, height: u32) -> Vector2<f32> {
+    // A pixel spans 2 / size in normalized device coordinates.
+    Vector2::new(
+        (halton(index, 2) - 0.5) * 2.0 / width.max(1) as f32,
+        (halton(index, 3) - 0.5) * 2.0 / height.max(1) as f32,
+    )
+}
+
+/// Value noise in [-1, 1] along `t`, smoothly interpolating hashed values at
+/// integer `t`. Each `(seed, channel)` pair gives an unrelated sequence.
+fn noise(seed: u32, channel: u32, t: f32) -> f32 {
+    let hash = |i: i32| {
+        let mut h = seed ^ channel.wrapping_mul(0x9e37_79b9) ^ (i as u32).wrapping_mul(0x85eb_ca6b);
+        h ^= h >> 16;
+        h = h.wrapping_mul(0x7feb_352d);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x846c_a68b);
+        h ^= h >> 16;
+        h as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+    let i = t.floor();
+    let f = t - i;
+    let f = f * f * (3.0 - 2.0 * f);
+    hash(i as i32) * (1.0 - f) + hash(i as i32 + 1) * f
+}
+
+/// A shake in progress, see `Camera::add_shake`.
+#[derive(Debug, Clone, Copy)]
+struct Shake {
+    amplitude: f32,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl Shake {
+    /// The amplitude left, easing out to zero at the end of the shake.
+    fn strength(&self) -> f32 {
+        let remaining = (1.0 - self.elapsed / self.duration).max(0.0);
+        self.amplitude * remaining * remaining
+    }
+}
+
 pub struct Camera {
-    pub transform: Decomposed<Vector3<f32>, Quaternion<f32>>,
-    pub projection_matrix: Matrix4<f32>,
+    // The camera's own transform, the shake is applied on top when building the view.
+    transform: Decomposed<Vector3<f32>, Quaternion<f32>>,
+    projection_matrix: Matrix4<f32>,
+    // Added to the projected position, in normalized device coordinates.
+    jitter: Vector2<f32>,
+
+    // Multiplies the screen size LOD selection sees, above 1 picks finer levels.
+    pub lod_bias: f32,
+
+    // Keep the eye `eye_height` above the y = `floor_height` plane while moving,
+    // for walking through a scene instead of flying. Off by default.
+    pub floor_clamp_enabled: bool,
+    pub floor_height: f32,
+    pub eye_height: f32,
+
+    shake: Option<Shake>,
+    shake_seed: u32,
+    // Seconds of shake noise played since the seed was set.
+    shake_time: f32,
+
+    // View-projection cache, recomputed lazily after the transform or lens changes.
+    view_proj: Cell<Matrix4<f32>>,
+    dirty: Cell<bool>,
 }
 
 impl Camera {
@@ -23,29 +124,304 @@ impl Camera {
         Self {
             transform: Decomposed::one(),
             projection_matrix: OPENGL_TO_WGPU_MATRIX * cgmath::perspective(Deg(45.0), aspect, 0.1, 100.0),
+            jitter: Vector2::zero(),
+            lod_bias: 1.0,
+            floor_clamp_enabled: false,
+            floor_height: 0.0,
+            eye_height: 1.7,
+            shake: None,
+            shake_seed: 0,
+            shake_time: 0.0,
+            view_proj: Cell::new(Matrix4::one()),
+            dirty: Cell::new(true),
         }
     }
 
     pub fn get_view_proj(&self) -> Matrix4<f32> {
-        self.get_proj() * self.get_view()
+        if self.dirty.get() {
+            self.view_proj.set(self.get_proj() * self.get_view());
+            self.dirty.set(false);
+        }
+        self.view_proj.get()
+    }
+
+    pub fn transform(&self) -> &Decomposed<Vector3<f32>, Quaternion<f32>> {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Decomposed<Vector3<f32>, Quaternion<f32>>) {
+        self.transform = transform;
+        self.dirty.set(true);
+    }
+
+    /// Place the camera at `eye` looking at `target`, upright with no roll. Looking
+    /// straight up or down, the camera's up is taken along -z instead.
+    pub fn look_at(&mut self, eye: Vector3<f32>, target: Vector3<f32>) {
+        let forward = target - eye;
+        if forward.magnitude2() == 0.0 {
+            return;
+        }
+        let forward = forward.normalize();
+        let right = forward.cross(Vector3::unit_y());
+        let right = if right.magnitude2() > 1e-12 { right.normalize() } else { Vector3::unit_x() };
+        let up = right.cross(forward);
+        self.set_transform(Decomposed {
+            scale: 1.0,
+            rot: Quaternion::from(Matrix3::from_cols(right, up, -forward)),
+            disp: eye,
+        });
     }
 
     pub fn get_view(&self) -> Matrix4<f32> {
-        let inverse_view: Matrix4<f32> = self.transform.into();
+        let inverse_view: Matrix4<f32> = self.shaken_transform().into();
         inverse_view.inverse_transform().unwrap()
     }
 
+    /// Shake the view for `duration` seconds, moving it up to `amplitude` world units
+    /// and turning it up to `SHAKE_ROTATION` degrees per unit of amplitude, fading
+    /// out over the duration. A shake started during another keeps the stronger
+    /// amplitude and the longer remaining time.
+    pub fn add_shake(&mut self, amplitude: f32, duration: f32) {
+        if duration <= 0.0 {
+            return;
+        }
+        let shake = match self.shake {
+            Some(current) => Shake {
+                amplitude: current.strength().max(amplitude),
+                duration: (current.duration - current.elapsed).max(duration),
+                elapsed: 0.0,
+            },
+            None => Shake { amplitude, duration, elapsed: 0.0 },
+        };
+        self.shake = Some(shake);
+        self.dirty.set(true);
+    }
+
+    /// Restart the shake noise from `seed`, so the same seed and frame times play
+    /// back the same offsets.
+    pub fn set_shake_seed(&mut self, seed: u32) {
+        self.shake_seed = seed;
+        self.shake_time = 0.0;
+        self.dirty.set(true);
+    }
+
+    /// Whether a shake is still playing, see `add_shake`.
+    pub fn is_shaking(&self) -> bool {
+        self.shake.is_some()
+    }
+
+    /// Advance the shake by `duration` seconds, dropping it once it has decayed.
+    pub fn update_shake(&mut self, duration: f32) {
+        if let Some(shake) = &mut self.shake {
+            shake.elapsed += duration;
+            self.shake_time += duration;
+            if shake.elapsed >= shake.duration {
+                self.shake = None;
+            }
+            self.dirty.set(true);
+        }
+    }
+
+    /// `transform` with the current shake offsets applied.
+    fn shaken_transform(&self) -> Decomposed<Vector3<f32>, Quaternion<f32>> {
+        let strength = match self.shake {
+            Some(shake) => shake.strength(),
+            None => return self.transform,
+        };
+        let t = self.shake_time * SHAKE_FREQUENCY;
+        let channel = |c| strength * noise(self.shake_seed, c, t);
+
+        // Offsets are in camera space, so the shake looks the same whichever way it faces.
+        let offset = Vector3::new(channel(0), channel(1), channel(2));
+        let angle = |c| Deg(SHAKE_ROTATION * channel(c));
+        let rot = Quaternion::from_angle_y(angle(3)) * Quaternion::from_angle_x(angle(4)) * Quaternion::from_angle_z(angle(5));
+        Decomposed {
+            scale: self.transform.scale,
+            rot: self.transform.rot * rot,
+            disp: self.transform.disp + self.transform.rot * offset,
+        }
+    }
+
+    /// The lens, with the jitter applied.
     pub fn get_proj(&self) -> Matrix4<f32> {
-        self.projection_matrix
+        if self.jitter == Vector2::zero() {
+            return self.projection_matrix;
+        }
+        // In clip space the shift scales with w, so it's the same on screen at every depth.
+        Matrix4::from_translation(Vector3::new(self.jitter.x, self.jitter.y, 0.0)) * self.projection_matrix
+    }
+
+    /// Shift the projected image by `offset` in normalized device coordinates,
+    /// after the lens and its OpenGL to wgpu remap, so successive frames can
+    /// sample different spots within each pixel for temporal techniques, see
+    /// `halton_jitter`. `get_proj` and `get_view_proj` include it until it's set
+    /// back to zero.
+    pub fn set_jitter(&mut self, offset: Vector2<f32>) {
+        self.jitter = offset;
+        self.dirty.set(true);
+    }
+
+    pub fn jitter(&self) -> Vector2<f32> {
+        self.jitter
     }
     
     pub fn set_lens(&mut self, perspective: PerspectiveFov<f32>) {
         self.projection_matrix = perspective.into();
         self.projection_matrix = OPENGL_TO_WGPU_MATRIX * self.projection_matrix;
+        self.dirty.set(true);
+    }
+
+    /// The Vertigo effect: walk `subject_distance_delta` toward the subject, or
+    /// away when negative, while widening or narrowing the field of view so the
+    /// subject keeps its size on screen and only the perspective around it shifts.
+    ///
+    /// `subject_size` is the world space height the view spans at the subject's
+    /// distance, e.g. the subject's own height for it to fill the frame. With the
+    /// current field of view it places the subject, `subject_size / 2 / tan(fov_y / 2)`
+    /// ahead, so call it with the same value throughout the effect. The field of
+    /// view stays within `DOLLY_MIN_FOV` to `DOLLY_MAX_FOV` degrees, the walk
+    /// stopping short where it would leave them. Aspect ratio and clip planes are
+    /// kept.
+    pub fn dolly_zoom(&mut self, subject_distance_delta: f32, subject_size: f32) {
+        // The projection's y scale is 1 / tan(fov_y / 2).
+        let scale = self.projection_matrix.y.y;
+        let half_size = subject_size.abs().max(f32::EPSILON) / 2.0;
+        let distance = half_size * scale;
+
+        let half_fov = (half_size / (distance - subject_distance_delta).max(f32::EPSILON))
+            .atan()
+            .max((DOLLY_MIN_FOV / 2.0).to_radians())
+            .min((DOLLY_MAX_FOV / 2.0).to_radians());
+        let new_scale = 1.0 / half_fov.tan();
+
+        self.walk(distance - half_size * new_scale);
+        self.projection_matrix.x.x *= new_scale / scale;
+        self.projection_matrix.y.y = new_scale;
+        self.dirty.set(true);
+    }
+
+    /// World space corners of the view frustum, the near plane first, each plane in
+    /// the order (-x, -y), (x, -y), (x, y), (-x, y) of normalized device coordinates.
+    pub fn frustum_corners(&self) -> [Point3<f32>; 8] {
+        let inverse_view_proj = self.get_view_proj().invert().unwrap();
+        let mut corners = [Point3::new(0.0, 0.0, 0.0); 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let x = if i % 4 == 1 || i % 4 == 2 { 1.0 } else { -1.0 };
+            let y = if i % 4 >= 2 { 1.0 } else { -1.0 };
+            let z = if i < 4 { 0.0 } else { 1.0 };
+            let world = inverse_view_proj * Vector4::new(x, y, z, 1.0);
+            *corner = Point3::from_homogeneous(world);
+        }
+        corners
+    }
+
+    /// World space planes bounding the view frustum, left, right, bottom, top, near
+    /// and far, each as `(normal, d)` with the normal pointing inward, so a point
+    /// `p` is inside where `normal · p + d >= 0` for all six. The normals aren't
+    /// unit length, see `sphere_in_frustum`.
+    pub fn frustum_planes(&self) -> [Vector4<f32>; 6] {
+        // Gribb and Hartmann, with wgpu's 0 to 1 depth range for the near plane.
+        let m = self.get_view_proj();
+        let row = |i: usize| Vector4::new(m.x[i], m.y[i], m.z[i], m.w[i]);
+        [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2),
+        ]
     }
 
+    /// Whether a sphere of `radius` around `center` is at least partly inside
+    /// `planes`, from `frustum_planes`. Spheres just outside a corner pass too,
+    /// which only costs drawing them.
+    pub fn sphere_in_frustum(planes: &[Vector4<f32>; 6], center: Vector3<f32>, radius: f32) -> bool {
+        planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            normal.dot(center) + plane.w >= -radius * normal.magnitude()
+        })
+    }
+
+    /// Pixels a sphere of `radius` around `center` spans vertically on a viewport
+    /// `viewport_height` pixels tall, times `lod_bias`. This is the screen space
+    /// size LOD selection goes by:
+    ///
+    /// `size = lod_bias * 2 * radius * (viewport_height / 2) / (distance * tan(fov_y / 2))`
+    ///
+    /// so it follows the field of view as well as the distance. Zooming in to
+    /// half the `tan(fov_y / 2)` doubles the size just like halving the distance
+    /// does. The camera inside the sphere counts as being on its surface.
+    pub fn projected_size(&self, center: Vector3<f32>, radius: f32, viewport_height: f32) -> f32 {
+        let distance = (center - self.transform.disp).magnitude().max(radius).max(f32::EPSILON);
+        // The projection's y scale is 1 / tan(fov_y / 2).
+        self.lod_bias * radius * self.projection_matrix.y.y * viewport_height / distance
+    }
+
+    /// Which mesh of a LOD chain to draw for a sphere of `radius` around `center`:
+    /// 0 for the full mesh, `k` for the `k`th of `levels` meshes from
+    /// `Mesh::generate_lod_chain` with `ratio`, capped at `levels`.
+    ///
+    /// The full mesh is meant for `full_detail_size` pixels and up. Triangle counts
+    /// follow screen area, the square of `projected_size`, so every time the area
+    /// shrinks by another `ratio` the next level is taken.
+    pub fn select_lod(&self, center: Vector3<f32>, radius: f32, viewport_height: f32, full_detail_size: f32, ratio: f32, levels: usize) -> usize {
+        let size = self.projected_size(center, radius, viewport_height);
+        if size >= full_detail_size || !(ratio > 0.0 && ratio < 1.0) {
+            return 0;
+        }
+        let area_ratio = (size / full_detail_size).powi(2);
+        let level = (area_ratio.ln() / ratio.ln()).floor();
+        (level.max(0.0) as usize).min(levels)
+    }
+
+    /// Ray from the camera through `cursor`, in pixels from the top-left corner of a
+    /// `viewport` sized in pixels.
+    pub fn screen_ray(&self, cursor: (f64, f64), viewport: (f32, f32)) -> Ray {
+        let x = 2.0 * cursor.0 as f32 / viewport.0 - 1.0;
+        let y = 1.0 - 2.0 * cursor.1 as f32 / viewport.1;
+
+        let inverse_view_proj = self.get_view_proj().invert().unwrap();
+        let near = Point3::from_homogeneous(inverse_view_proj * Vector4::new(x, y, 0.0, 1.0));
+        let far = Point3::from_homogeneous(inverse_view_proj * Vector4::new(x, y, 1.0, 1.0));
+        Ray::new(near.to_vec(), far - near)
+    }
+
+    /// Switch to orbiting the point in the middle of the view without the view
+    /// jumping. The pivot is where the forward ray first hits one of `bounds`, in
+    /// world space, or the y = 0 ground plane, and `ORBIT_FALLBACK_DISTANCE`
+    /// ahead when it hits neither. Boxes closer than the orbit's minimum distance,
+    /// such as one around the camera, are looked through.
+    ///
+    /// The orbit has no roll and keeps its pitch within 89 degrees, so only a
+    /// rolled camera or one looking straight up or down moves.
+    pub fn orbit_from_current(self, bounds: &[Aabb]) -> OrbitCamera {
+        let ray = Ray::new(self.transform.disp, self.forward());
+        let distance = bounds
+            .iter()
+            .filter_map(|aabb| ray.intersect_aabb(aabb))
+            .chain(ray.intersect_plane(Vector3::new(0.0, 0.0, 0.0), Vector3::unit_y()))
+            .filter(|&t| t >= ORBIT_MIN_DISTANCE)
+            .fold(None, |nearest: Option<f32>, t| Some(nearest.map_or(t, |nearest| nearest.min(t))))
+            .unwrap_or(ORBIT_FALLBACK_DISTANCE);
+
+        // Inverts the orbit's rotation, whose forward is
+        // (-cos pitch sin yaw, -sin pitch, -cos pitch cos yaw).
+        let forward = ray.direction;
+        let pitch = Deg::from(Rad((-forward.y).max(-1.0).min(1.0).asin())).0.max(-89.0).min(89.0);
+        let yaw = Deg::from(Rad((-forward.x).atan2(-forward.z))).0;
+
+        let target = ray.at(distance);
+        let mut orbit = OrbitCamera::new(self, target, distance);
+        orbit.yaw = yaw;
+        orbit.pitch = pitch;
+        orbit.update_camera();
+        orbit
+    }
+
+    // The projection is right handed, the camera looks down its local -z.
     pub fn forward(&self) -> Vector3<f32> {
-        self.transform.rot * Vector3::unit_z()
+        self.transform.rot * -Vector3::unit_z()
     }
 
     pub fn right(&self) -> Vector3<f32> {
@@ -54,34 +430,53 @@ impl Camera {
 
     pub fn walk(&mut self, distance: f32) {
         self.transform.disp += distance * self.forward();
+        self.dirty.set(true);
     }
 
     pub fn strafe(&mut self, distance: f32) {
         self.transform.disp += distance * self.right();
+        self.dirty.set(true);
+    }
+
+    /// Lift the camera back to `eye_height` above the floor if it went below,
+    /// while `floor_clamp_enabled`. Only the height changes, so walking into the
+    /// floor slides along it.
+    pub fn clamp_to_floor(&mut self) {
+        let min_y = self.floor_height + self.eye_height;
+        if self.floor_clamp_enabled && self.transform.disp.y < min_y {
+            self.transform.disp.y = min_y;
+            self.dirty.set(true);
+        }
     }
 
     pub fn rotate_y(&mut self, angle: f32) {
         self.transform.rot = Quaternion::from_angle_y(Deg(angle)) * self.transform.rot;
+        self.dirty.set(true);
     }
 
     pub fn pitch(&mut self, angle: f32) {
         self.transform.rot = Quaternion::from_axis_angle(self.right(), Deg(angle)) * self.transform.rot;
+        self.dirty.set(true);
     }
 }
 
 impl ControllerUpdate for Camera {
     fn update(&mut self, controller: &Controller, duration: f32) {
+        self.update_shake(duration);
+
         controller.up_pressed.then(|| self.walk(controller.speed * duration));
         controller.down_pressed.then(|| self.walk(-controller.speed * duration));
         controller.right_pressed.then(|| self.strafe(controller.speed * duration));
         controller.left_pressed.then(|| self.strafe(-controller.speed * duration));
+        self.clamp_to_floor();
 
         controller.dragged.then(|| {
             let theta = controller.current_cursor.0 - controller.last_cursor.0;
             let phi = controller.current_cursor.1 - controller.last_cursor.1;
 
-            self.pitch(0.25 * phi as f32);
-            self.rotate_y(0.25 * theta as f32);
+            // Dragging right or down turns the view right or down.
+            self.pitch(-0.25 * phi as f32);
+            self.rotate_y(-0.25 * theta as f32);
         });
     }
 }
\ No newline at end of file
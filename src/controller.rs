@@ -1,10 +1,114 @@
+use std::collections::HashMap;
+
 use winit::event::{
-    ElementState, KeyboardInput, WindowEvent, VirtualKeyCode,
-    MouseButton,
+    AxisId, DeviceEvent, ElementState, KeyboardInput, WindowEvent, VirtualKeyCode,
+    ModifiersState, MouseButton, MouseScrollDelta,
 };
 
+// Range the speed keys keep `Controller::speed` in.
+const MIN_SPEED: f32 = 0.01;
+const MAX_SPEED: f32 = 1000.0;
+
+/// The keys driving the `Controller` and the application's toggles. A movement
+/// action takes any of its keys.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    pub forward: Vec<VirtualKeyCode>,
+    pub back: Vec<VirtualKeyCode>,
+    pub left: Vec<VirtualKeyCode>,
+    pub right: Vec<VirtualKeyCode>,
+    pub faster: Vec<VirtualKeyCode>,
+    pub slower: Vec<VirtualKeyCode>,
+
+    pub bloom: VirtualKeyCode,
+    pub fxaa: VirtualKeyCode,
+    pub gizmo: VirtualKeyCode,
+    pub outline: VirtualKeyCode,
+    pub wireframe: VirtualKeyCode,
+    pub shake: VirtualKeyCode,
+    pub floor_clamp: VirtualKeyCode,
+    pub reset_camera: VirtualKeyCode,
+    pub msaa: VirtualKeyCode,
+    pub normal_view: VirtualKeyCode,
+    pub handedness_view: VirtualKeyCode,
+    pub click_ray: VirtualKeyCode,
+    pub tone_map: VirtualKeyCode,
+    pub gpu_report: VirtualKeyCode,
+    pub mesh_report: VirtualKeyCode,
+    pub help: VirtualKeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            forward: vec![VirtualKeyCode::W, VirtualKeyCode::Up],
+            back: vec![VirtualKeyCode::S, VirtualKeyCode::Down],
+            left: vec![VirtualKeyCode::A, VirtualKeyCode::Left],
+            right: vec![VirtualKeyCode::D, VirtualKeyCode::Right],
+            // Plus is shifted Equals on most layouts.
+            faster: vec![VirtualKeyCode::Equals, VirtualKeyCode::Plus, VirtualKeyCode::NumpadAdd],
+            slower: vec![VirtualKeyCode::Minus, VirtualKeyCode::NumpadSubtract],
+            bloom: VirtualKeyCode::B,
+            fxaa: VirtualKeyCode::F,
+            gizmo: VirtualKeyCode::G,
+            outline: VirtualKeyCode::O,
+            wireframe: VirtualKeyCode::X,
+            shake: VirtualKeyCode::K,
+            floor_clamp: VirtualKeyCode::C,
+            reset_camera: VirtualKeyCode::Home,
+            msaa: VirtualKeyCode::M,
+            normal_view: VirtualKeyCode::N,
+            handedness_view: VirtualKeyCode::H,
+            click_ray: VirtualKeyCode::R,
+            tone_map: VirtualKeyCode::T,
+            gpu_report: VirtualKeyCode::P,
+            mesh_report: VirtualKeyCode::I,
+            help: VirtualKeyCode::F1,
+        }
+    }
+}
+
+impl KeyBindings {
+    /// The key names and action of every binding, in the order to list them.
+    pub fn describe(&self) -> Vec<(String, &'static str)> {
+        let keys = |keys: &[VirtualKeyCode]| {
+            keys.iter().map(|key| format!("{:?}", key)).collect::<Vec<_>>().join(" / ")
+        };
+
+        vec![
+            (keys(&self.forward), "move forward"),
+            (keys(&self.back), "move back"),
+            (keys(&self.left), "move left"),
+            (keys(&self.right), "move right"),
+            (keys(&self.faster), "move faster"),
+            (keys(&self.slower), "move slower"),
+            ("left drag".to_string(), "look around"),
+            (keys(&[self.bloom]), "toggle bloom"),
+            (keys(&[self.fxaa]), "toggle FXAA"),
+            (keys(&[self.gizmo]), "toggle the gizmo"),
+            (keys(&[self.outline]), "toggle the outline"),
+            (keys(&[self.wireframe]), "toggle the wireframe"),
+            (keys(&[self.shake]), "shake the camera"),
+            (keys(&[self.floor_clamp]), "toggle walking on the floor"),
+            (keys(&[self.reset_camera]), "reset the camera"),
+            (keys(&[self.msaa]), "switch between 1x and 4x MSAA"),
+            (keys(&[self.normal_view]), "toggle the normal view"),
+            (keys(&[self.handedness_view]), "toggle the tangent handedness view"),
+            (keys(&[self.click_ray]), "toggle the click ray"),
+            (keys(&[self.tone_map]), "cycle tone mapping"),
+            (keys(&[self.gpu_report]), "print GPU memory use"),
+            (keys(&[self.mesh_report]), "print mesh statistics"),
+            ("1-5".to_string(), "show a primitive mesh"),
+            (keys(&[self.help]), "toggle this help"),
+        ]
+    }
+}
+
 pub struct Controller {
     pub speed: f32,
+    // `speed` is multiplied or divided by this on each press of the speed keys.
+    pub speed_step: f32,
+    pub bindings: KeyBindings,
 
     pub up_pressed: bool,
     pub down_pressed: bool,
@@ -13,8 +117,22 @@ pub struct Controller {
 
     pub dragged: bool,
 
+    // Cleared by `set_input_enabled` while a UI layer takes the input.
+    input_enabled: bool,
+
+    // Held modifier keys, either side.
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+
     pub current_cursor: (f64, f64),
     pub last_cursor: (f64, f64),
+
+    // Relative input from device events, accumulated over a frame.
+    pub mouse_delta: (f64, f64),
+    pub scroll_delta: f32,
+    // Latest value of each raw axis, e.g. gamepad sticks.
+    pub axes: HashMap<AxisId, f64>,
 }
 
 pub trait ControllerUpdate {
@@ -28,11 +146,27 @@ impl Controller {
         }
 
         self.last_cursor = self.current_cursor;
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll_delta = 0.0;
+    }
+
+    /// Whether any key, button, cursor or wheel input arrived since the last `update_all`.
+    pub fn has_input(&self) -> bool {
+        self.up_pressed
+            || self.down_pressed
+            || self.left_pressed
+            || self.right_pressed
+            || self.dragged
+            || self.current_cursor != self.last_cursor
+            || self.mouse_delta != (0.0, 0.0)
+            || self.scroll_delta != 0.0
     }
 
     pub fn new(speed: f32) -> Self {
         Self {
             speed,
+            speed_step: 1.5,
+            bindings: KeyBindings::default(),
             up_pressed: false,
             down_pressed: false,
             left_pressed: false,
@@ -40,10 +174,57 @@ impl Controller {
             current_cursor: (0.0, 0.0),
             last_cursor: (0.0, 0.0),
             dragged: false,
+            input_enabled: true,
+            ctrl: false,
+            alt: false,
+            shift: false,
+            mouse_delta: (0.0, 0.0),
+            scroll_delta: 0.0,
+            axes: HashMap::new(),
+        }
+    }
+
+    /// Suspend or resume navigation, e.g. while a text field has focus. While
+    /// disabled, `process_events` and `process_device_event` leave key, button and
+    /// mouse events to other handlers. Disabling releases everything held, since
+    /// the matching releases won't be seen.
+    pub fn set_input_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.release_all();
+            self.mouse_delta = (0.0, 0.0);
+            self.scroll_delta = 0.0;
         }
+        self.input_enabled = enabled;
+    }
+
+    pub fn input_enabled(&self) -> bool {
+        self.input_enabled
+    }
+
+    fn release_all(&mut self) {
+        self.up_pressed = false;
+        self.down_pressed = false;
+        self.left_pressed = false;
+        self.right_pressed = false;
+        self.dragged = false;
     }
 
     pub fn process_events(&mut self, event: &WindowEvent) -> bool {
+        if !self.input_enabled {
+            // Keep following the cursor, without a delta, so it doesn't jump on resuming.
+            if let WindowEvent::CursorMoved { position, .. } = event {
+                self.current_cursor = (*position).into();
+                self.last_cursor = self.current_cursor;
+            }
+            match event {
+                WindowEvent::KeyboardInput { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::CursorMoved { .. }
+                | WindowEvent::MouseWheel { .. } => return false,
+                _ => {}
+            }
+        }
+
         match event {
             WindowEvent::KeyboardInput {
                 input: KeyboardInput {
@@ -54,25 +235,26 @@ impl Controller {
                 ..
             } => {
                 let press_state = *state == ElementState::Pressed;
-                match keycode {
-                    VirtualKeyCode::W | VirtualKeyCode::Up => {
-                        self.up_pressed = press_state;
-                        true
+                if self.bindings.forward.contains(keycode) {
+                    self.up_pressed = press_state;
+                } else if self.bindings.back.contains(keycode) {
+                    self.down_pressed = press_state;
+                } else if self.bindings.left.contains(keycode) {
+                    self.left_pressed = press_state;
+                } else if self.bindings.right.contains(keycode) {
+                    self.right_pressed = press_state;
+                } else if self.bindings.faster.contains(keycode) {
+                    if press_state {
+                        self.speed = (self.speed * self.speed_step).min(MAX_SPEED);
                     }
-                    VirtualKeyCode::S | VirtualKeyCode::Down => {
-                        self.down_pressed = press_state;
-                        true
-                    }
-                    VirtualKeyCode::A | VirtualKeyCode::Left => {
-                        self.left_pressed = press_state;
-                        true
-                    }
-                    VirtualKeyCode::D | VirtualKeyCode::Right => {
-                        self.right_pressed = press_state;
-                        true
+                } else if self.bindings.slower.contains(keycode) {
+                    if press_state {
+                        self.speed = (self.speed / self.speed_step).max(MIN_SPEED);
                     }
-                    _ => false
+                } else {
+                    return false;
                 }
+                true
             }
 
             WindowEvent::MouseInput {
@@ -90,6 +272,19 @@ impl Controller {
                 }
             }
 
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.set_modifiers(*modifiers);
+                // Other handlers may want to see modifier changes too.
+                false
+            }
+
+            // Releases while unfocused never arrive, so forget everything held.
+            WindowEvent::Focused(false) => {
+                self.release_all();
+                self.set_modifiers(ModifiersState::empty());
+                false
+            }
+
             WindowEvent::CursorMoved {
                 position,
                 ..
@@ -101,4 +296,48 @@ impl Controller {
             _ => false
         }
     }
+
+    fn set_modifiers(&mut self, modifiers: ModifiersState) {
+        self.ctrl = modifiers.ctrl();
+        self.alt = modifiers.alt();
+        self.shift = modifiers.shift();
+    }
+
+    /// Relative input that doesn't come through the window, returns whether the
+    /// event was consumed like `process_events`.
+    pub fn process_device_event(&mut self, event: &DeviceEvent) -> bool {
+        if !self.input_enabled {
+            return false;
+        }
+
+        match event {
+            DeviceEvent::MouseMotion {
+                delta,
+            } => {
+                self.mouse_delta.0 += delta.0;
+                self.mouse_delta.1 += delta.1;
+                true
+            }
+
+            DeviceEvent::MouseWheel {
+                delta,
+            } => {
+                self.scroll_delta += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32,
+                };
+                true
+            }
+
+            DeviceEvent::Motion {
+                axis,
+                value,
+            } => {
+                self.axes.insert(*axis, *value);
+                true
+            }
+
+            _ => false
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,146 @@
+use cgmath::Vector3;
+
+use crate::{pipeline::PipelineBuilder, ray::Ray};
+
+// Room for this many lines before the vertex buffer has to grow.
+const INITIAL_CAPACITY: usize = 64;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for LineVertex {}
+unsafe impl bytemuck::Pod for LineVertex {}
+
+impl LineVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float4,
+                },
+            ]
+        }
+    }
+}
+
+/// World space line segments for debugging, drawn over the frame as a line list.
+///
+/// Lines are collected between `clear` calls, so callers add what they want to
+/// see every frame. Rays are drawn `ray_length` long.
+pub struct DebugLines {
+    pub ray_length: f32,
+
+    vertices: Vec<LineVertex>,
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    // In vertices.
+    capacity: usize,
+}
+
+impl DebugLines {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, camera_layout: &wgpu::BindGroupLayout) -> Self {
+        let pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Debug Line Pipeline Layout"),
+                bind_group_layouts: &[camera_layout],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/debug_line.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/debug_line.frag.spv"));
+
+        let pipeline = PipelineBuilder::new(&pipeline_layout, &vs_module, &fs_module, format)
+            .label("Debug Line Pipeline")
+            .vertex_layout(LineVertex::desc())
+            .topology(wgpu::PrimitiveTopology::LineList)
+            .cull_mode(wgpu::CullMode::None)
+            .depth_format(None)
+            .build(device);
+
+        let capacity = INITIAL_CAPACITY * 2;
+        Self {
+            ray_length: 10.0,
+            vertices: Vec::with_capacity(capacity),
+            pipeline,
+            vertex_buffer: Self::create_vertex_buffer(device, capacity),
+            capacity,
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug Line Vertex Buffer"),
+            size: (capacity * std::mem::size_of::<LineVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn add_line(&mut self, start: Vector3<f32>, end: Vector3<f32>, color: [f32; 4]) {
+        self.vertices.push(LineVertex { position: start.into(), color });
+        self.vertices.push(LineVertex { position: end.into(), color });
+    }
+
+    /// Add the first `ray_length` of `ray`.
+    pub fn add_ray(&mut self, ray: &Ray, color: [f32; 4]) {
+        self.add_line(ray.origin, ray.at(self.ray_length), color);
+    }
+
+    /// Upload the lines and draw them over `target`, `camera_bind_group` holds the
+    /// view-projection. The vertex buffer is reallocated when the lines outgrow it.
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        camera_bind_group: &wgpu::BindGroup,
+    ) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        if self.vertices.len() > self.capacity {
+            self.capacity = self.vertices.len().next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug Line Pass"),
+            color_attachments: &[
+                wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                }
+            ],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertices.len() as u32, 0..1);
+    }
+}
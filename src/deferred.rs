@@ -0,0 +1,317 @@
+use cgmath::Vector3;
+use wgpu::util::DeviceExt;
+
+use crate::{model::{AsVertexPrimitive, Vertex}, pipeline::PipelineBuilder, post_process::{fullscreen_pass, texture_entry, sampler_entry, uniform_entry}, render_item::{DrawRenderItem, RenderItem}, texture::Texture};
+
+/// World space position, w is 1 where there is geometry. Half floats keep about
+/// three significant digits, enough for scenes within a few hundred units.
+pub const GBUFFER_POSITION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// World space unit normal, w is 1 where there is geometry.
+pub const GBUFFER_NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Diffuse texture color and alpha. The sRGB format spends the 8 bits where the
+/// eye notices, and reads back linear.
+pub const GBUFFER_ALBEDO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// The most lights the lighting pass accumulates, the rest of `lights` is ignored.
+pub const MAX_POINT_LIGHTS: usize = 32;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PointLight {
+    pub position: Vector3<f32>,
+    // Linear color, scaled by the intensity.
+    pub color: Vector3<f32>,
+    // Distance at which the light has faded out completely.
+    pub radius: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct LightUniforms {
+    position_radius: [[f32; 4]; MAX_POINT_LIGHTS],
+    color: [[f32; 4]; MAX_POINT_LIGHTS],
+    count: u32,
+    // Pad to the 16 byte multiple std140 rounds the block up to.
+    _padding: [u32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for LightUniforms {}
+unsafe impl bytemuck::Pod for LightUniforms {}
+
+/// The layouts of the bind groups `Deferred::render` takes, shared with the
+/// forward pipelines.
+pub struct DeferredLayouts<'a> {
+    pub texture: &'a wgpu::BindGroupLayout,
+    pub uniform: &'a wgpu::BindGroupLayout,
+    pub object: &'a wgpu::BindGroupLayout,
+    pub environment: &'a wgpu::BindGroupLayout,
+}
+
+/// Deferred shading of the render items with point lights.
+///
+/// The G-buffer pass draws the items into three targets at once, see the
+/// `GBUFFER_*_FORMAT`s for what each holds, with a depth buffer of its own. A
+/// fullscreen lighting pass then reads them back, adds up to `MAX_POINT_LIGHTS`
+/// of `lights` over the same SH ambient the forward pass uses, and writes the
+/// output. Its cost grows with the pixels times the lights, not the triangles.
+/// There's no MSAA, the G-buffer would have to be multisampled and resolved per
+/// sample.
+pub struct Deferred {
+    pub lights: Vec<PointLight>,
+
+    gbuffer_pipeline: wgpu::RenderPipeline,
+    lighting_pipeline: wgpu::RenderPipeline,
+    gbuffer_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    light_buffer: wgpu::Buffer,
+    light_bind_group: wgpu::BindGroup,
+
+    position_target: Texture,
+    normal_target: Texture,
+    albedo_target: Texture,
+    depth_texture: Texture,
+    gbuffer_bind_group: wgpu::BindGroup,
+}
+
+impl Deferred {
+    pub fn new(
+        device: &wgpu::Device,
+        swap_chain_desc: &wgpu::SwapChainDescriptor,
+        output_format: wgpu::TextureFormat,
+        layouts: DeferredLayouts,
+        deinterleave_vertices: bool,
+        lights: Vec<PointLight>,
+    ) -> Self {
+        let gbuffer_pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("G-Buffer Pipeline Layout"),
+                bind_group_layouts: &[layouts.texture, layouts.uniform, layouts.object],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let gbuffer_vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/gbuffer.vert.spv"));
+        let gbuffer_fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/gbuffer.frag.spv"));
+
+        let vertex_layouts = if deinterleave_vertices { Vertex::deinterleaved_desc() } else { vec![Vertex::desc()] };
+        let mut gbuffer_pipeline_builder = PipelineBuilder::new(
+            &gbuffer_pipeline_layout,
+            &gbuffer_vs_module,
+            &gbuffer_fs_module,
+            GBUFFER_POSITION_FORMAT,
+        )
+        .label("G-Buffer Pipeline")
+        .color_target(GBUFFER_NORMAL_FORMAT)
+        .color_target(GBUFFER_ALBEDO_FORMAT);
+        for layout in vertex_layouts {
+            gbuffer_pipeline_builder = gbuffer_pipeline_builder.vertex_layout(layout);
+        }
+        let gbuffer_pipeline = gbuffer_pipeline_builder.build(device);
+
+        let gbuffer_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("gbuffer_layout"),
+                entries: &[texture_entry(0), texture_entry(1), texture_entry(2), sampler_entry(3)],
+            }
+        );
+        let light_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("light_layout"),
+                entries: &[uniform_entry(0)],
+            }
+        );
+
+        let lighting_pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Deferred Lighting Pipeline Layout"),
+                bind_group_layouts: &[&gbuffer_layout, &light_layout, layouts.environment],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let lighting_vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/fullscreen.vert.spv"));
+        let lighting_fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/deferred_lighting.frag.spv"));
+
+        let lighting_pipeline = PipelineBuilder::new(&lighting_pipeline_layout, &lighting_vs_module, &lighting_fs_module, output_format)
+            .label("Deferred Lighting Pipeline")
+            .depth_format(None)
+            .cull_mode(wgpu::CullMode::None)
+            .build(device);
+
+        // The lighting pass reads texel centers only, so nothing is filtered.
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+
+        let light_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Light Buffer"),
+                contents: bytemuck::cast_slice(&[Self::light_uniforms(&lights)]),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            }
+        );
+        let light_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("light_bind_group"),
+                layout: &light_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: light_buffer.as_entire_binding(),
+                    },
+                ],
+            }
+        );
+
+        let (position_target, normal_target, albedo_target, depth_texture) = Self::create_targets(device, swap_chain_desc);
+        let gbuffer_bind_group = Self::create_gbuffer_bind_group(device, &gbuffer_layout, &sampler, [&position_target, &normal_target, &albedo_target]);
+
+        Self {
+            lights,
+            gbuffer_pipeline,
+            lighting_pipeline,
+            gbuffer_layout,
+            sampler,
+            light_buffer,
+            light_bind_group,
+            position_target,
+            normal_target,
+            albedo_target,
+            depth_texture,
+            gbuffer_bind_group,
+        }
+    }
+
+    /// Recreate the G-buffer at the swap chain's new size.
+    pub fn resize(&mut self, device: &wgpu::Device, swap_chain_desc: &wgpu::SwapChainDescriptor) {
+        let (position_target, normal_target, albedo_target, depth_texture) = Self::create_targets(device, swap_chain_desc);
+        self.gbuffer_bind_group = Self::create_gbuffer_bind_group(device, &self.gbuffer_layout, &self.sampler, [&position_target, &normal_target, &albedo_target]);
+        self.position_target = position_target;
+        self.normal_target = normal_target;
+        self.albedo_target = albedo_target;
+        self.depth_texture = depth_texture;
+    }
+
+    /// Record the G-buffer pass drawing `items` and the lighting pass writing `output`.
+    /// The bind groups are the forward pass's, for the layouts given to `new`, and
+    /// items with a `RenderItem::material` are drawn with it instead of `diffuse_bind_group`.
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        items: &[RenderItem],
+        diffuse_bind_group: &wgpu::BindGroup,
+        uniform_bind_group: &wgpu::BindGroup,
+        environment_bind_group: &wgpu::BindGroup,
+    ) {
+        queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[Self::light_uniforms(&self.lights)]));
+
+        {
+            let clear = |attachment| wgpu::RenderPassColorAttachmentDescriptor {
+                attachment,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("G-Buffer Pass"),
+                color_attachments: &[
+                    clear(&self.position_target.view),
+                    clear(&self.normal_target.view),
+                    clear(&self.albedo_target.view),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                    attachment: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+
+            render_pass.set_pipeline(&self.gbuffer_pipeline);
+            render_pass.set_bind_group(1, uniform_bind_group, &[]);
+            for item in items {
+                render_pass.set_bind_group(0, item.material.as_ref().unwrap_or(diffuse_bind_group), &[]);
+                render_pass.draw_item(item);
+            }
+        }
+
+        fullscreen_pass(
+            encoder,
+            "Deferred Lighting Pass",
+            output,
+            &self.lighting_pipeline,
+            &[&self.gbuffer_bind_group, &self.light_bind_group, environment_bind_group],
+        );
+    }
+
+    fn create_targets(device: &wgpu::Device, swap_chain_desc: &wgpu::SwapChainDescriptor) -> (Texture, Texture, Texture, Texture) {
+        let (width, height) = (swap_chain_desc.width, swap_chain_desc.height);
+        (
+            Texture::create_render_target(device, width, height, GBUFFER_POSITION_FORMAT, "gbuffer_position"),
+            Texture::create_render_target(device, width, height, GBUFFER_NORMAL_FORMAT, "gbuffer_normal"),
+            Texture::create_render_target(device, width, height, GBUFFER_ALBEDO_FORMAT, "gbuffer_albedo"),
+            Texture::create_depth_texture(device, swap_chain_desc, 1, "gbuffer_depth"),
+        )
+    }
+
+    fn create_gbuffer_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        [position, normal, albedo]: [&Texture; 3],
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("gbuffer_bind_group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&position.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&normal.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&albedo.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+            }
+        )
+    }
+
+    fn light_uniforms(lights: &[PointLight]) -> LightUniforms {
+        let mut uniforms = LightUniforms {
+            position_radius: [[0.0; 4]; MAX_POINT_LIGHTS],
+            color: [[0.0; 4]; MAX_POINT_LIGHTS],
+            count: lights.len().min(MAX_POINT_LIGHTS) as u32,
+            _padding: [0; 3],
+        };
+        for (i, light) in lights.iter().take(MAX_POINT_LIGHTS).enumerate() {
+            let p = light.position;
+            let c = light.color;
+            uniforms.position_radius[i] = [p.x, p.y, p.z, light.radius];
+            uniforms.color[i] = [c.x, c.y, c.z, 0.0];
+        }
+        uniforms
+    }
+}
\ No newline at end of file
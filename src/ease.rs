@@ -0,0 +1,109 @@
+use cgmath::{Vector3, VectorSpace};
+
+use crate::timer::Timer;
+
+// Easing curves map `t` in [0, 1] to [0, 1], starting at 0 and ending at 1.
+
+pub fn ease_in(t: f32) -> f32 {
+    t * t
+}
+
+pub fn ease_out(t: f32) -> f32 {
+    t * (2.0 - t)
+}
+
+pub fn ease_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        2.0 * t * t
+    } else {
+        -1.0 + (4.0 - 2.0 * t) * t
+    }
+}
+
+pub fn linear(t: f32) -> f32 {
+    t
+}
+
+pub trait Tweenable: Copy {
+    fn tween(self, to: Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn tween(self, to: Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Tweenable for Vector3<f32> {
+    fn tween(self, to: Self, t: f32) -> Self {
+        self.lerp(to, t)
+    }
+}
+
+/// Moves a value from `from` to `to` over `duration` seconds along an easing
+/// curve, `ease_in_out` unless changed with `easing`. Tweens chained with `then`
+/// run one after the other, the time left over when one finishes going to the next.
+pub struct Tween<T: Tweenable> {
+    from: T,
+    to: T,
+    duration: f32,
+    elapsed: f32,
+    easing: fn(f32) -> f32,
+    next: Option<Box<Tween<T>>>,
+}
+
+impl<T: Tweenable> Tween<T> {
+    pub fn new(from: T, to: T, duration: f32) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            elapsed: 0.0,
+            easing: ease_in_out,
+            next: None,
+        }
+    }
+
+    pub fn easing(mut self, easing: fn(f32) -> f32) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Run `next` once this tween and the ones already chained to it are finished.
+    pub fn then(mut self, next: Tween<T>) -> Self {
+        match self.next {
+            Some(tail) => self.next = Some(Box::new((*tail).then(next))),
+            None => self.next = Some(Box::new(next)),
+        }
+        self
+    }
+
+    /// Advance by the timer's last frame and return the new value.
+    pub fn update(&mut self, timer: &Timer) -> T {
+        self.advance(timer.delta_time());
+        self.value()
+    }
+
+    pub fn advance(&mut self, seconds: f32) {
+        self.elapsed += seconds;
+        while self.elapsed >= self.duration {
+            let next = match self.next.take() {
+                Some(next) => next,
+                None => break,
+            };
+            let left_over = self.elapsed - self.duration;
+            *self = *next;
+            self.elapsed += left_over;
+        }
+    }
+
+    pub fn value(&self) -> T {
+        let t = if self.duration > 0.0 { (self.elapsed / self.duration).min(1.0) } else { 1.0 };
+        self.from.tween(self.to, (self.easing)(t))
+    }
+
+    /// Whether this tween and every tween chained after it have finished.
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration && self.next.is_none()
+    }
+}
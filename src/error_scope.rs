@@ -0,0 +1,109 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+
+/// Error scopes for wgpu 0.7, which lacks `push_error_scope`/`pop_error_scope`.
+///
+/// Native wgpu reports validation errors synchronously, through the device's
+/// uncaptured error handler, from inside the call that caused them. So this
+/// installs a handler that collects the errors while a scope is open, and keeps
+/// the default behavior of panicking on those raised outside any scope.
+#[derive(Clone)]
+pub struct ErrorScopes {
+    // One list of errors per open scope, the innermost last.
+    scopes: Arc<Mutex<Vec<Vec<wgpu::Error>>>>,
+}
+
+impl ErrorScopes {
+    /// Take over `device`'s uncaptured error handler. Only one `ErrorScopes`
+    /// should be installed per device, the last one replaces the others.
+    pub fn install(device: &wgpu::Device) -> Self {
+        let scopes: Arc<Mutex<Vec<Vec<wgpu::Error>>>> = Arc::new(Mutex::new(Vec::new()));
+        let handler_scopes = scopes.clone();
+        device.on_uncaptured_error(move |error| {
+            let mut scopes = handler_scopes.lock().unwrap();
+            if let Some(scope) = scopes.last_mut() {
+                scope.push(error);
+                return;
+            }
+            // Unlocked first, panicking with the guard held would poison it for every later scope.
+            drop(scopes);
+            panic!("wgpu error: {}", error);
+        });
+        Self { scopes }
+    }
+
+    pub fn push(&self) {
+        self.scopes.lock().unwrap().push(Vec::new());
+    }
+
+    /// Close the innermost scope, returning the first error raised in it.
+    pub fn pop(&self) -> Option<wgpu::Error> {
+        let mut errors = self.scopes.lock().unwrap().pop().expect("pop without a matching push");
+        if errors.is_empty() {
+            None
+        } else {
+            Some(errors.remove(0))
+        }
+    }
+
+    /// Run `f` in its own scope, turning an error raised by it into `Err`. The
+    /// message is wgpu's, which names the object's label and, for shaders, the
+    /// location the parser or validator stopped at.
+    pub fn scoped<T>(&self, f: impl FnOnce() -> T) -> Result<T> {
+        self.push();
+        let value = f();
+        match self.pop() {
+            Some(error) => Err(anyhow!("{}", error)),
+            None => Ok(value),
+        }
+    }
+
+    /// `create_shader_module`, failing instead of panicking on a module that
+    /// doesn't parse or validate.
+    pub fn create_shader_module(&self, device: &wgpu::Device, desc: &wgpu::ShaderModuleDescriptor) -> Result<wgpu::ShaderModule> {
+        self.scoped(|| device.create_shader_module(desc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use super::*;
+    use crate::render_check::gpu::headless_device;
+
+    const BROKEN_SHADER: &str = "[[stage(vertex)]] fn main( -> {";
+
+    fn broken_shader_desc() -> wgpu::ShaderModuleDescriptor<'static> {
+        wgpu::ShaderModuleDescriptor {
+            label: Some("broken_shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(BROKEN_SHADER)),
+            flags: wgpu::ShaderFlags::VALIDATION,
+        }
+    }
+
+    #[test]
+    fn broken_shader_is_an_error() {
+        let (device, _queue) = match headless_device() {
+            Some(device) => device,
+            None => return eprintln!("no adapter, skipping the error scope check"),
+        };
+        let error_scopes = ErrorScopes::install(&device);
+        assert!(error_scopes.create_shader_module(&device, &broken_shader_desc()).is_err());
+        assert!(error_scopes.scoped(|| ()).is_ok());
+    }
+
+    #[test]
+    fn unscoped_error_panics_without_poisoning() {
+        let (device, _queue) = match headless_device() {
+            Some(device) => device,
+            None => return eprintln!("no adapter, skipping the error scope check"),
+        };
+        let error_scopes = ErrorScopes::install(&device);
+        let unscoped = catch_unwind(AssertUnwindSafe(|| device.create_shader_module(&broken_shader_desc())));
+        assert!(unscoped.is_err());
+        assert!(error_scopes.create_shader_module(&device, &broken_shader_desc()).is_err());
+    }
+}
\ No newline at end of file
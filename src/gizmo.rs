@@ -0,0 +1,254 @@
+use cgmath::{Decomposed, InnerSpace, Quaternion, Vector3};
+use wgpu::util::DeviceExt;
+
+use crate::{camera::Camera, model::{AsVertexPrimitive, Axis, Mesh, Vertex}, pipeline::PipelineBuilder, ray::Ray, render_item::{DrawRenderItem, RenderItem}};
+
+// Handle geometry in gizmo units, the whole arrow is one unit long.
+const SHAFT_RADIUS: f32 = 0.03;
+const SHAFT_LENGTH: f32 = 0.8;
+const HEAD_RADIUS: f32 = 0.08;
+const HEAD_LENGTH: f32 = 0.2;
+// Rays closer than this to a handle's axis grab it, a bit wider than the head.
+const PICK_RADIUS: f32 = 0.1;
+
+const ACTIVE_COLOR: [f32; 4] = [1.0, 0.9, 0.1, 1.0];
+
+fn handle_color(axis: Axis) -> [f32; 4] {
+    match axis {
+        Axis::X => [0.9, 0.2, 0.2, 1.0],
+        Axis::Y => [0.2, 0.9, 0.2, 1.0],
+        Axis::Z => [0.2, 0.4, 1.0, 1.0],
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct HandleUniforms {
+    color: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for HandleUniforms {}
+unsafe impl bytemuck::Pod for HandleUniforms {}
+
+struct Drag {
+    axis: Axis,
+    start_position: Vector3<f32>,
+    start_t: f32,
+}
+
+/// A translate manipulator, three arrows along the world axes at `position`.
+///
+/// Dragging a handle moves `position` along that handle's axis, following the
+/// point of the axis closest to the mouse ray. The handles are scaled with their
+/// distance to the camera, so they always cover `screen_size` of the viewport
+/// height.
+pub struct Gizmo {
+    pub position: Vector3<f32>,
+    pub screen_size: f32,
+
+    drag: Option<Drag>,
+    pipeline: wgpu::RenderPipeline,
+    handles: Vec<RenderItem>,
+    handle_buffers: Vec<wgpu::Buffer>,
+    handle_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl Gizmo {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_layout: &wgpu::BindGroupLayout,
+        object_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let handle_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("gizmo_handle_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            }
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Gizmo Pipeline Layout"),
+                bind_group_layouts: &[camera_layout, &handle_layout, object_layout],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/gizmo.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/gizmo.frag.spv"));
+
+        // Drawn over the finished frame without a depth buffer, so it's never hidden by the scene.
+        let pipeline = PipelineBuilder::new(&pipeline_layout, &vs_module, &fs_module, format)
+            .label("Gizmo Pipeline")
+            .vertex_layout(Vertex::desc())
+            .depth_format(None)
+            .build(device);
+
+        let arrow = Mesh::arrow(SHAFT_RADIUS, SHAFT_LENGTH, HEAD_RADIUS, HEAD_LENGTH, 16);
+        let handles = Axis::ALL
+            .iter()
+            .map(|axis| RenderItem::from_mesh(device, object_layout, &arrow, &format!("gizmo {:?}", axis)))
+            .collect();
+
+        let handle_buffers: Vec<_> = Axis::ALL
+            .iter()
+            .map(|axis| {
+                device.create_buffer_init(
+                    &wgpu::util::BufferInitDescriptor {
+                        label: Some(&format!("Gizmo {:?} Handle Buffer", axis)),
+                        contents: bytemuck::cast_slice(&[HandleUniforms { color: handle_color(axis) }]),
+                        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                    }
+                )
+            })
+            .collect();
+
+        let handle_bind_groups = handle_buffers
+            .iter()
+            .map(|buffer| {
+                device.create_bind_group(
+                    &wgpu::BindGroupDescriptor {
+                        label: Some("gizmo_handle_bind_group"),
+                        layout: &handle_layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: buffer.as_entire_binding(),
+                            },
+                        ],
+                    }
+                )
+            })
+            .collect();
+
+        Self {
+            position: Vector3::new(0.0, 0.0, 0.0),
+            screen_size: 0.2,
+            drag: None,
+            pipeline,
+            handles,
+            handle_buffers,
+            handle_bind_groups,
+        }
+    }
+
+    /// World space length of a handle, so it spans `screen_size` of the viewport height.
+    pub fn scale(&self, camera: &Camera) -> f32 {
+        let depth = (self.position - camera.transform().disp).dot(camera.forward()).abs();
+        // The projection's y scale is the cotangent of half the vertical field of view.
+        self.screen_size * 2.0 * depth / camera.get_proj().y.y
+    }
+
+    /// The handle under `ray`, the nearest one along the ray when several are.
+    pub fn hit_test(&self, ray: &Ray, camera: &Camera) -> Option<Axis> {
+        let scale = self.scale(camera);
+        let mut hit = None;
+        let mut nearest = f32::INFINITY;
+
+        for &axis in &Axis::ALL {
+            let direction = axis.direction();
+            let (ray_t, axis_t) = match ray.closest_to_line(self.position, direction) {
+                Some(closest) => closest,
+                None => continue,
+            };
+            let on_handle = self.position + direction * axis_t.max(0.0).min(scale);
+            if ray_t >= 0.0 && ray_t < nearest && ray.distance_to_point(on_handle) < PICK_RADIUS * scale {
+                hit = Some(axis);
+                nearest = ray_t;
+            }
+        }
+        hit
+    }
+
+    /// Start dragging the handle under `ray`, returns whether one was hit.
+    pub fn begin_drag(&mut self, ray: &Ray, camera: &Camera) -> bool {
+        let axis = match self.hit_test(ray, camera) {
+            Some(axis) => axis,
+            None => return false,
+        };
+        if let Some((_, start_t)) = ray.closest_to_line(self.position, axis.direction()) {
+            self.drag = Some(Drag {
+                axis,
+                start_position: self.position,
+                start_t,
+            });
+        }
+        self.drag.is_some()
+    }
+
+    /// Move `position` along the dragged axis to follow `ray`.
+    pub fn drag(&mut self, ray: &Ray) {
+        if let Some(drag) = &self.drag {
+            let direction = drag.axis.direction();
+            // Looking straight down the axis there's no closest point, keep the last position.
+            if let Some((_, t)) = ray.closest_to_line(drag.start_position, direction) {
+                self.position = drag.start_position + direction * (t - drag.start_t);
+            }
+        }
+    }
+
+    /// Stop dragging, returns whether a drag was in progress.
+    pub fn end_drag(&mut self) -> bool {
+        self.drag.take().is_some()
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Upload the handle transforms and colors for the current camera.
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera) {
+        let scale = self.scale(camera);
+        let active = self.drag.as_ref().map(|drag| drag.axis);
+
+        for (i, &axis) in Axis::ALL.iter().enumerate() {
+            let handle = &mut self.handles[i];
+            handle.transform = Decomposed {
+                scale,
+                rot: Quaternion::from_arc(Vector3::unit_y(), axis.direction(), None),
+                disp: self.position,
+            };
+            handle.write_transform(queue);
+
+            let color = if active == Some(axis) { ACTIVE_COLOR } else { handle_color(axis) };
+            queue.write_buffer(&self.handle_buffers[i], 0, bytemuck::cast_slice(&[HandleUniforms { color }]));
+        }
+    }
+
+    /// Draw the handles over `target`, `camera_bind_group` holds the view-projection.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, camera_bind_group: &wgpu::BindGroup) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Gizmo Pass"),
+            color_attachments: &[
+                wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                }
+            ],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        for (handle, bind_group) in self.handles.iter().zip(&self.handle_bind_groups) {
+            render_pass.set_bind_group(1, bind_group, &[]);
+            render_pass.draw_item(handle);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,139 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static BUFFER_BYTES: AtomicU64 = AtomicU64::new(0);
+static BUFFER_COUNT: AtomicU64 = AtomicU64::new(0);
+static TEXTURE_BYTES: AtomicU64 = AtomicU64::new(0);
+static TEXTURE_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Count the memory of `Texture`s and `RenderItem`s created from now on. Off by
+/// default, when nothing is counted.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Approximate GPU memory held by the crate's textures and render item buffers,
+/// from their sizes as created, not from the driver. Alignment, padding and
+/// driver overhead aren't included, and neither are the swap chain or buffers
+/// created outside `Texture` and `RenderItem`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GpuReport {
+    pub buffers: u64,
+    pub buffer_bytes: u64,
+    pub textures: u64,
+    pub texture_bytes: u64,
+}
+
+impl GpuReport {
+    /// What's alive right now.
+    pub fn current() -> Self {
+        Self {
+            buffers: BUFFER_COUNT.load(Ordering::Relaxed),
+            buffer_bytes: BUFFER_BYTES.load(Ordering::Relaxed),
+            textures: TEXTURE_COUNT.load(Ordering::Relaxed),
+            texture_bytes: TEXTURE_BYTES.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.buffer_bytes + self.texture_bytes
+    }
+}
+
+impl fmt::Display for GpuReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        write!(
+            f,
+            "{} buffers: {:.2} MiB, {} textures: {:.2} MiB, total: {:.2} MiB",
+            self.buffers,
+            mib(self.buffer_bytes),
+            self.textures,
+            mib(self.texture_bytes),
+            mib(self.total_bytes()),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Buffer,
+    Texture,
+}
+
+impl Kind {
+    fn counters(self) -> (&'static AtomicU64, &'static AtomicU64) {
+        match self {
+            Kind::Buffer => (&BUFFER_COUNT, &BUFFER_BYTES),
+            Kind::Texture => (&TEXTURE_COUNT, &TEXTURE_BYTES),
+        }
+    }
+}
+
+/// Counted in the `GpuReport` while alive, kept next to the resource it stands
+/// for. Remembers whether tracking was on when it was created, so toggling
+/// `set_enabled` later never subtracts what wasn't added.
+#[derive(Debug)]
+pub(crate) struct Allocation {
+    kind: Kind,
+    count: u64,
+    bytes: u64,
+    tracked: bool,
+}
+
+impl Allocation {
+    /// `count` buffers of `bytes` in total.
+    pub(crate) fn buffers(count: u64, bytes: u64) -> Self {
+        Self::new(Kind::Buffer, count, bytes)
+    }
+
+    pub(crate) fn texture(desc: &wgpu::TextureDescriptor) -> Self {
+        let info = desc.format.describe();
+        let (block_width, block_height) = (info.block_dimensions.0 as u64, info.block_dimensions.1 as u64);
+        let bytes: u64 = (0..desc.mip_level_count)
+            .map(|level| {
+                let width = (desc.size.width >> level).max(1) as u64;
+                let height = (desc.size.height >> level).max(1) as u64;
+                let blocks = ((width + block_width - 1) / block_width) * ((height + block_height - 1) / block_height);
+                blocks * info.block_size as u64 * desc.size.depth as u64
+            })
+            .sum();
+        Self::new(Kind::Texture, 1, bytes * desc.sample_count as u64)
+    }
+
+    fn new(kind: Kind, count: u64, bytes: u64) -> Self {
+        let tracked = is_enabled();
+        if tracked {
+            let (count_counter, bytes_counter) = kind.counters();
+            count_counter.fetch_add(count, Ordering::Relaxed);
+            bytes_counter.fetch_add(bytes, Ordering::Relaxed);
+        }
+        Self { kind, count, bytes, tracked }
+    }
+
+    /// Count `count` more buffers of `bytes` in total, e.g. one added to a render item.
+    pub(crate) fn add_buffers(&mut self, count: u64, bytes: u64) {
+        if self.tracked {
+            let (count_counter, bytes_counter) = self.kind.counters();
+            count_counter.fetch_add(count, Ordering::Relaxed);
+            bytes_counter.fetch_add(bytes, Ordering::Relaxed);
+        }
+        self.count += count;
+        self.bytes += bytes;
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        if self.tracked {
+            let (count_counter, bytes_counter) = self.kind.counters();
+            count_counter.fetch_sub(self.count, Ordering::Relaxed);
+            bytes_counter.fetch_sub(self.bytes, Ordering::Relaxed);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,200 @@
+use futures::task::SpawnExt;
+use wgpu::util::DeviceExt;
+use wgpu_glyph::{ab_glyph, GlyphBrush, GlyphBrushBuilder, Section, Text};
+
+use crate::pipeline::PipelineBuilder;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct PanelVertex {
+    // In normalized device coordinates.
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for PanelVertex {}
+unsafe impl bytemuck::Pod for PanelVertex {}
+
+impl PanelVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<PanelVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float4,
+                },
+            ]
+        }
+    }
+}
+
+// A queued panel, in pixels.
+struct Panel {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    color: [f32; 4],
+}
+
+/// Screen-space text drawn over the rendered scene, on optional translucent panels.
+pub struct Hud {
+    glyph_brush: GlyphBrush<()>,
+    panel_pipeline: wgpu::RenderPipeline,
+    panels: Vec<Panel>,
+    staging_belt: wgpu::util::StagingBelt,
+    local_pool: futures::executor::LocalPool,
+    pub font_size: f32,
+    pub color: [f32; 4],
+}
+
+impl Hud {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, font_size: f32, color: [f32; 4]) -> Self {
+        let font = ab_glyph::FontArc::try_from_slice(
+            include_bytes!("resources/fonts/Inconsolata-Regular.ttf")
+        ).unwrap();
+
+        let pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Panel Pipeline Layout"),
+                bind_group_layouts: &[],
+                push_constant_ranges: &[],
+            }
+        );
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/panel.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/debug_line.frag.spv"));
+        let panel_pipeline = PipelineBuilder::new(&pipeline_layout, &vs_module, &fs_module, format)
+            .label("Panel Pipeline")
+            .vertex_layout(PanelVertex::desc())
+            .cull_mode(wgpu::CullMode::None)
+            .depth_format(None)
+            .alpha_blending()
+            .build(device);
+
+        Self {
+            glyph_brush: GlyphBrushBuilder::using_font(font).build(device, format),
+            panel_pipeline,
+            panels: Vec::new(),
+            staging_belt: wgpu::util::StagingBelt::new(1024),
+            local_pool: futures::executor::LocalPool::new(),
+            font_size,
+            color,
+        }
+    }
+
+    /// Queue `text` with its top-left corner at pixel (`x`, `y`).
+    pub fn draw_text(&mut self, x: f32, y: f32, text: &str) {
+        self.glyph_brush.queue(Section {
+            screen_position: (x, y),
+            text: vec![
+                Text::new(text)
+                    .with_color(self.color)
+                    .with_scale(self.font_size)
+            ],
+            ..Section::default()
+        });
+    }
+
+    /// Queue a rectangle of `color` with its top-left corner at pixel (`x`, `y`),
+    /// drawn behind all queued text. A `color` alpha below 1 keeps the scene visible.
+    pub fn draw_panel(&mut self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+        self.panels.push(Panel { x, y, width, height, color });
+    }
+
+    /// Approximate width in pixels of `text` at `font_size`, from the advance of
+    /// the monospaced font, for sizing panels around it.
+    pub fn text_width(&self, text: &str) -> f32 {
+        text.chars().count() as f32 * self.font_size * 0.5
+    }
+
+    /// Draw all queued panels and text into `target`, alpha blended over its current
+    /// contents, so this has to be recorded after the 3D pass.
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) {
+        if !self.panels.is_empty() {
+            self.render_panels(device, encoder, target, width, height);
+        }
+        self.glyph_brush
+            .draw_queued(device, &mut self.staging_belt, encoder, target, width, height)
+            .unwrap();
+        self.staging_belt.finish();
+    }
+
+    fn render_panels(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) {
+        let to_ndc = |x: f32, y: f32| [2.0 * x / width as f32 - 1.0, 1.0 - 2.0 * y / height as f32];
+        let vertices: Vec<PanelVertex> = self.panels
+            .drain(..)
+            .flat_map(|panel| {
+                let top_left = to_ndc(panel.x, panel.y);
+                let bottom_right = to_ndc(panel.x + panel.width, panel.y + panel.height);
+                let corner = |x: f32, y: f32| PanelVertex { position: [x, y], color: panel.color };
+                vec![
+                    corner(top_left[0], top_left[1]),
+                    corner(top_left[0], bottom_right[1]),
+                    corner(bottom_right[0], bottom_right[1]),
+                    corner(top_left[0], top_left[1]),
+                    corner(bottom_right[0], bottom_right[1]),
+                    corner(bottom_right[0], top_left[1]),
+                ]
+            })
+            .collect();
+
+        // A handful of panels at most, so a fresh buffer per frame is fine.
+        let vertex_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Panel Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsage::VERTEX,
+            }
+        );
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Panel Pass"),
+            color_attachments: &[
+                wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                }
+            ],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.panel_pipeline);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+    }
+
+    /// Reclaim the staging buffers, call after the frame has been submitted.
+    pub fn recall(&mut self) {
+        self.local_pool
+            .spawner()
+            .spawn(self.staging_belt.recall())
+            .unwrap();
+        self.local_pool.run_until_stalled();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{model::Mesh, render_item::RenderItem, texture::Texture};
+
+// Decoding threads, enough to keep a few large images going at once.
+const WORKER_COUNT: usize = 4;
+
+/// Refers to a texture requested from a `ResourceLoader`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureHandle(usize);
+
+/// Refers to a mesh requested from a `ResourceLoader`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeshHandle(usize);
+
+type Job = Box<dyn FnOnce() -> Decoded + Send>;
+
+// What a worker hands back to the render thread for uploading.
+enum Decoded {
+    Texture(usize, String, Result<image::DynamicImage>),
+    Mesh(usize, String, Result<Mesh>),
+}
+
+/// Loads textures and meshes in the background.
+///
+/// Reading files, decoding images, parsing and generating meshes run on a pool
+/// of worker threads. Creating the GPU resources from the results stays on the
+/// thread owning the `Device` and `Queue`, in `upload`, since not every backend
+/// lets them be used from other threads. So the render thread calls `upload`,
+/// once per frame or in a loop with `wait`, and each handle resolves to its
+/// resource there.
+pub struct ResourceLoader {
+    jobs: Option<mpsc::Sender<Job>>,
+    decoded: mpsc::Receiver<Decoded>,
+    workers: Vec<JoinHandle<()>>,
+    next_id: usize,
+    pending: usize,
+    textures: HashMap<usize, Result<Texture>>,
+    meshes: HashMap<usize, Result<RenderItem>>,
+}
+
+impl ResourceLoader {
+    pub fn new() -> Self {
+        let (job_sender, job_receiver) = mpsc::channel::<Job>();
+        let (decoded_sender, decoded) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..WORKER_COUNT)
+            .map(|i| {
+                let job_receiver = job_receiver.clone();
+                let decoded_sender = decoded_sender.clone();
+                thread::Builder::new()
+                    .name(format!("resource loader {}", i))
+                    .spawn(move || loop {
+                        // The lock is only held while waiting, not while the job runs.
+                        let job = job_receiver.lock().unwrap().recv();
+                        match job {
+                            Ok(job) => {
+                                if decoded_sender.send(job()).is_err() {
+                                    break;
+                                }
+                            }
+                            // The loader was dropped.
+                            Err(_) => break,
+                        }
+                    })
+                    .unwrap()
+            })
+            .collect();
+
+        Self {
+            jobs: Some(job_sender),
+            decoded,
+            workers,
+            next_id: 0,
+            pending: 0,
+            textures: HashMap::new(),
+            meshes: HashMap::new(),
+        }
+    }
+
+    /// Read and decode the image at `path` in the background.
+    pub fn load_texture(&mut self, path: impl Into<PathBuf>) -> TextureHandle {
+        let path = path.into();
+        let id = self.submit(Box::new(move |id| {
+            let label = path.display().to_string();
+            let image = catch_panic(|| Ok(image::open(&path)?))
+                .with_context(|| format!("loading texture {}", label))
+                // `Texture::from_image` takes RGBA8 only.
+                .map(|image| image::DynamicImage::ImageRgba8(image.to_rgba8()));
+            Decoded::Texture(id, label, image)
+        }));
+        TextureHandle(id)
+    }
+
+    /// Read and parse the OBJ file at `path` in the background, see `Mesh::from_obj`.
+    pub fn load_mesh(&mut self, path: impl Into<PathBuf>) -> MeshHandle {
+        let path = path.into();
+        self.load_mesh_with(path.display().to_string(), move || {
+            let source = std::fs::read_to_string(&path)?;
+            Mesh::from_obj(&source)
+        })
+    }
+
+    /// Build a mesh with `build` in the background, e.g. a generator too slow to
+    /// run on the render thread.
+    pub fn load_mesh_with(
+        &mut self,
+        label: impl Into<String>,
+        build: impl FnOnce() -> Result<Mesh> + Send + 'static,
+    ) -> MeshHandle {
+        let label = label.into();
+        let id = self.submit(Box::new(move |id| {
+            let mesh = catch_panic(build).with_context(|| format!("loading mesh {}", label));
+            Decoded::Mesh(id, label, mesh)
+        }));
+        MeshHandle(id)
+    }
+
+    fn submit(&mut self, job: Box<dyn FnOnce(usize) -> Decoded + Send>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending += 1;
+        self.jobs.as_ref().unwrap().send(Box::new(move || job(id))).unwrap();
+        id
+    }
+
+    /// Create the GPU resources for everything decoded so far, without waiting.
+    /// Must be called on the thread owning `device` and `queue`.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, object_layout: &wgpu::BindGroupLayout) {
+        while let Ok(decoded) = self.decoded.try_recv() {
+            self.upload_decoded(device, queue, object_layout, decoded);
+        }
+    }
+
+    /// `upload` until every request so far is resolved, blocking on the workers.
+    pub fn wait(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, object_layout: &wgpu::BindGroupLayout) {
+        while self.pending > 0 {
+            // Workers only stop once the loader is dropped, a panicking job becomes an error.
+            let decoded = self.decoded.recv().expect("resource loader workers stopped");
+            self.upload_decoded(device, queue, object_layout, decoded);
+        }
+    }
+
+    fn upload_decoded(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, object_layout: &wgpu::BindGroupLayout, decoded: Decoded) {
+        self.pending -= 1;
+        match decoded {
+            Decoded::Texture(id, label, image) => {
+                let texture = image.and_then(|image| Texture::from_image(device, queue, &image, Some(&label)));
+                if let Err(e) = &texture {
+                    log::warn!("{:#}", e);
+                }
+                self.textures.insert(id, texture);
+            }
+            Decoded::Mesh(id, label, mesh) => {
+                let item = mesh.map(|mesh| RenderItem::from_mesh(device, object_layout, &mesh, &label));
+                if let Err(e) = &item {
+                    log::warn!("{:#}", e);
+                }
+                self.meshes.insert(id, item);
+            }
+        }
+    }
+
+    /// Requests not uploaded yet.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// `None` until the texture is uploaded, then the texture or why it failed.
+    pub fn texture(&self, handle: TextureHandle) -> Option<&Result<Texture>> {
+        self.textures.get(&handle.0)
+    }
+
+    /// `None` until the mesh is uploaded, then its render item or why it failed.
+    pub fn mesh(&self, handle: MeshHandle) -> Option<&Result<RenderItem>> {
+        self.meshes.get(&handle.0)
+    }
+
+    /// Move an uploaded mesh's render item out of the loader.
+    pub fn take_mesh(&mut self, handle: MeshHandle) -> Option<Result<RenderItem>> {
+        self.meshes.remove(&handle.0)
+    }
+}
+
+/// Run `f` on a worker, turning a panic in it into an error. The request then
+/// still resolves, rather than leaving `wait` blocked on a result that never comes.
+fn catch_panic<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        Err(anyhow!("panicked: {}", message))
+    })
+}
+
+impl Drop for ResourceLoader {
+    fn drop(&mut self) {
+        // Closing the job channel stops the workers once the queued jobs are done.
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_check::gpu::headless_device;
+
+    #[test]
+    fn panicking_job_resolves_to_an_error() {
+        let (device, queue) = match headless_device() {
+            Some(device) => device,
+            None => return eprintln!("no adapter, skipping the loader check"),
+        };
+        let object_layout = RenderItem::create_object_layout(&device);
+        let mut loader = ResourceLoader::new();
+        let panicking = loader.load_mesh_with("panicking", || panic!("generator bug"));
+        let fine = loader.load_mesh_with("fine", || Ok(Mesh::brick(1.0, 1.0, 1.0, 0)));
+
+        loader.wait(&device, &queue, &object_layout);
+        let error = loader.mesh(panicking).unwrap().as_ref().err().unwrap();
+        assert!(format!("{:#}", error).contains("generator bug"));
+        assert!(loader.mesh(fine).unwrap().is_ok());
+    }
+}
\ No newline at end of file
@@ -4,6 +4,33 @@ mod controller;
 mod timer;
 mod model;
 mod application;
+mod render_item;
+mod pipeline;
+mod hud;
+mod post_process;
+mod benchmark;
+mod shadow;
+mod ray;
+mod gizmo;
+mod ease;
+mod debug_lines;
+mod orbit_camera;
+mod sh;
+mod error_scope;
+mod deferred;
+#[cfg(test)]
+mod render_check;
+mod loader;
+mod gpu_memory;
+mod scene;
+mod mesh_cache;
+mod outline;
+mod noise;
+mod terrain;
+mod oit;
+mod morph;
+mod wireframe;
+mod batching;
 
 use cgmath::{Decomposed, Deg, InnerSpace, Matrix4, One, PerspectiveFov, Quaternion, Rotation3, Vector3, Zero};
 use controller::Controller;
@@ -12,27 +39,123 @@ use timer::Timer;
 use wgpu::util::DeviceExt;
 use winit::{dpi::LogicalSize, event::*, event_loop::{ControlFlow, EventLoop}, window::{WindowBuilder, Window}};
 use camera::Camera;
-use crate::{application::Application, model::Mesh};
+use crate::{application::{Application, RedrawMode}, model::{GridUvMode, Material, Mesh, Model}, oit::Translucent, terrain::Terrain};
 use crate::model::{Vertex, AsVertexPrimitive};
 
 fn main() {
-    let meshs = vec![
-        Mesh::geo_sphere(1.0, 10),
-    ];
+    // Logs go to stderr, filtered by RUST_LOG, e.g. RUST_LOG=pbr_wgpu_demo=debug.
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().collect();
+
+    // `--scene <dir>` lays out the OBJ files in that directory in place of the sphere.
+    let scene_directory = args.iter().position(|arg| arg == "--scene").and_then(|i| args.get(i + 1));
+
+    let meshs = if scene_directory.is_some() {
+        Vec::new()
+    } else {
+        vec![
+            Mesh::geo_sphere(1.0, 10),
+        ]
+    };
 
     let width = 800u32;
     let height = 600u32;
 
     let camera = Camera::new(width as f32 / height as f32);
 
-    let app = Application {
+    let mut app = Application::new(
         meshs,
         camera,
-        size: LogicalSize {
+        LogicalSize {
             width,
             height
         },
-    };
+    )
+    .with_validation(args.iter().any(|arg| arg == "--validation"));
+
+    if let Some(dir) = scene_directory {
+        app = app.with_scene_directory(dir);
+    }
+
+    // `--material-override` adds a brick beside the sphere, drawn plain white instead of textured.
+    if args.iter().any(|arg| arg == "--material-override") {
+        let mut model = Model::new(vec![Mesh::brick(1.0, 1.0, 1.0, 0)]);
+        model.transform.disp = Vector3::new(2.5, 0.0, 0.0);
+        model.set_material_override(Some(Material::WHITE));
+        app = app.with_model(model);
+    }
+
+    // `--trace <dir>` records the wgpu calls into that directory.
+    if let Some(path) = args.iter().position(|arg| arg == "--trace").and_then(|i| args.get(i + 1)) {
+        app = app.with_trace(path);
+    }
+
+    // `--gpu-memory` counts texture and buffer memory, printed with P.
+    app = app.with_gpu_memory_tracking(args.iter().any(|arg| arg == "--gpu-memory"));
+
+    // `--deferred` shades the scene with point lights through a G-buffer.
+    app.deferred_enabled = args.iter().any(|arg| arg == "--deferred");
+
+    // `--wait` only draws after input instead of continuously, to save power.
+    if args.iter().any(|arg| arg == "--wait") {
+        app = app.with_redraw_mode(RedrawMode::Wait);
+    }
+
+    // `--depth-prepass` draws the meshes' depth first so each pixel is shaded once.
+    app = app.with_depth_prepass(args.iter().any(|arg| arg == "--depth-prepass"));
+
+    // `--msaa <samples>` starts with that many samples per pixel, 1 or 4.
+    if let Some(sample_count) = args.iter().position(|arg| arg == "--msaa").and_then(|i| args.get(i + 1)).and_then(|n| n.parse().ok()) {
+        app = app.with_sample_count(sample_count);
+    }
+
+    // `--terrain <ground> <rock> <snow>` adds a noise displaced grid blending those three images.
+    if let Some(paths) = args.iter().position(|arg| arg == "--terrain").and_then(|i| args.get(i + 1..i + 4)) {
+        let layers: Result<Vec<_>, _> = paths.iter().map(image::open).collect();
+        match layers {
+            Ok(layers) => {
+                let mut mesh = Mesh::grid(40.0, 40.0, 200, 200, GridUvMode::Stretch);
+                mesh.displace_noise(0.08, 2.5, 7);
+                app = app.with_terrain(Terrain::new(mesh, layers));
+            }
+            Err(e) => log::warn!("Leaving the terrain out: {}", e),
+        }
+    }
+
+    // `--morph` swells the sphere into a lumpy one and back, blended on the GPU.
+    if args.iter().any(|arg| arg == "--morph") {
+        let mut target = Mesh::geo_sphere(1.0, 10);
+        for vertex in &mut target.vertices {
+            let p = vertex.position * 2.0;
+            vertex.position *= 1.0 + 0.3 * noise::perlin_3d(3, p.x, p.y, p.z);
+        }
+        target.recompute_normals();
+        app = app.with_morph_target(target);
+    }
+
+    // `--oit` adds a ring of overlapping translucent spheres, blended without sorting.
+    if args.iter().any(|arg| arg == "--oit") {
+        let colors = [[1.0, 0.2, 0.2, 0.4], [0.2, 1.0, 0.2, 0.4], [0.2, 0.2, 1.0, 0.4], [1.0, 1.0, 0.2, 0.4], [0.2, 1.0, 1.0, 0.4], [1.0, 0.2, 1.0, 0.4]];
+        for (i, &color) in colors.iter().enumerate() {
+            let angle = i as f32 / colors.len() as f32 * std::f32::consts::TAU;
+            app = app.with_translucent(Translucent {
+                mesh: Mesh::geo_sphere(0.8, 3),
+                position: Vector3::new(1.2 * angle.cos(), 0.5, 1.2 * angle.sin()),
+                color,
+            });
+        }
+        app = app.with_oit(true);
+    }
+
+    // `--benchmark <frames>` renders that many frames without vsync and prints the timings.
+    let benchmark_frames = args.iter()
+        .position(|arg| arg == "--benchmark")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|frames| frames.parse().ok());
 
-    app.run();
-}
+    match benchmark_frames {
+        Some(frames) => println!("{}", app.benchmark(frames)),
+        None => app.run(),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::{model::Mesh, render_item::{MeshBuffers, RenderItem}};
+
+/// Uploaded meshes by `Mesh::content_hash`, so asking again for a mesh that's
+/// already on the GPU, e.g. one regenerated every frame or switched back to,
+/// reuses its vertex and index buffers instead of uploading new ones.
+///
+/// Each request gets a `RenderItem` of its own, sharing the buffers through an
+/// `Rc` but with its own transform and object uniform, so items can be placed,
+/// moved or handed to a batch without affecting the others drawing the mesh.
+///
+/// The hash only covers positions and indices, so meshes differing in nothing
+/// but their normals, tangents or texture coordinates share the buffers of the
+/// first one uploaded.
+///
+/// Nothing is evicted on its own: the cache is unbounded, growing with every
+/// distinct mesh until `evict_unused` or `clear` is called.
+pub struct MeshCache {
+    // Upload each vertex attribute into its own buffer, see `RenderItem::from_mesh_deinterleaved`.
+    deinterleave_vertices: bool,
+    buffers: HashMap<u64, Rc<MeshBuffers>>,
+}
+
+impl MeshCache {
+    pub fn new(deinterleave_vertices: bool) -> Self {
+        Self {
+            deinterleave_vertices,
+            buffers: HashMap::new(),
+        }
+    }
+
+    /// An item drawing `mesh`, its buffers uploaded with `label` unless an equal
+    /// mesh's were before.
+    pub fn get_or_upload(
+        &mut self,
+        device: &wgpu::Device,
+        object_layout: &wgpu::BindGroupLayout,
+        mesh: &Mesh,
+        label: &str,
+    ) -> RenderItem {
+        let hash = mesh.content_hash();
+        if let Some(buffers) = self.buffers.get(&hash) {
+            return RenderItem::from_buffers(device, object_layout, Rc::clone(buffers), label);
+        }
+
+        let item = if self.deinterleave_vertices {
+            RenderItem::from_mesh_deinterleaved(device, object_layout, mesh, label)
+        } else {
+            RenderItem::from_mesh(device, object_layout, mesh, label)
+        };
+        self.buffers.insert(hash, Rc::clone(&item.buffers));
+        item
+    }
+
+    /// Drop the buffers no item draws anymore, freeing them, e.g. after switching
+    /// scenes.
+    pub fn evict_unused(&mut self) {
+        self.buffers.retain(|_, buffers| Rc::strong_count(buffers) > 1);
+    }
+
+    /// Forget every mesh. Buffers items still draw stay alive until those are dropped.
+    pub fn clear(&mut self) {
+        self.buffers.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_check::gpu::headless_device;
+
+    #[test]
+    fn equal_meshes_share_their_buffers() {
+        let (device, _queue) = match headless_device() {
+            Some(device) => device,
+            None => return eprintln!("no adapter, skipping the mesh cache check"),
+        };
+        let object_layout = RenderItem::create_object_layout(&device);
+        let mut cache = MeshCache::new(false);
+
+        let mut first = cache.get_or_upload(&device, &object_layout, &Mesh::sphere(1.0, 16, 8), "first");
+        let second = cache.get_or_upload(&device, &object_layout, &Mesh::sphere(1.0, 16, 8), "second");
+        let brick = cache.get_or_upload(&device, &object_layout, &Mesh::brick(1.0, 1.0, 1.0, 0), "brick");
+        assert!(Rc::ptr_eq(&first.buffers, &second.buffers));
+        assert!(!Rc::ptr_eq(&first.buffers, &brick.buffers));
+
+        // Moving one leaves the other where it was.
+        first.transform.disp.x = 2.0;
+        assert_eq!(second.transform.disp.x, 0.0);
+
+        // The sphere's buffers outlive one of its items, the brick's go with it.
+        let sphere_buffers = Rc::downgrade(&first.buffers);
+        let brick_buffers = Rc::downgrade(&brick.buffers);
+        drop(first);
+        drop(brick);
+        cache.evict_unused();
+        assert!(sphere_buffers.upgrade().is_some());
+        assert!(brick_buffers.upgrade().is_none());
+
+        drop(second);
+        cache.evict_unused();
+        assert!(sphere_buffers.upgrade().is_none());
+    }
+}
\ No newline at end of file
@@ -1,10 +1,124 @@
 mod vertex;
 mod mesh;
+mod aabb;
+mod marching_cubes;
+mod decimate;
+mod binary;
+mod report;
+mod material;
 
-pub use vertex::{Vertex, AsVertexPrimitive};
-pub use mesh::Mesh;
+use cgmath::{Decomposed, One, Quaternion, Transform, Vector3, Zero};
+
+pub use vertex::{Vertex, AsVertexPrimitive, VERTEX_SIZE};
+pub use mesh::{Axis, DEFAULT_TRIANGLE_LIMIT, FinalizeOptions, GridUvMode, Mesh, PoleCap, UpAxis};
+pub use aabb::Aabb;
+pub use binary::MeshBinError;
+pub use report::{EdgeReport, MeshDiagnostics, MeshReport};
+pub use material::Material;
 pub(crate) use vertex::{get_middle, new_vertex};
 
 pub struct Model {
-    pub mesh: Mesh,
-}
\ No newline at end of file
+    pub meshes: Vec<Mesh>,
+    // Placement of every mesh in the world.
+    pub transform: Decomposed<Vector3<f32>, Quaternion<f32>>,
+    // Point in model space that `transform` scales and rotates around, the
+    // origin by default. See `world_transform`.
+    pub pivot: Vector3<f32>,
+    // Shades every mesh instead of their own material when set, see
+    // `set_material_override`.
+    pub material_override: Option<Material>,
+}
+
+impl Model {
+    pub fn new(meshes: Vec<Mesh>) -> Self {
+        Self {
+            meshes,
+            transform: Decomposed::one(),
+            pivot: Vector3::zero(),
+            material_override: None,
+        }
+    }
+
+    /// Draw every mesh with `material` instead of its own, or with their own
+    /// again for `None`. Only read when the model is added with
+    /// `Application::with_model`, which takes the model, so set or clear it before.
+    pub fn set_material_override(&mut self, material: Option<Material>) {
+        self.material_override = material;
+    }
+
+    /// Rotate and scale around `center_of_mass` instead of the origin, so a
+    /// turntable spin of an off-center mesh turns it in place.
+    pub fn with_center_of_mass_pivot(mut self) -> Self {
+        self.pivot = self.center_of_mass();
+        self
+    }
+
+    /// Area weighted center of mass of all the meshes together, in model space,
+    /// see `Mesh::center_of_mass`. The origin when they have no area.
+    pub fn center_of_mass(&self) -> Vector3<f32> {
+        let (weighted, area) = self.meshes
+            .iter()
+            .map(|mesh| mesh.area_weighted_centroid())
+            .fold((Vector3::zero(), 0.0), |(weighted, area), (w, a)| (weighted + w, area + a));
+        if area > 0.0 { weighted / area } else { Vector3::zero() }
+    }
+
+    /// `transform` applied around `pivot`: the pivot stays where it is, moved
+    /// only by the translation, and the meshes scale and rotate around it. The
+    /// same as `transform` while the pivot is the origin.
+    pub fn world_transform(&self) -> Decomposed<Vector3<f32>, Quaternion<f32>> {
+        Decomposed {
+            scale: self.transform.scale,
+            rot: self.transform.rot,
+            disp: self.transform.disp + self.pivot - self.transform.transform_vector(self.pivot),
+        }
+    }
+
+    /// World space bounds of all the meshes, empty when there are none. Each
+    /// mesh's box is transformed corner by corner, so rotations stay covered.
+    pub fn bounding_box(&self) -> Aabb {
+        let transform = self.world_transform();
+        let mut bounds = Aabb::empty();
+        for mesh in &self.meshes {
+            let local = mesh.bounding_box();
+            if local.is_empty() {
+                continue;
+            }
+            for corner in local.corners().iter() {
+                bounds.extend(transform.transform_vector(*corner) + transform.disp);
+            }
+        }
+        bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, InnerSpace, Rotation3};
+
+    use super::*;
+
+    #[test]
+    fn models_without_vertices_have_empty_bounds() {
+        assert!(Model::new(Vec::new()).bounding_box().is_empty());
+        let empty_mesh = Mesh { vertices: Vec::new(), indices: Vec::new() };
+        assert!(Model::new(vec![empty_mesh]).bounding_box().is_empty());
+    }
+
+    #[test]
+    fn rotated_model_bounds_cover_the_turned_meshes() {
+        // 2 wide along x, 1 deep along z, turned a quarter around y and moved along x.
+        let mut model = Model::new(vec![Mesh::brick(2.0, 1.0, 1.0, 0)]);
+        model.transform.rot = Quaternion::from_angle_y(Deg(90.0));
+        model.transform.disp = Vector3::new(3.0, 0.0, 0.0);
+        let bounds = model.bounding_box();
+        assert!((bounds.size() - Vector3::new(1.0, 1.0, 2.0)).magnitude() < 1e-5, "{:?}", bounds);
+        assert!((bounds.center() - Vector3::new(3.0, 0.0, 0.0)).magnitude() < 1e-5, "{:?}", bounds);
+
+        // Turned an eighth of the way, the corners stick out along both x and z.
+        model.transform.rot = Quaternion::from_angle_y(Deg(45.0));
+        let extent = 1.5 * std::f32::consts::FRAC_1_SQRT_2 * 2.0;
+        let bounds = model.bounding_box();
+        assert!((bounds.size() - Vector3::new(extent, 1.0, extent)).magnitude() < 1e-5, "{:?}", bounds);
+    }
+}
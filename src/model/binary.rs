@@ -0,0 +1,179 @@
+use super::{Mesh, Vertex};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Grid positions are snapped to before `Mesh::content_hash` hashes them, so
+/// results that only differ by float rounding noise hash the same. Well below any
+/// feature size the generators produce, in the meshes' units.
+pub const HASH_EPSILON: f32 = 1e-5;
+
+/// Version of the `Mesh::write_bin` format, bump it whenever the header or the
+/// `Vertex` layout changes.
+pub const BIN_VERSION: u32 = 1;
+
+const BIN_MAGIC: &[u8; 4] = b"MESH";
+// Magic, version, vertex size, vertex count, index count and content hash.
+const HEADER_SIZE: usize = 4 + 4 * 4 + 8;
+
+// 64 bit FNV-1a, spelled out since std's hashers may change between releases.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+#[derive(Debug)]
+pub enum MeshBinError {
+    Io(std::io::Error),
+    /// The file doesn't start with the mesh magic.
+    NotAMesh,
+    /// Written by another version of the format.
+    VersionMismatch { found: u32, expected: u32 },
+    /// Written with a different `Vertex` layout under the same version.
+    VertexSizeMismatch { found: u32, expected: u32 },
+    /// Shorter or longer than the header says.
+    Truncated,
+    /// The contents don't match the content hash stored with them.
+    Corrupted,
+}
+
+impl fmt::Display for MeshBinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshBinError::Io(e) => write!(f, "{}", e),
+            MeshBinError::NotAMesh => write!(f, "not a mesh file"),
+            MeshBinError::VersionMismatch { found, expected } => {
+                write!(f, "mesh file version {}, expected {}", found, expected)
+            }
+            MeshBinError::VertexSizeMismatch { found, expected } => {
+                write!(f, "mesh file vertices are {} bytes, expected {}", found, expected)
+            }
+            MeshBinError::Truncated => write!(f, "mesh file size doesn't match its header"),
+            MeshBinError::Corrupted => write!(f, "mesh file doesn't match its content hash"),
+        }
+    }
+}
+
+impl std::error::Error for MeshBinError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MeshBinError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for MeshBinError {
+    fn from(e: std::io::Error) -> Self {
+        MeshBinError::Io(e)
+    }
+}
+
+impl Mesh {
+    /// A hash of the vertex positions, quantized to `HASH_EPSILON`, and the indices,
+    /// the same on every platform and run. Other vertex attributes are not hashed.
+    pub fn content_hash(&self) -> u64 {
+        let mut hash = FNV_OFFSET_BASIS;
+        hash = fnv1a(hash, &(self.vertices.len() as u64).to_le_bytes());
+        hash = fnv1a(hash, &(self.indices.len() as u64).to_le_bytes());
+        for vertex in &self.vertices {
+            for &component in &[vertex.position.x, vertex.position.y, vertex.position.z] {
+                let quantized = (component / HASH_EPSILON).round() as i64;
+                hash = fnv1a(hash, &quantized.to_le_bytes());
+            }
+        }
+        for &index in &self.indices {
+            hash = fnv1a(hash, &index.to_le_bytes());
+        }
+        hash
+    }
+
+    /// Read the mesh cached at `path`, or build it with `generate` and cache it
+    /// there. A cache that `read_bin` rejects is regenerated. Failing to write the
+    /// cache only logs a warning, the generated mesh is returned either way.
+    pub fn load_or_generate(path: impl AsRef<Path>, generate: impl FnOnce() -> Mesh) -> Mesh {
+        let path = path.as_ref();
+        if let Ok(mesh) = Self::read_bin(path) {
+            return mesh;
+        }
+
+        let mesh = generate();
+        if let Err(e) = mesh.write_bin(path) {
+            log::warn!("Couldn't cache mesh to {}: {}", path.display(), e);
+        }
+        mesh
+    }
+
+    /// Write the vertices and indices losslessly to `path`.
+    ///
+    /// The header holds a magic, `BIN_VERSION`, the size of a `Vertex`, the vertex
+    /// and index counts as u32 and the `content_hash` as u64, all little endian. The
+    /// raw `Vertex` and index arrays follow in native byte order.
+    pub fn write_bin(&self, path: impl AsRef<Path>) -> Result<(), MeshBinError> {
+        let path = path.as_ref();
+        let mut bytes = Vec::with_capacity(
+            HEADER_SIZE + self.vertices.len() * std::mem::size_of::<Vertex>() + self.indices.len() * 4
+        );
+        bytes.extend_from_slice(BIN_MAGIC);
+        bytes.extend_from_slice(&BIN_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(std::mem::size_of::<Vertex>() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.vertices.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.indices.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.content_hash().to_le_bytes());
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.vertices));
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.indices));
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Read a mesh written by `write_bin`, checking its version, vertex layout, size
+    /// and content hash.
+    pub fn read_bin(path: impl AsRef<Path>) -> Result<Self, MeshBinError> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < HEADER_SIZE || &bytes[0..4] != BIN_MAGIC {
+            return Err(MeshBinError::NotAMesh);
+        }
+        let u32_at = |offset: usize| u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+
+        let version = u32_at(4);
+        if version != BIN_VERSION {
+            return Err(MeshBinError::VersionMismatch { found: version, expected: BIN_VERSION });
+        }
+        let vertex_size = u32_at(8);
+        if vertex_size as usize != std::mem::size_of::<Vertex>() {
+            return Err(MeshBinError::VertexSizeMismatch {
+                found: vertex_size,
+                expected: std::mem::size_of::<Vertex>() as u32,
+            });
+        }
+        let (vertex_count, index_count) = (u32_at(12) as usize, u32_at(16) as usize);
+        let hash = (u32_at(20) as u64) | ((u32_at(24) as u64) << 32);
+
+        let vertex_bytes = vertex_count * std::mem::size_of::<Vertex>();
+        let index_bytes = index_count * std::mem::size_of::<u32>();
+        if bytes.len() != HEADER_SIZE + vertex_bytes + index_bytes {
+            return Err(MeshBinError::Truncated);
+        }
+        // The file contents aren't aligned for `Vertex`, so copy instead of casting in place.
+        let body = &bytes[HEADER_SIZE..];
+        let mut vertices = vec![<Vertex as bytemuck::Zeroable>::zeroed(); vertex_count];
+        bytemuck::cast_slice_mut(&mut vertices).copy_from_slice(&body[..vertex_bytes]);
+        let mut indices = vec![0u32; index_count];
+        bytemuck::cast_slice_mut(&mut indices).copy_from_slice(&body[vertex_bytes..]);
+
+        let mesh = Self {
+            vertices,
+            indices,
+        };
+        if mesh.content_hash() != hash {
+            return Err(MeshBinError::Corrupted);
+        }
+        Ok(mesh)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,334 @@
+use super::{Mesh, Vertex};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use cgmath::{InnerSpace, Vector3};
+
+// Boundary edges get a plane perpendicular to their triangle, weighted this much
+// more than the surface planes, so collapses don't pull them inwards.
+const BOUNDARY_WEIGHT: f64 = 1000.0;
+
+/// Symmetric 4x4 matrix summing squared distances to planes, the upper triangle
+/// stored row by row.
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_plane(normal: Vector3<f64>, d: f64, weight: f64) -> Self {
+        let [a, b, c] = [normal.x, normal.y, normal.z];
+        Quadric([
+            a * a, a * b, a * c, a * d,
+                   b * b, b * c, b * d,
+                          c * c, c * d,
+                                 d * d,
+        ].map(|q| q * weight))
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (q, o) in self.0.iter_mut().zip(other.0.iter()) {
+            *q += o;
+        }
+    }
+
+    fn error(&self, p: Vector3<f64>) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+            + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+            + q[7] * z * z + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+/// Moving position `from` onto `to`, ordered so the heap pops the cheapest first.
+struct Collapse {
+    cost: f64,
+    from: usize,
+    to: usize,
+    // Versions of the two positions when the cost was computed, stale if either moved on.
+    from_version: u32,
+    to_version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+fn to_f64(v: Vector3<f32>) -> Vector3<f64> {
+    Vector3::new(v.x as f64, v.y as f64, v.z as f64)
+}
+
+impl Mesh {
+    /// Simplify the mesh to about `target_ratio` of its triangles by quadric error
+    /// metric edge collapses, always taking the collapse that moves the surface least.
+    ///
+    /// Vertices with equal attributes are welded first. Collapses move one end of an
+    /// edge onto the other, so the kept vertices keep their attributes. Positions on
+    /// a boundary or on a seam, where vertices with different attributes meet, are
+    /// never moved, which keeps holes, UV seams and hard edges in place but can stop
+    /// short of the target. So can collapses that would fold the surface over.
+    pub fn decimate(&self, target_ratio: f32) -> Mesh {
+        let attribute_key = |v: &Vertex| {
+            let mut key = [0u32; 11];
+            let values = [
+                v.position.x, v.position.y, v.position.z,
+                v.normal.x, v.normal.y, v.normal.z,
+                v.tangent.x, v.tangent.y, v.tangent.z,
+                v.tex_coord.x, v.tex_coord.y,
+            ];
+            for (k, value) in key.iter_mut().zip(values.iter()) {
+                *k = value.to_bits();
+            }
+            key
+        };
+
+        // Weld identical vertices, then give each distinct position an id.
+        let mut welded: HashMap<[u32; 11], u32> = HashMap::new();
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let remap: Vec<u32> = self.vertices.iter().map(|v| {
+            *welded.entry(attribute_key(v)).or_insert_with(|| {
+                vertices.push(*v);
+                vertices.len() as u32 - 1
+            })
+        }).collect();
+
+        let mut position_ids: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut positions: Vec<Vector3<f64>> = Vec::new();
+        let mut vertices_at: Vec<Vec<u32>> = Vec::new();
+        let vertex_position: Vec<usize> = vertices.iter().enumerate().map(|(i, v)| {
+            let key = [v.position.x.to_bits(), v.position.y.to_bits(), v.position.z.to_bits()];
+            let id = *position_ids.entry(key).or_insert_with(|| {
+                positions.push(to_f64(v.position));
+                vertices_at.push(Vec::new());
+                positions.len() - 1
+            });
+            vertices_at[id].push(i as u32);
+            id
+        }).collect();
+
+        let mut triangles: Vec<[u32; 3]> = self.indices
+            .chunks_exact(3)
+            .map(|t| [remap[t[0] as usize], remap[t[1] as usize], remap[t[2] as usize]])
+            .filter(|t| {
+                let [a, b, c] = t.map(|v| vertex_position[v as usize]);
+                a != b && b != c && c != a
+            })
+            .collect();
+        let position_of = |triangles: &[[u32; 3]], t: usize| triangles[t].map(|v| vertex_position[v as usize]);
+
+        let mut alive = vec![true; triangles.len()];
+        let mut position_triangles: Vec<HashSet<usize>> = vec![HashSet::new(); positions.len()];
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        let mut edge_triangles: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for t in 0..triangles.len() {
+            let [a, b, c] = position_of(&triangles, t);
+            let normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+            // Weighting by area keeps slivers from dominating.
+            let area = normal.magnitude();
+            if area > 0.0 {
+                let normal = normal / area;
+                let plane = Quadric::from_plane(normal, -normal.dot(positions[a]), area);
+                for &p in &[a, b, c] {
+                    quadrics[p].add(&plane);
+                }
+            }
+            for &p in &[a, b, c] {
+                position_triangles[p].insert(t);
+            }
+            for &(u, v) in &[(a, b), (b, c), (c, a)] {
+                edge_triangles.entry((u.min(v), u.max(v))).or_default().push(t);
+            }
+        }
+
+        let mut locked: Vec<bool> = vertices_at.iter().map(|at| at.len() > 1).collect();
+        for (&(u, v), edge) in &edge_triangles {
+            if edge.len() != 1 {
+                continue;
+            }
+            locked[u] = true;
+            locked[v] = true;
+            let [a, b, c] = position_of(&triangles, edge[0]);
+            let face_normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+            let along = positions[v] - positions[u];
+            let normal = along.cross(face_normal);
+            if normal.magnitude2() > 0.0 {
+                let normal = normal.normalize();
+                let plane = Quadric::from_plane(normal, -normal.dot(positions[u]), BOUNDARY_WEIGHT * along.magnitude2());
+                quadrics[u].add(&plane);
+                quadrics[v].add(&plane);
+            }
+        }
+
+        let mut versions = vec![0u32; positions.len()];
+        let mut heap = BinaryHeap::new();
+        let push = |heap: &mut BinaryHeap<Collapse>, quadrics: &[Quadric], versions: &[u32], from: usize, to: usize| {
+            let mut quadric = quadrics[from];
+            quadric.add(&quadrics[to]);
+            heap.push(Collapse {
+                cost: quadric.error(positions[to]),
+                from,
+                to,
+                from_version: versions[from],
+                to_version: versions[to],
+            });
+        };
+        let neighbours = |position_triangles: &[HashSet<usize>], triangles: &[[u32; 3]], p: usize| {
+            let mut neighbours = HashSet::new();
+            for &t in &position_triangles[p] {
+                neighbours.extend(position_of(triangles, t).iter().copied().filter(|&q| q != p));
+            }
+            neighbours
+        };
+
+        for &(u, v) in edge_triangles.keys() {
+            if !locked[u] {
+                push(&mut heap, &quadrics, &versions, u, v);
+            }
+            if !locked[v] {
+                push(&mut heap, &quadrics, &versions, v, u);
+            }
+        }
+
+        let target = ((triangles.len() as f32 * target_ratio.max(0.0).min(1.0)).ceil() as usize).max(1);
+        let mut alive_count = triangles.len();
+        while alive_count > target {
+            let collapse = match heap.pop() {
+                Some(collapse) => collapse,
+                None => break,
+            };
+            let (from, to) = (collapse.from, collapse.to);
+            if collapse.from_version != versions[from] || collapse.to_version != versions[to] || locked[from] {
+                continue;
+            }
+
+            let shared: Vec<usize> = position_triangles[from].intersection(&position_triangles[to]).copied().collect();
+            if shared.is_empty() {
+                continue;
+            }
+            // Neighbours common to both ends other than the triangles' third corners
+            // would pinch the surface into a non-manifold edge.
+            let from_neighbours = neighbours(&position_triangles, &triangles, from);
+            let to_neighbours = neighbours(&position_triangles, &triangles, to);
+            if from_neighbours.intersection(&to_neighbours).count() > shared.len() {
+                continue;
+            }
+            // Reject collapses turning any remaining triangle around `from` over.
+            let flips = position_triangles[from].iter().filter(|&t| !shared.contains(t)).any(|&t| {
+                let corners = position_of(&triangles, t);
+                let before = (positions[corners[1]] - positions[corners[0]]).cross(positions[corners[2]] - positions[corners[0]]);
+                let moved = corners.map(|p| if p == from { positions[to] } else { positions[p] });
+                let after = (moved[1] - moved[0]).cross(moved[2] - moved[0]);
+                after.dot(before) <= 0.0
+            });
+            if flips {
+                continue;
+            }
+
+            // `from` isn't on a seam, so it has a single vertex. Take the vertex `to`
+            // has in a shared triangle as its replacement.
+            let from_vertex = vertices_at[from][0];
+            let to_vertex = *triangles[shared[0]].iter().find(|&&v| vertex_position[v as usize] == to).unwrap();
+
+            for &t in &shared {
+                alive[t] = false;
+                alive_count -= 1;
+                for p in position_of(&triangles, t).iter() {
+                    position_triangles[*p].remove(&t);
+                }
+            }
+            let moved: Vec<usize> = position_triangles[from].drain().collect();
+            for t in moved {
+                for v in triangles[t].iter_mut() {
+                    if *v == from_vertex {
+                        *v = to_vertex;
+                    }
+                }
+                position_triangles[to].insert(t);
+            }
+
+            let from_quadric = quadrics[from];
+            quadrics[to].add(&from_quadric);
+            locked[from] = true;
+            versions[from] += 1;
+            versions[to] += 1;
+            for n in neighbours(&position_triangles, &triangles, to) {
+                if !locked[n] {
+                    push(&mut heap, &quadrics, &versions, n, to);
+                }
+                if !locked[to] {
+                    push(&mut heap, &quadrics, &versions, to, n);
+                }
+            }
+        }
+
+        // Keep only the vertices the remaining triangles use.
+        let mut compact: HashMap<u32, u32> = HashMap::new();
+        let mut mesh = Mesh {
+            vertices: Vec::new(),
+            indices: Vec::with_capacity(alive_count * 3),
+        };
+        for (triangle, _) in triangles.iter().zip(&alive).filter(|(_, alive)| **alive) {
+            for &v in triangle {
+                let index = *compact.entry(v).or_insert_with(|| {
+                    mesh.vertices.push(vertices[v as usize]);
+                    mesh.vertices.len() as u32 - 1
+                });
+                mesh.indices.push(index);
+            }
+        }
+        mesh
+    }
+
+    /// `levels` successively simplified meshes, each `ratio` of the triangles of the
+    /// one before, starting from a decimated copy of this mesh.
+    pub fn generate_lod_chain(&self, levels: u32, ratio: f32) -> Vec<Mesh> {
+        let mut chain: Vec<Mesh> = Vec::with_capacity(levels as usize);
+        for _ in 0..levels {
+            let next = chain.last().unwrap_or(self).decimate(ratio);
+            chain.push(next);
+        }
+        chain
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lod_chain_halves_the_triangles() {
+        let mesh = Mesh::geo_sphere(1.0, 4);
+        let bounds = mesh.bounding_box();
+        let chain = mesh.generate_lod_chain(3, 0.5);
+        assert_eq!(chain.len(), 3);
+
+        let mut previous = mesh.indices.len() / 3;
+        for level in &chain {
+            let triangles = level.indices.len() / 3;
+            let ratio = triangles as f32 / previous as f32;
+            assert!((0.4..=0.6).contains(&ratio), "{} of {} triangles kept", triangles, previous);
+            previous = triangles;
+
+            // Collapses only move vertices onto others, the silhouette barely shrinks.
+            let level_bounds = level.bounding_box();
+            assert!((level_bounds.size() - bounds.size()).magnitude() < 0.1 * bounds.size().magnitude());
+            assert!((level_bounds.center() - bounds.center()).magnitude() < 0.05);
+        }
+    }
+}
\ No newline at end of file
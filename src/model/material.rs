@@ -0,0 +1,11 @@
+/// A flat color a model is shaded with in place of its meshes' own texture, see
+/// `Model::set_material_override`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    // sRGB color and alpha, as a texel of the diffuse texture holds them.
+    pub base_color: [u8; 4],
+}
+
+impl Material {
+    pub const WHITE: Material = Material { base_color: [255, 255, 255, 255] };
+}
\ No newline at end of file
@@ -1,7 +1,236 @@
 use super::{new_vertex, get_middle};
-use super::Vertex;
+use super::{Aabb, Vertex};
+use super::marching_cubes::{CORNERS, EDGES, EDGE_TABLE, TRI_TABLE};
+use std::collections::{HashMap, HashSet};
 use std::f32;
-use cgmath::InnerSpace;
+use std::sync::atomic::{AtomicU64, Ordering};
+use anyhow::{bail, Context, Result};
+use cgmath::{ElementWise, InnerSpace, Matrix, Matrix3, Matrix4, SquareMatrix, Vector2, Vector3, Vector4, VectorSpace, Zero};
+use crate::{camera::Camera, noise};
+
+/// How `Mesh::grid` lays texture coordinates over the sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridUvMode {
+    /// One 0..1 span across the whole sheet.
+    Stretch,
+    /// 0..1 across each cell, so a repeating sampler tiles the texture once per quad.
+    PerCell,
+}
+
+impl Default for GridUvMode {
+    fn default() -> Self {
+        GridUvMode::Stretch
+    }
+}
+
+/// How `Mesh::sphere_cylindrical_uv` builds the triangles meeting at the poles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoleCap {
+    /// A single vertex per pole, as in `Mesh::sphere`. All the pole triangles share
+    /// its one u, so the texture twists into a fan around the pole.
+    Shared,
+    /// A pole vertex per slice with u in the middle of its slice, so each pole
+    /// triangle maps the texture straight up from its ring edge. Costs `slice - 1`
+    /// extra vertices per pole.
+    PerSlice,
+}
+
+impl Default for PoleCap {
+    fn default() -> Self {
+        PoleCap::PerSlice
+    }
+}
+
+/// One of the world axes, e.g. to project texture coordinates along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+    pub fn direction(self) -> Vector3<f32> {
+        match self {
+            Axis::X => Vector3::unit_x(),
+            Axis::Y => Vector3::unit_y(),
+            Axis::Z => Vector3::unit_z(),
+        }
+    }
+}
+
+/// Which axis points up in a mesh's source data. Everything in this crate is Y-up,
+/// the loaders turn other conventions into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpAxis {
+    Y,
+    /// Right handed Z-up, as in Blender scenes and most CAD tools.
+    Z,
+}
+
+impl Default for UpAxis {
+    fn default() -> Self {
+        UpAxis::Y
+    }
+}
+
+impl UpAxis {
+    /// The rotation taking this convention to Y-up. Z-up turns -90 degrees about
+    /// x, so +z becomes +y and +y becomes -z.
+    pub fn to_y_up(self) -> Matrix3<f32> {
+        match self {
+            UpAxis::Y => Matrix3::new(
+                1.0, 0.0, 0.0,
+                0.0, 1.0, 0.0,
+                0.0, 0.0, 1.0,
+            ),
+            UpAxis::Z => Matrix3::new(
+                1.0, 0.0, 0.0,
+                0.0, 0.0, -1.0,
+                0.0, 1.0, 0.0,
+            ),
+        }
+    }
+}
+
+/// The steps `Mesh::finalize` runs, all of them by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FinalizeOptions {
+    pub weld: bool,
+    pub normals: bool,
+    pub tangents: bool,
+    pub optimize_vertex_cache: bool,
+}
+
+impl Default for FinalizeOptions {
+    fn default() -> Self {
+        Self {
+            weld: true,
+            normals: true,
+            tangents: true,
+            optimize_vertex_cache: true,
+        }
+    }
+}
+
+// Vertex cache size assumed by `optimize_vertex_cache`, and the weights of
+// Forsyth's scoring.
+const VERTEX_CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// Triangles the primitive generators may produce unless changed with
+/// `Mesh::set_triangle_limit`. Enough for `geo_sphere` with 10 subdivisions, at
+/// over a GiB of vertices and indices.
+pub const DEFAULT_TRIANGLE_LIMIT: u64 = 1 << 25;
+
+static TRIANGLE_LIMIT: AtomicU64 = AtomicU64::new(DEFAULT_TRIANGLE_LIMIT);
+
+/// The most of `subdivision` 4-way `Mesh::subdivide` passes that keep `base`
+/// triangles within the triangle limit, warning when that's fewer.
+fn clamp_subdivision(name: &str, base: u64, subdivision: u32) -> u32 {
+    let limit = Mesh::triangle_limit();
+    let mut clamped = 0;
+    let mut triangles = base;
+    while clamped < subdivision && triangles.saturating_mul(4) <= limit {
+        clamped += 1;
+        triangles *= 4;
+    }
+    if clamped < subdivision {
+        log::warn!(
+            "{}: subdivision {} would make {:.0} triangles, over the limit of {}, using {}",
+            name,
+            subdivision,
+            base as f64 * 4f64.powi(subdivision as i32),
+            limit,
+            clamped,
+        );
+    }
+    clamped
+}
+
+/// `resolution` scaled down evenly, to no less than `minimum` in either
+/// direction, until the `triangles` it makes fit the triangle limit, warning
+/// when it had to.
+fn clamp_resolution(name: &str, resolution: (u32, u32), minimum: u32, triangles: impl Fn(u32, u32) -> u64) -> (u32, u32) {
+    let limit = Mesh::triangle_limit();
+    let projected = triangles(resolution.0, resolution.1);
+    if projected <= limit {
+        return resolution;
+    }
+
+    let scale = (limit as f64 / projected as f64).sqrt();
+    let clamped = (
+        ((resolution.0 as f64 * scale) as u32).max(minimum),
+        ((resolution.1 as f64 * scale) as u32).max(minimum),
+    );
+    log::warn!(
+        "{}: {} by {} would make {} triangles, over the limit of {}, using {} by {}",
+        name,
+        resolution.0,
+        resolution.1,
+        projected,
+        limit,
+        clamped.0,
+        clamped.1,
+    );
+    clamped
+}
+
+/// Triangles covering the simple counter-clockwise polygon `points`, as index
+/// triples, by clipping ears. O(n^3), fine for hand-made profiles.
+fn ear_clip(points: &[Vector2<f32>]) -> Vec<[u32; 3]> {
+    let cross = |o: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>| (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x);
+
+    let mut remaining: Vec<u32> = (0..points.len() as u32).collect();
+    let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let corner = |i: usize| (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = corner(i);
+            let (pa, pb, pc) = (points[a as usize], points[b as usize], points[c as usize]);
+            // A convex corner with no other vertex inside or on its triangle.
+            cross(pa, pb, pc) > 0.0 && remaining.iter().all(|&j| {
+                let p = points[j as usize];
+                j == a || j == b || j == c
+                    || cross(pa, pb, p) < 0.0 || cross(pb, pc, p) < 0.0 || cross(pc, pa, p) < 0.0
+            })
+        });
+        // Only a polygon that isn't simple runs out of ears, cut a corner anyway
+        // so it still gets covered, if wrongly.
+        let i = ear.unwrap_or(0);
+        let (a, b, c) = corner(i);
+        triangles.push([a, b, c]);
+        remaining.remove(i);
+    }
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+/// `position` projected along `axis` into `bounds`' 0..1 texture space, see
+/// `Mesh::planar_uv_project`.
+fn planar_uv(axis: Axis, position: Vector3<f32>, bounds: &Aabb) -> Vector2<f32> {
+    // Right and up on the plane, seen from the positive side of the axis.
+    let (right, up) = match axis {
+        Axis::X => (-Vector3::unit_z(), Vector3::unit_y()),
+        Axis::Y => (Vector3::unit_x(), -Vector3::unit_z()),
+        Axis::Z => (Vector3::unit_x(), Vector3::unit_y()),
+    };
+    let along = |direction: Vector3<f32>| {
+        let (a, b) = (bounds.min.dot(direction), bounds.max.dot(direction));
+        let (low, high) = (a.min(b), a.max(b));
+        if high > low { (position.dot(direction) - low) / (high - low) } else { 0.0 }
+    };
+    // Texture v runs down the image.
+    Vector2::new(along(right), 1.0 - along(up))
+}
 
 pub struct Mesh {
     pub vertices: Vec<Vertex>,
@@ -9,7 +238,21 @@ pub struct Mesh {
 }
 
 impl Mesh {
+    /// Cap the triangles `brick`, `grid`, `sphere`, `sphere_cylindrical_uv` and
+    /// `geo_sphere` produce at `limit`, `DEFAULT_TRIANGLE_LIMIT` until changed.
+    /// Asking them for more lowers their detail parameters to fit and logs a
+    /// warning, rather than running out of memory on a typo like
+    /// `geo_sphere(1.0, 12)`. Raise it to generate larger meshes on purpose.
+    pub fn set_triangle_limit(limit: u64) {
+        TRIANGLE_LIMIT.store(limit, Ordering::Relaxed);
+    }
+
+    pub fn triangle_limit() -> u64 {
+        TRIANGLE_LIMIT.load(Ordering::Relaxed)
+    }
+
     pub fn brick(width: f32, height: f32, depth: f32, subdivision: u32) -> Self {
+        let subdivision = clamp_subdivision("brick", 12, subdivision);
         let w2 = 0.5 * width;
         let h2 = 0.5 * height;
         let d2 = 0.5 * depth;
@@ -73,7 +316,51 @@ impl Mesh {
         mesh
     }
 
+    /// A flat `width` by `depth` sheet in the xz plane facing +y, centered on the
+    /// origin, with `m` rows of vertices along z and `n` columns along x.
+    pub fn grid(width: f32, depth: f32, m: u32, n: u32, uv_mode: GridUvMode) -> Self {
+        let (m, n) = clamp_resolution("grid", (m.max(2), n.max(2)), 2, |m, n| {
+            2 * (m as u64 - 1) * (n as u64 - 1)
+        });
+        let half_width = 0.5 * width;
+        let half_depth = 0.5 * depth;
+        let dx = width / (n - 1) as f32;
+        let dz = depth / (m - 1) as f32;
+        // Per cell UVs step by one between neighbouring vertices.
+        let (du, dv) = match uv_mode {
+            GridUvMode::Stretch => (1.0 / (n - 1) as f32, 1.0 / (m - 1) as f32),
+            GridUvMode::PerCell => (1.0, 1.0),
+        };
+
+        let mut vertices = Vec::with_capacity((m * n) as usize);
+        for i in 0..m {
+            let z = half_depth - i as f32 * dz;
+            for j in 0..n {
+                let x = -half_width + j as f32 * dx;
+                vertices.push(new_vertex!(x, 0.0, z, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, j as f32 * du, i as f32 * dv));
+            }
+        }
+
+        let mut indices = Vec::with_capacity(((m - 1) * (n - 1) * 6) as usize);
+        for i in 0..m - 1 {
+            for j in 0..n - 1 {
+                indices.extend_from_slice(&[
+                    i * n + j, i * n + j + 1, (i + 1) * n + j,
+                    (i + 1) * n + j, i * n + j + 1, (i + 1) * n + j + 1,
+                ]);
+            }
+        }
+
+        Self {
+            vertices,
+            indices,
+        }
+    }
+
     pub fn sphere(radius: f32, slice: u32, stack: u32) -> Self {
+        let (slice, stack) = clamp_resolution("sphere", (slice, stack), 3, |slice, stack| {
+            2 * slice as u64 * (stack as u64).saturating_sub(1)
+        });
         let vertex_count = slice * (stack - 1) + 2;
         let mut mesh = Self {
             vertices: Vec::with_capacity(vertex_count as usize),
@@ -172,6 +459,102 @@ impl Mesh {
         mesh
     }
 
+    /// Like `sphere`, but unwrapped with a cylindrical equal-area projection: u
+    /// still goes around y, while v follows the height, `(1 - y / radius) / 2`,
+    /// rather than the angle from the north pole.
+    ///
+    /// `sphere`'s spherical unwrap gives every stack the same height in the texture,
+    /// so equirectangular images like world maps and environments fit it exactly,
+    /// but their texels crowd together toward the poles. Here every texel covers
+    /// the same area of the surface, which suits tiling, noise and other textures
+    /// without a projection of their own, at the price of squashing them vertically
+    /// near the poles. Both duplicate the seam vertices at u = 1, so the last slice
+    /// doesn't interpolate u backward across the whole texture.
+    pub fn sphere_cylindrical_uv(radius: f32, slice: u32, stack: u32, pole_cap: PoleCap) -> Self {
+        let (slice, stack) = clamp_resolution("sphere_cylindrical_uv", (slice.max(3), stack.max(2)), 3, |slice, stack| {
+            2 * slice as u64 * (stack as u64 - 1)
+        });
+        let ring_vertices = slice + 1;
+        let pole_vertices = match pole_cap {
+            PoleCap::Shared => 1,
+            PoleCap::PerSlice => slice,
+        };
+
+        let mut mesh = Self {
+            vertices: Vec::with_capacity((ring_vertices * (stack - 1) + 2 * pole_vertices) as usize),
+            indices: Vec::with_capacity((slice * (stack - 1) * 6) as usize),
+        };
+
+        let phi_step = f32::consts::PI / stack as f32;
+        let theta_step = 2.0 * f32::consts::PI / slice as f32;
+
+        let push_poles = |vertices: &mut Vec<Vertex>, y: f32| {
+            let v = 0.5 * (1.0 - y);
+            match pole_cap {
+                PoleCap::Shared => vertices.push(new_vertex!(0.0, y * radius, 0.0, 0.0, y, 0.0, 1.0, 0.0, 0.0, 0.0, v)),
+                PoleCap::PerSlice => {
+                    for j in 0..slice {
+                        let theta = (j as f32 + 0.5) * theta_step;
+                        vertices.push(new_vertex!(
+                            0.0, y * radius, 0.0,
+                            0.0, y, 0.0,
+                            -theta.sin(), 0.0, theta.cos(),
+                            (j as f32 + 0.5) / slice as f32, v
+                        ));
+                    }
+                }
+            }
+        };
+        // The first pole vertex above or below slice `j`.
+        let pole_index = |first: u32, j: u32| match pole_cap {
+            PoleCap::Shared => first,
+            PoleCap::PerSlice => first + j,
+        };
+
+        push_poles(&mut mesh.vertices, 1.0);
+
+        for i in 1..stack {
+            let phi = i as f32 * phi_step;
+            for j in 0..ring_vertices {
+                let theta = j as f32 * theta_step;
+                let normal = Vector3::new(phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin());
+
+                mesh.vertices.push(Vertex {
+                    position: normal * radius,
+                    normal,
+                    tangent: Vector3::new(-theta.sin(), 0.0, theta.cos()),
+                    tex_coord: Vector2::new(j as f32 / slice as f32, 0.5 * (1.0 - normal.y)),
+                });
+            }
+        }
+
+        let south_pole = mesh.vertices.len() as u32;
+        push_poles(&mut mesh.vertices, -1.0);
+
+        // Same windings as `sphere`.
+        let first_ring = pole_vertices;
+        for j in 0..slice {
+            mesh.indices.extend_from_slice(&[pole_index(0, j), first_ring + j + 1, first_ring + j]);
+        }
+
+        for i in 0..(stack - 2) {
+            let ring = first_ring + i * ring_vertices;
+            for j in 0..slice {
+                mesh.indices.extend_from_slice(&[
+                    ring + j, ring + j + 1, ring + ring_vertices + j,
+                    ring + ring_vertices + j, ring + j + 1, ring + ring_vertices + j + 1,
+                ]);
+            }
+        }
+
+        let last_ring = south_pole - ring_vertices;
+        for j in 0..slice {
+            mesh.indices.extend_from_slice(&[pole_index(south_pole, j), last_ring + j, last_ring + j + 1]);
+        }
+
+        mesh
+    }
+
     pub fn geo_sphere(radius: f32, subdivision: u32) -> Self {
         let X = 0.525731f32;
         let Z = 0.850651f32;
@@ -197,6 +580,7 @@ impl Mesh {
             indices,
         };
 
+        let subdivision = clamp_subdivision("geo_sphere", mesh.indices.len() as u64 / 3, subdivision);
         for _ in 0..subdivision {
             mesh.subdivide();
         }
@@ -224,6 +608,1384 @@ impl Mesh {
         mesh
     }
 
+    /// An arrow along +y from the origin, a cylinder shaft capped at its base with a
+    /// cone head of `head_length` on top. Caps get their own vertices so they stay
+    /// flat shaded while the sides are smooth.
+    pub fn arrow(shaft_radius: f32, shaft_length: f32, head_radius: f32, head_length: f32, slices: u32) -> Self {
+        let slices = slices.max(3);
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        let push_ring = |vertices: &mut Vec<Vertex>, radius: f32, y: f32| {
+            let base = vertices.len() as u32;
+            for i in 0..slices {
+                let theta = f32::consts::TAU * i as f32 / slices as f32;
+                vertices.push(new_vertex!(radius * theta.cos(), y, radius * theta.sin()));
+            }
+            base
+        };
+
+        // Base cap and the head's underside, both facing -y.
+        for &(radius, y) in &[(shaft_radius, 0.0), (head_radius, shaft_length)] {
+            let center = vertices.len() as u32;
+            vertices.push(new_vertex!(0.0, y, 0.0));
+            let ring = push_ring(&mut vertices, radius, y);
+            for i in 0..slices {
+                indices.extend_from_slice(&[center, ring + i, ring + (i + 1) % slices]);
+            }
+        }
+
+        let bottom = push_ring(&mut vertices, shaft_radius, 0.0);
+        let top = push_ring(&mut vertices, shaft_radius, shaft_length);
+        for i in 0..slices {
+            let j = (i + 1) % slices;
+            indices.extend_from_slice(&[
+                bottom + i, top + i, bottom + j,
+                bottom + j, top + i, top + j,
+            ]);
+        }
+
+        let head = push_ring(&mut vertices, head_radius, shaft_length);
+        let tip = vertices.len() as u32;
+        vertices.push(new_vertex!(0.0, shaft_length + head_length, 0.0));
+        for i in 0..slices {
+            indices.extend_from_slice(&[head + i, tip, head + (i + 1) % slices]);
+        }
+
+        let mut mesh = Self {
+            vertices,
+            indices,
+        };
+        mesh.recompute_normals();
+        mesh
+    }
+
+    /// A surface of revolution, `profile` turned around the y axis in `segments`
+    /// steps, each point `(x, y)` becoming a ring of radius x at height y.
+    ///
+    /// The surface faces the right of the profile's direction in the xy plane, so
+    /// one walked upward away from the axis, like a vase from the middle of its
+    /// base to its rim, faces outward. Normals are smooth along the profile too,
+    /// averaged over the edges meeting at each point. u goes around y as on
+    /// `sphere`, with the seam column duplicated at u = 1, and v along the profile
+    /// by length, from 1 at the first point to 0 at the last. Points on the axis
+    /// are poles: they get a vertex per segment, centered in its u range, and the
+    /// triangles that would collapse onto them are left out. Fewer than 2 points
+    /// give an empty mesh.
+    pub fn lathe(profile: &[Vector2<f32>], segments: u32) -> Self {
+        let segments = segments.max(3);
+        let ring_vertices = segments + 1;
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        if profile.len() < 2 {
+            return Self { vertices, indices };
+        }
+
+        let length: f32 = profile.windows(2).map(|pair| (pair[1] - pair[0]).magnitude()).sum();
+        let theta_step = f32::consts::TAU / segments as f32;
+        let direction = |a: Vector2<f32>, b: Vector2<f32>| {
+            let d = b - a;
+            if d.magnitude2() > 0.0 { d.normalize() } else { Vector2::zero() }
+        };
+
+        // Where each ring starts, and whether it's a pole.
+        let mut rings = Vec::with_capacity(profile.len());
+        let mut walked = 0.0;
+        for (k, &point) in profile.iter().enumerate() {
+            if k > 0 {
+                walked += (point - profile[k - 1]).magnitude();
+            }
+            let v = if length > 0.0 { 1.0 - walked / length } else { 0.0 };
+
+            let before = if k > 0 { direction(profile[k - 1], point) } else { Vector2::zero() };
+            let after = if k + 1 < profile.len() { direction(point, profile[k + 1]) } else { Vector2::zero() };
+            let along = before + after;
+            // The right of the profile's direction, outward when walking up.
+            let side = if along.magnitude2() > 0.0 { along.normalize() } else { Vector2::unit_y() };
+            let (radial, up) = (side.y, -side.x);
+
+            let pole = point.x == 0.0;
+            rings.push((vertices.len() as u32, pole));
+            let (count, offset) = if pole { (segments, 0.5) } else { (ring_vertices, 0.0) };
+            for j in 0..count {
+                let theta = (j as f32 + offset) * theta_step;
+                let (sin, cos) = theta.sin_cos();
+                vertices.push(Vertex {
+                    position: Vector3::new(point.x * cos, point.y, point.x * sin),
+                    normal: Vector3::new(radial * cos, up, radial * sin).normalize(),
+                    tangent: Vector3::new(-sin, 0.0, cos),
+                    tex_coord: Vector2::new((j as f32 + offset) / segments as f32, v),
+                });
+            }
+        }
+
+        for pair in rings.windows(2) {
+            let ((r0, pole0), (r1, pole1)) = (pair[0], pair[1]);
+            for j in 0..segments {
+                // A pole's vertex `j` sits in the middle of segment `j`.
+                let (b0, b1) = (r0 + j, if pole0 { r0 + j } else { r0 + j + 1 });
+                let (t0, t1) = (r1 + j, if pole1 { r1 + j } else { r1 + j + 1 });
+                if !pole1 {
+                    indices.extend_from_slice(&[t0, t1, b0]);
+                }
+                if !pole0 {
+                    indices.extend_from_slice(&[b0, t1, b1]);
+                }
+            }
+        }
+
+        Self {
+            vertices,
+            indices,
+        }
+    }
+
+    /// A prism of `profile`, a polygon in the xy plane, extruded `depth` along z
+    /// and centered on z = 0, with a cap facing +z, one facing -z and a flat wall
+    /// on every edge.
+    ///
+    /// The profile must be a simple polygon, its edges not crossing each other,
+    /// concave ones like a star are fine. It's closed from its last point back to
+    /// the first, and either winding works, it's turned counter-clockwise so both
+    /// caps face outward. The caps are mapped to the profile's bounds as seen from
+    /// their side, the walls get u around the perimeter and v from the front cap
+    /// to the back. Fewer than 3 points give an empty mesh.
+    pub fn extrude(profile: &[Vector2<f32>], depth: f32) -> Self {
+        let mut points = profile.to_vec();
+        if points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        if points.len() < 3 {
+            return Self { vertices, indices };
+        }
+
+        let n = points.len();
+        let twice_area: f32 = (0..n).map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            a.x * b.y - b.x * a.y
+        }).sum();
+        if twice_area < 0.0 {
+            points.reverse();
+        }
+
+        let (mut min, mut max) = (points[0], points[0]);
+        for p in &points {
+            min = Vector2::new(min.x.min(p.x), min.y.min(p.y));
+            max = Vector2::new(max.x.max(p.x), max.y.max(p.y));
+        }
+        let size = Vector2::new((max.x - min.x).max(f32::EPSILON), (max.y - min.y).max(f32::EPSILON));
+        let d2 = 0.5 * depth.abs();
+
+        // Front cap on +z as is, the back one seen from behind, mirrored in x.
+        let triangles = ear_clip(&points);
+        for &side in &[1.0f32, -1.0] {
+            let base = vertices.len() as u32;
+            for p in &points {
+                let u = (if side > 0.0 { p.x - min.x } else { max.x - p.x }) / size.x;
+                vertices.push(Vertex {
+                    position: Vector3::new(p.x, p.y, side * d2),
+                    normal: Vector3::new(0.0, 0.0, side),
+                    tangent: Vector3::new(side, 0.0, 0.0),
+                    tex_coord: Vector2::new(u, (max.y - p.y) / size.y),
+                });
+            }
+            for &[a, b, c] in &triangles {
+                if side > 0.0 {
+                    indices.extend_from_slice(&[base + a, base + b, base + c]);
+                } else {
+                    indices.extend_from_slice(&[base + a, base + c, base + b]);
+                }
+            }
+        }
+
+        let perimeter: f32 = (0..n).map(|i| (points[(i + 1) % n] - points[i]).magnitude()).sum();
+        let mut walked = 0.0;
+        for i in 0..n {
+            let (p0, p1) = (points[i], points[(i + 1) % n]);
+            let edge = p1 - p0;
+            let length = edge.magnitude();
+            if length == 0.0 {
+                continue;
+            }
+            // Counter-clockwise, so the outside is on the right of each edge.
+            let normal = Vector3::new(edge.y, -edge.x, 0.0) / length;
+            let tangent = Vector3::new(edge.x, edge.y, 0.0) / length;
+            let (u0, u1) = (walked / perimeter, (walked + length) / perimeter);
+            walked += length;
+
+            let base = vertices.len() as u32;
+            for &(p, z, u, v) in &[(p0, d2, u0, 0.0), (p1, d2, u1, 0.0), (p0, -d2, u0, 1.0), (p1, -d2, u1, 1.0)] {
+                vertices.push(Vertex {
+                    position: Vector3::new(p.x, p.y, z),
+                    normal,
+                    tangent,
+                    tex_coord: Vector2::new(u, v),
+                });
+            }
+            indices.extend_from_slice(&[
+                base, base + 2, base + 3,
+                base, base + 3, base + 1,
+            ]);
+        }
+
+        Self {
+            vertices,
+            indices,
+        }
+    }
+
+    /// Line segments from `(start, end)` pairs as quads `width` wide in world units,
+    /// turned to face a camera at `eye`, e.g. `Camera::transform().disp`, with
+    /// normals towards it, u along each segment and v across it.
+    ///
+    /// wgpu draws line lists one pixel wide: there's no line width in its pipeline
+    /// state since Metal and DX12 have none and Vulkan's wide lines are optional,
+    /// so thick lines have to be triangles. The quads only face the camera they
+    /// were built for, so rebuild them when it moves. Segments of zero length or
+    /// pointing straight at the camera are left out.
+    pub fn thick_lines(segments: &[(Vector3<f32>, Vector3<f32>)], width: f32, eye: Vector3<f32>) -> Self {
+        let mut vertices = Vec::with_capacity(4 * segments.len());
+        let mut indices = Vec::with_capacity(6 * segments.len());
+
+        for &(start, end) in segments {
+            let along = end - start;
+            let to_eye = eye - 0.5 * (start + end);
+            let side = along.cross(to_eye);
+            if along.magnitude2() == 0.0 || side.magnitude2() == 0.0 {
+                continue;
+            }
+            let side = 0.5 * width * side.normalize();
+            let normal = side.cross(along).normalize();
+            let tangent = along.normalize();
+
+            let base = vertices.len() as u32;
+            for &(position, u, v) in &[
+                (start - side, 0.0, 0.0),
+                (start + side, 0.0, 1.0),
+                (end - side, 1.0, 0.0),
+                (end + side, 1.0, 1.0),
+            ] {
+                vertices.push(Vertex {
+                    position,
+                    normal,
+                    tangent,
+                    tex_coord: Vector2::new(u, v),
+                });
+            }
+            // Counter-clockwise as seen from the camera.
+            indices.extend_from_slice(&[
+                base, base + 3, base + 2,
+                base, base + 1, base + 3,
+            ]);
+        }
+
+        Self {
+            vertices,
+            indices,
+        }
+    }
+
+    /// Polygonise the `f == 0` isosurface of a scalar field with marching cubes,
+    /// sampling `f` on a grid of `resolution` cells along each axis of `bounds`.
+    ///
+    /// `f` is treated like a signed distance, negative inside, and the triangles
+    /// face towards positive values. Vertices on a cell edge are shared with the
+    /// neighbouring cells, so `recompute_normals` gives a smooth surface. Texture
+    /// coordinates and tangents are left at zero.
+    pub fn from_sdf(bounds: Aabb, resolution: u32, f: impl Fn(Vector3<f32>) -> f32) -> Self {
+        let resolution = resolution.max(1) as usize;
+        let samples_per_axis = resolution + 1;
+        let step = bounds.size() / resolution as f32;
+        let grid_point = |(x, y, z): (usize, usize, usize)| {
+            bounds.min + Vector3::new(x as f32 * step.x, y as f32 * step.y, z as f32 * step.z)
+        };
+
+        let mut samples = Vec::with_capacity(samples_per_axis.pow(3));
+        for z in 0..samples_per_axis {
+            for y in 0..samples_per_axis {
+                for x in 0..samples_per_axis {
+                    samples.push(f(grid_point((x, y, z))));
+                }
+            }
+        }
+        let sample = |(x, y, z): (usize, usize, usize)| {
+            samples[x + samples_per_axis * (y + samples_per_axis * z)]
+        };
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        // Grid edges, as their two end points, to the vertex placed on them.
+        let mut edge_vertices = HashMap::new();
+
+        for z in 0..resolution {
+            for y in 0..resolution {
+                for x in 0..resolution {
+                    let corners = CORNERS.map(|(dx, dy, dz)| (x + dx, y + dy, z + dz));
+                    let values = corners.map(sample);
+
+                    let mut case = 0;
+                    for (i, value) in values.iter().enumerate() {
+                        if *value < 0.0 {
+                            case |= 1 << i;
+                        }
+                    }
+                    if EDGE_TABLE[case] == 0 {
+                        continue;
+                    }
+
+                    for &edge in TRI_TABLE[case].iter().take_while(|&&edge| edge != -1) {
+                        let (a, b) = EDGES[edge as usize];
+                        let key = if corners[a] < corners[b] { (corners[a], corners[b]) } else { (corners[b], corners[a]) };
+                        let index = *edge_vertices.entry(key).or_insert_with(|| {
+                            // The signs differ across a crossed edge, so this never divides by zero.
+                            let t = values[a] / (values[a] - values[b]);
+                            let pa = grid_point(corners[a]);
+                            let position = pa + (grid_point(corners[b]) - pa) * t;
+                            vertices.push(new_vertex!(position.x, position.y, position.z));
+                            vertices.len() as u32 - 1
+                        });
+                        indices.push(index);
+                    }
+                }
+            }
+        }
+
+        let mut mesh = Self {
+            vertices,
+            indices,
+        };
+        mesh.recompute_normals();
+        mesh
+    }
+
+    /// Load the triangles of a Wavefront OBJ file. Polygons are fan triangulated,
+    /// `v`/`vt` pairs become vertices, and the `s` directives decide which faces
+    /// share smooth normals, see `weld_by_smoothing_groups`. Faces before any `s`
+    /// are smoothed together, while those under `s off` or `s 0` are in group 0
+    /// and flat shaded. `vn` is ignored since the normals are generated, and
+    /// tangents are left at zero.
+    pub fn from_obj(source: &str) -> Result<Self> {
+        Self::from_obj_with_up_axis(source, UpAxis::Y)
+    }
+
+    /// `from_obj` for a file authored `up_axis` up, baked into Y-up.
+    pub fn from_obj_with_up_axis(source: &str, up_axis: UpAxis) -> Result<Self> {
+        let mut positions: Vec<Vector3<f32>> = Vec::new();
+        let mut tex_coords: Vec<[f32; 2]> = Vec::new();
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut groups = Vec::new();
+        // (position, texture coordinate) indices -> vertex index.
+        let mut vertex_indices: HashMap<(usize, Option<usize>), u32> = HashMap::new();
+        // Until the first `s`, faces are smooth, in a group no directive names.
+        let mut group = u32::MAX;
+
+        // OBJ indices are 1-based, negative ones count back from the end.
+        let resolve = |index: &str, len: usize, line: usize| -> Result<usize> {
+            let index: i64 = index.parse().with_context(|| format!("line {}: bad index {:?}", line, index))?;
+            let resolved = if index < 0 { len as i64 + index } else { index - 1 };
+            if resolved < 0 || resolved >= len as i64 {
+                bail!("line {}: index {} out of range", line, index);
+            }
+            Ok(resolved as usize)
+        };
+
+        for (number, line) in source.lines().enumerate() {
+            let number = number + 1;
+            let mut tokens = line.split_whitespace();
+            let floats = |tokens: std::str::SplitWhitespace, count: usize| -> Result<Vec<f32>> {
+                let values = tokens
+                    .take(count)
+                    .map(|token| token.parse::<f32>())
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .with_context(|| format!("line {}: bad number", number))?;
+                if values.len() < count {
+                    bail!("line {}: expected {} numbers", number, count);
+                }
+                Ok(values)
+            };
+
+            match tokens.next() {
+                Some("v") => {
+                    let v = floats(tokens, 3)?;
+                    positions.push(Vector3::new(v[0], v[1], v[2]));
+                }
+                Some("vt") => {
+                    let vt = floats(tokens, 2)?;
+                    // OBJ puts v = 0 at the bottom of the image, wgpu at the top.
+                    tex_coords.push([vt[0], 1.0 - vt[1]]);
+                }
+                Some("s") => {
+                    group = match tokens.next() {
+                        Some("off") | None => 0,
+                        Some(id) => id.parse().with_context(|| format!("line {}: bad smoothing group {:?}", number, id))?,
+                    };
+                }
+                Some("f") => {
+                    let mut face = Vec::new();
+                    for corner in tokens {
+                        let mut parts = corner.split('/');
+                        let position = resolve(parts.next().unwrap_or(""), positions.len(), number)?;
+                        let tex_coord = match parts.next() {
+                            Some("") | None => None,
+                            Some(index) => Some(resolve(index, tex_coords.len(), number)?),
+                        };
+                        let index = *vertex_indices.entry((position, tex_coord)).or_insert_with(|| {
+                            let p = positions[position];
+                            let [u, v] = tex_coord.map_or([0.0, 0.0], |i| tex_coords[i]);
+                            vertices.push(new_vertex!(p.x, p.y, p.z, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, u, v));
+                            vertices.len() as u32 - 1
+                        });
+                        face.push(index);
+                    }
+                    if face.len() < 3 {
+                        bail!("line {}: face has fewer than 3 vertices", number);
+                    }
+                    for i in 1..face.len() - 1 {
+                        indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                        groups.push(group);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut mesh = Self {
+            vertices,
+            indices,
+        };
+        mesh.weld_by_smoothing_groups(&groups);
+        mesh.bake_up_axis(up_axis);
+        Ok(mesh)
+    }
+
+    /// Rotate the mesh from `up_axis` up to Y-up, positions, normals and tangents
+    /// alike. The rotation is proper, so the winding stays as it is.
+    pub fn bake_up_axis(&mut self, up_axis: UpAxis) {
+        if up_axis == UpAxis::Y {
+            return;
+        }
+        let rotation = up_axis.to_y_up();
+        for vertex in &mut self.vertices {
+            vertex.position = rotation * vertex.position;
+            vertex.normal = rotation * vertex.normal;
+            vertex.tangent = rotation * vertex.tangent;
+        }
+    }
+
+    /// Reverse the winding of every triangle, so clockwise data can be drawn with a
+    /// `Ccw` pipeline, see `PipelineBuilder::front_face`. The normals are left alone.
+    pub fn flip_winding(&mut self) {
+        for triangle in self.indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+
+    /// Replace the texture coordinates with the positions projected along `axis`,
+    /// scaled so the bounding box spans 0..1. The image is seen from the positive
+    /// side of the axis, upright with +y up, or -z up when projecting along y.
+    /// Surfaces parallel to the axis get a single row or column of texels.
+    pub fn planar_uv_project(&mut self, axis: Axis) {
+        let bounds = self.bounding_box();
+        for vertex in &mut self.vertices {
+            vertex.tex_coord = planar_uv(axis, vertex.position, &bounds);
+        }
+    }
+
+    /// Project texture coordinates per triangle along the axis its normal is most
+    /// aligned with, like `planar_uv_project`, with the bounding box scaled to 0..1
+    /// on each of the three planes.
+    ///
+    /// Vertices shared by triangles projected along different axes are split, so
+    /// there's a UV seam wherever the chosen axis changes, visible as a jump in the
+    /// texture across the edge. Curved surfaces get them along the 45 degree lines
+    /// between the axes, and opposite sides show the texture mirrored.
+    pub fn box_uv_project(&mut self) {
+        let bounds = self.bounding_box();
+        let mut split: HashMap<(u32, usize), u32> = HashMap::new();
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+        let source = &self.vertices;
+
+        for triangle in self.indices.chunks_exact_mut(3) {
+            let [p0, p1, p2] = [0, 1, 2].map(|i| source[triangle[i] as usize].position);
+            let normal = (p1 - p0).cross(p2 - p0);
+            let (x, y, z) = (normal.x.abs(), normal.y.abs(), normal.z.abs());
+            let axis = if x >= y && x >= z {
+                Axis::X
+            } else if y >= z {
+                Axis::Y
+            } else {
+                Axis::Z
+            };
+
+            for index in triangle.iter_mut() {
+                let original = *index;
+                *index = *split.entry((original, axis as usize)).or_insert_with(|| {
+                    let mut vertex = source[original as usize];
+                    vertex.tex_coord = planar_uv(axis, vertex.position, &bounds);
+                    vertices.push(vertex);
+                    vertices.len() as u32 - 1
+                });
+            }
+        }
+        self.vertices = vertices;
+    }
+
+    /// Turn the mesh inside out, reversing the winding and negating the normals, so
+    /// a closed mesh such as a sphere or brick can be viewed from inside, e.g. as a
+    /// sky dome. Unlike `flip_winding` alone, lighting then agrees with the faces.
+    pub fn invert(&mut self) {
+        self.flip_winding();
+        for vertex in &mut self.vertices {
+            vertex.normal = -vertex.normal;
+        }
+    }
+
+    /// Scale the positions by `s` per axis, e.g. a sphere into an ellipsoid.
+    ///
+    /// Normals are divided by `s` instead, the inverse transpose of the scale, so
+    /// they stay perpendicular to the stretched surface, and tangents, which lie in
+    /// it, are multiplied. Both are renormalized. An odd number of negative
+    /// components mirrors the mesh, and the winding is reversed to keep it front
+    /// facing. Panics when a component is zero, which would flatten the mesh.
+    pub fn scale(&mut self, s: Vector3<f32>) {
+        assert!(s.x != 0.0 && s.y != 0.0 && s.z != 0.0, "can't scale a mesh by zero");
+        let normalize = |v: Vector3<f32>| if v.magnitude2() > 0.0 { v.normalize() } else { v };
+
+        for vertex in &mut self.vertices {
+            vertex.position = vertex.position.mul_element_wise(s);
+            vertex.normal = normalize(vertex.normal.div_element_wise(s));
+            vertex.tangent = normalize(vertex.tangent.mul_element_wise(s));
+        }
+        if s.x * s.y * s.z < 0.0 {
+            self.flip_winding();
+        }
+    }
+
+    /// Append `other` placed by `transform`, offsetting its indices past this mesh's
+    /// vertices.
+    ///
+    /// Normals go through the inverse transpose of `transform`, so they stay
+    /// perpendicular to the surface under non-uniform scale. Tangents lie in the
+    /// surface and go through `transform` itself. Both are renormalized. A mirroring
+    /// transform has its triangles' winding reversed, keeping them front facing.
+    pub fn append_transformed(&mut self, other: &Mesh, transform: Matrix4<f32>) {
+        let linear = Matrix3::from_cols(transform.x.truncate(), transform.y.truncate(), transform.z.truncate());
+        let normal_matrix = linear.invert().map_or(linear, |inverse| inverse.transpose());
+        let normalize = |v: Vector3<f32>| if v.magnitude2() > 0.0 { v.normalize() } else { v };
+
+        let base = self.vertices.len() as u32;
+        self.vertices.reserve(other.vertices.len());
+        for vertex in &other.vertices {
+            let mut vertex = *vertex;
+            vertex.position = (transform * vertex.position.extend(1.0)).truncate();
+            vertex.normal = normalize(normal_matrix * vertex.normal);
+            vertex.tangent = normalize(linear * vertex.tangent);
+            self.vertices.push(vertex);
+        }
+
+        self.indices.reserve(other.indices.len());
+        let mirrored = linear.determinant() < 0.0;
+        for triangle in other.indices.chunks_exact(3) {
+            if mirrored {
+                self.indices.extend_from_slice(&[base + triangle[0], base + triangle[2], base + triangle[1]]);
+            } else {
+                self.indices.extend_from_slice(&[base + triangle[0], base + triangle[1], base + triangle[2]]);
+            }
+        }
+    }
+
+    /// Object space bounds of the vertices, empty for a mesh without any.
+    pub fn bounding_box(&self) -> Aabb {
+        let mut bounds = Aabb::empty();
+        for vertex in &self.vertices {
+            bounds.extend(vertex.position);
+        }
+        bounds
+    }
+
+    /// Center of mass of the surface, each triangle's centroid weighted by its
+    /// area, as if the mesh were an evenly thin shell.
+    ///
+    /// Unlike `bounding_box().center()`, it follows where the surface actually
+    /// is: a few outlying vertices barely move it, and densely modelled parts
+    /// don't pull it more than their area. Empty meshes, and meshes without any
+    /// triangle area such as point clouds, give the origin. Triangles with an
+    /// out of range index are left out.
+    pub fn center_of_mass(&self) -> Vector3<f32> {
+        let (weighted, area) = self.area_weighted_centroid();
+        if area > 0.0 { weighted / area } else { Vector3::zero() }
+    }
+
+    /// The sum of the triangle centroids times their areas, and the total area.
+    pub(super) fn area_weighted_centroid(&self) -> (Vector3<f32>, f32) {
+        let vertex_count = self.vertices.len();
+        let mut weighted = Vector3::zero();
+        let mut total = 0.0;
+        for triangle in self.indices.chunks_exact(3) {
+            if triangle.iter().any(|&index| index as usize >= vertex_count) {
+                continue;
+            }
+            let p0 = self.vertices[triangle[0] as usize].position;
+            let p1 = self.vertices[triangle[1] as usize].position;
+            let p2 = self.vertices[triangle[2] as usize].position;
+            let area = 0.5 * (p1 - p0).cross(p2 - p0).magnitude();
+            // Skips NaN areas as well.
+            if area > 0.0 {
+                weighted += (p0 + p1 + p2) * (area / 3.0);
+                total += area;
+            }
+        }
+        (weighted, total)
+    }
+
+    /// Push every vertex along its normal by `amplitude` times `noise::perlin_3d`
+    /// of its position scaled by `frequency`, then recompute the normals and
+    /// tangents for the new shape, e.g. to roughen a grid into terrain or a
+    /// sphere into a rock. The same `seed` always gives the same mesh.
+    ///
+    /// Vertices sharing a position move together as long as their normals agree,
+    /// which holds along the texture seams of the generators. Along hard edges
+    /// with split normals, like a brick's, the surface tears open.
+    pub fn displace_noise(&mut self, frequency: f32, amplitude: f32, seed: u32) {
+        for vertex in &mut self.vertices {
+            let p = vertex.position * frequency;
+            let offset = amplitude * noise::perlin_3d(seed, p.x, p.y, p.z);
+            if vertex.normal.magnitude2() > 0.0 {
+                vertex.position += vertex.normal.normalize() * offset;
+            }
+        }
+        self.recompute_normals();
+        self.recompute_tangents();
+    }
+
+    /// Blend toward `target`, the same mesh with its vertices moved, e.g. a face
+    /// with another expression. Positions, normals and tangents are interpolated
+    /// by `t`, 0 giving `self` and 1 `target`, the directions renormalized, while
+    /// the texture coordinates and triangles stay `self`'s. `MorphTarget` does the
+    /// same on the GPU without rebuilding the mesh.
+    ///
+    /// Panics when the meshes have different vertex counts. Vertices are paired
+    /// by index, so the two must list them in the same order.
+    pub fn morph(&self, target: &Mesh, t: f32) -> Mesh {
+        assert_eq!(
+            self.vertices.len(),
+            target.vertices.len(),
+            "morphing needs meshes with the same vertices",
+        );
+        let blend = |a: Vector3<f32>, b: Vector3<f32>| {
+            let v = a.lerp(b, t);
+            if v.magnitude2() > 0.0 { v.normalize() } else { v }
+        };
+        let vertices = self.vertices.iter().zip(&target.vertices).map(|(from, to)| Vertex {
+            position: from.position.lerp(to.position, t),
+            normal: blend(from.normal, to.normal),
+            tangent: blend(from.tangent, to.tangent),
+            tex_coord: from.tex_coord,
+        }).collect();
+
+        Mesh {
+            vertices,
+            indices: self.indices.clone(),
+        }
+    }
+
+    /// Set each vertex normal to the area weighted average of the normals of the
+    /// triangles using it.
+    pub fn recompute_normals(&mut self) {
+        for vertex in &mut self.vertices {
+            vertex.normal = Vector3::zero();
+        }
+
+        for triangle in self.indices.chunks_exact(3) {
+            let p0 = self.vertices[triangle[0] as usize].position;
+            let p1 = self.vertices[triangle[1] as usize].position;
+            let p2 = self.vertices[triangle[2] as usize].position;
+            // The cross product's length is twice the triangle's area, which does the weighting.
+            let normal = (p1 - p0).cross(p2 - p0);
+            for &index in triangle {
+                self.vertices[index as usize].normal += normal;
+            }
+        }
+
+        for vertex in &mut self.vertices {
+            if vertex.normal.magnitude2() > 0.0 {
+                vertex.normal = vertex.normal.normalize();
+            }
+        }
+    }
+
+    /// Run the steps chosen in `options` in the order each needs the previous:
+    ///
+    /// 1. `weld` first, so the later steps see which triangles are connected. When
+    ///    the normals are recomputed anyway the old normals and tangents are cleared
+    ///    beforehand, so vertices only split by them, like hard edges, get merged.
+    /// 2. `normals` next, averaged across the welded vertices.
+    /// 3. `tangents` after that, they are made orthogonal to the new normals.
+    /// 4. `optimize_vertex_cache` last, it only reorders what the others produced.
+    pub fn finalize(&mut self, options: FinalizeOptions) {
+        if options.weld {
+            if options.normals {
+                for vertex in &mut self.vertices {
+                    vertex.normal = Vector3::zero();
+                    vertex.tangent = Vector3::zero();
+                }
+            }
+            self.weld();
+        }
+        if options.normals {
+            self.recompute_normals();
+        }
+        if options.tangents {
+            self.recompute_tangents();
+        }
+        if options.optimize_vertex_cache {
+            self.optimize_vertex_cache();
+        }
+    }
+
+    /// Merge vertices whose attributes are all bitwise equal, such as the copies
+    /// `subdivide` makes of the midpoints of shared edges. Vertices differing only
+    /// in texture coordinates, like the two sides of a UV seam, stay apart.
+    pub fn weld(&mut self) {
+        let mut welded: HashMap<[u32; 11], u32> = HashMap::new();
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+        let remap: Vec<u32> = self.vertices.iter().map(|v| {
+            let key = [
+                v.position.x, v.position.y, v.position.z,
+                v.normal.x, v.normal.y, v.normal.z,
+                v.tangent.x, v.tangent.y, v.tangent.z,
+                v.tex_coord.x, v.tex_coord.y,
+            ].map(f32::to_bits);
+            *welded.entry(key).or_insert_with(|| {
+                vertices.push(*v);
+                vertices.len() as u32 - 1
+            })
+        }).collect();
+
+        for index in &mut self.indices {
+            *index = remap[*index as usize];
+        }
+        self.vertices = vertices;
+    }
+
+    /// Like `weld`, but merging vertices whose positions only agree to within
+    /// `epsilon`, e.g. ones written out rounded by an exporter. Positions are
+    /// snapped to a grid of `epsilon` sized cells and compared there, so two
+    /// points closer than `epsilon` but on either side of a cell boundary stay
+    /// apart. The merged vertex keeps the first copy's exact position. See
+    /// `suggest_weld_epsilon` for a value to start from.
+    pub fn weld_within(&mut self, epsilon: f32) {
+        assert!(epsilon > 0.0, "weld epsilon must be positive");
+        let mut welded: HashMap<([i64; 3], [u32; 8]), u32> = HashMap::new();
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+        let remap: Vec<u32> = self.vertices.iter().map(|v| {
+            let cell = [v.position.x, v.position.y, v.position.z].map(|c| (c / epsilon).round() as i64);
+            let rest = [
+                v.normal.x, v.normal.y, v.normal.z,
+                v.tangent.x, v.tangent.y, v.tangent.z,
+                v.tex_coord.x, v.tex_coord.y,
+            ].map(f32::to_bits);
+            *welded.entry((cell, rest)).or_insert_with(|| {
+                vertices.push(*v);
+                vertices.len() as u32 - 1
+            })
+        }).collect();
+
+        for index in &mut self.indices {
+            *index = remap[*index as usize];
+        }
+        self.vertices = vertices;
+    }
+
+    /// An epsilon for `weld_within` scaled to the mesh: a 100000th of its bounding
+    /// box's diagonal, so `mesh.weld_within(mesh.suggest_weld_epsilon())` suits a
+    /// ring and a building alike. It's fine enough to keep real features apart on
+    /// all but extremely detailed meshes, pass `weld_within` a value of your own
+    /// for those. `f32::EPSILON` for a mesh without any extent.
+    pub fn suggest_weld_epsilon(&self) -> f32 {
+        let bounds = self.bounding_box();
+        if bounds.is_empty() {
+            return f32::EPSILON;
+        }
+        (bounds.size().magnitude() * 1e-5).max(f32::EPSILON)
+    }
+
+    /// Merge vertices with bitwise equal positions and texture coordinates,
+    /// whatever their normals and tangents. UV seams, such as the wrap-around ring
+    /// of `sphere`, keep their vertices on both sides, while copies split only by
+    /// their shading, e.g. hard edges or normals accumulated in a different
+    /// order, are joined. Each merged vertex keeps the normal and tangent of the
+    /// first of its copies, so follow with `recompute_normals` and
+    /// `recompute_tangents` when those differed.
+    pub fn weld_preserving_seams(&mut self) {
+        let mut welded: HashMap<[u32; 5], u32> = HashMap::new();
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+        let remap: Vec<u32> = self.vertices.iter().map(|v| {
+            let key = [
+                v.position.x, v.position.y, v.position.z,
+                v.tex_coord.x, v.tex_coord.y,
+            ].map(f32::to_bits);
+            *welded.entry(key).or_insert_with(|| {
+                vertices.push(*v);
+                vertices.len() as u32 - 1
+            })
+        }).collect();
+
+        for index in &mut self.indices {
+            *index = remap[*index as usize];
+        }
+        self.vertices = vertices;
+    }
+
+    /// Set each vertex tangent to the direction of increasing u over the triangles
+    /// using it, made orthogonal to the normal. Vertices without usable texture
+    /// coordinates keep a zero tangent.
+    ///
+    /// Where a mirrored UV island meets its original, e.g. the middle of a
+    /// symmetric character, the triangles on either side map u in opposite
+    /// directions and averaging their tangents across the shared vertices would
+    /// cancel them into a seam. Those vertices are split first, the triangles
+    /// with left handed texture coordinates moving to a copy, so each island
+    /// keeps a consistent frame. This adds vertices and renumbers indices only for
+    /// meshes that have such shared vertices.
+    pub fn recompute_tangents(&mut self) {
+        // Tangent of each triangle and whether its texture coordinates are right
+        // handed, `None` where they're degenerate.
+        let triangle_tangents: Vec<Option<(Vector3<f32>, bool)>> = self.indices.chunks_exact(3).map(|triangle| {
+            let [v0, v1, v2] = [triangle[0], triangle[1], triangle[2]].map(|i| self.vertices[i as usize]);
+            let (e1, e2) = (v1.position - v0.position, v2.position - v0.position);
+            let (d1, d2) = (v1.tex_coord - v0.tex_coord, v2.tex_coord - v0.tex_coord);
+            let det = d1.x * d2.y - d2.x * d1.y;
+            if det.abs() < f32::EPSILON {
+                return None;
+            }
+            Some(((e1 * d2.y - e2 * d1.y) / det, det > 0.0))
+        }).collect();
+
+        // Which handedness each vertex is used with, right then left.
+        let mut used = vec![(false, false); self.vertices.len()];
+        for (triangle, tangent) in self.indices.chunks_exact(3).zip(&triangle_tangents) {
+            if let Some((_, right_handed)) = tangent {
+                for &index in triangle {
+                    let (right, left) = &mut used[index as usize];
+                    if *right_handed { *right = true } else { *left = true }
+                }
+            }
+        }
+        let mut mirrored_copies: Vec<Option<u32>> = vec![None; self.vertices.len()];
+        for (index, &used) in used.iter().enumerate() {
+            if used == (true, true) {
+                mirrored_copies[index] = Some(self.vertices.len() as u32);
+                self.vertices.push(self.vertices[index]);
+            }
+        }
+        for (triangle, tangent) in self.indices.chunks_exact_mut(3).zip(&triangle_tangents) {
+            if let Some((_, false)) = tangent {
+                for index in triangle {
+                    if let Some(copy) = mirrored_copies[*index as usize] {
+                        *index = copy;
+                    }
+                }
+            }
+        }
+
+        for vertex in &mut self.vertices {
+            vertex.tangent = Vector3::zero();
+        }
+        for (triangle, tangent) in self.indices.chunks_exact(3).zip(&triangle_tangents) {
+            if let Some((tangent, _)) = tangent {
+                for &index in triangle {
+                    self.vertices[index as usize].tangent += *tangent;
+                }
+            }
+        }
+
+        for vertex in &mut self.vertices {
+            let tangent = vertex.tangent - vertex.normal * vertex.normal.dot(vertex.tangent);
+            vertex.tangent = if tangent.magnitude2() > 0.0 { tangent.normalize() } else { Vector3::zero() };
+        }
+    }
+
+    /// Reorder the triangles so consecutive ones reuse recently transformed vertices,
+    /// with Tom Forsyth's "Linear-Speed Vertex Cache Optimisation", then renumber
+    /// the vertices in the order they're first used. The triangles themselves don't
+    /// change.
+    pub fn optimize_vertex_cache(&mut self) {
+        let num_triangle = self.indices.len() / 3;
+        let mut vertex_triangles = vec![Vec::new(); self.vertices.len()];
+        for (t, triangle) in self.indices.chunks_exact(3).enumerate() {
+            for &index in triangle {
+                vertex_triangles[index as usize].push(t);
+            }
+        }
+
+        let vertex_score = |cache_position: Option<usize>, remaining: usize| {
+            if remaining == 0 {
+                return -1.0;
+            }
+            let cache_score = match cache_position {
+                Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+                Some(position) => {
+                    let scale = 1.0 / (VERTEX_CACHE_SIZE - 3) as f32;
+                    (1.0 - (position - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+                }
+                None => 0.0,
+            };
+            cache_score + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+        };
+
+        let mut cache_positions: Vec<Option<usize>> = vec![None; self.vertices.len()];
+        let mut vertex_scores: Vec<f32> = vertex_triangles.iter().map(|t| vertex_score(None, t.len())).collect();
+        let triangle_score = |t: usize, vertex_scores: &[f32]| {
+            self.indices[t * 3..t * 3 + 3].iter().map(|&i| vertex_scores[i as usize]).sum::<f32>()
+        };
+        let mut triangle_scores: Vec<f32> = (0..num_triangle).map(|t| triangle_score(t, &vertex_scores)).collect();
+        let mut added = vec![false; num_triangle];
+
+        let mut cache: Vec<u32> = Vec::with_capacity(VERTEX_CACHE_SIZE + 3);
+        let mut order = Vec::with_capacity(self.indices.len());
+        let mut best = (0..num_triangle).max_by(|&a, &b| triangle_scores[a].partial_cmp(&triangle_scores[b]).unwrap());
+        let mut scan = 0;
+        for _ in 0..num_triangle {
+            // Nothing in the cache has triangles left, restart from any unused one.
+            let t = match best {
+                Some(t) => t,
+                None => {
+                    while added[scan] {
+                        scan += 1;
+                    }
+                    scan
+                }
+            };
+            added[t] = true;
+            let triangle = [self.indices[t * 3], self.indices[t * 3 + 1], self.indices[t * 3 + 2]];
+            order.extend_from_slice(&triangle);
+            for &index in &triangle {
+                vertex_triangles[index as usize].retain(|&other| other != t);
+            }
+
+            // The triangle's vertices move to the front of the cache, pushing the rest back.
+            let mut new_cache = triangle.to_vec();
+            new_cache.extend(cache.iter().copied().filter(|index| !triangle.contains(index)));
+            for &evicted in new_cache.iter().skip(VERTEX_CACHE_SIZE) {
+                cache_positions[evicted as usize] = None;
+                vertex_scores[evicted as usize] = vertex_score(None, vertex_triangles[evicted as usize].len());
+            }
+            new_cache.truncate(VERTEX_CACHE_SIZE);
+            cache = new_cache;
+
+            for (position, &index) in cache.iter().enumerate() {
+                cache_positions[index as usize] = Some(position);
+                vertex_scores[index as usize] = vertex_score(Some(position), vertex_triangles[index as usize].len());
+            }
+
+            best = None;
+            let mut best_score = f32::NEG_INFINITY;
+            for &index in &cache {
+                for &other in &vertex_triangles[index as usize] {
+                    triangle_scores[other] = triangle_score(other, &vertex_scores);
+                    if triangle_scores[other] > best_score {
+                        best = Some(other);
+                        best_score = triangle_scores[other];
+                    }
+                }
+            }
+        }
+
+        // Renumber the vertices by first use.
+        let mut remap = vec![u32::MAX; self.vertices.len()];
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+        for index in &mut order {
+            if remap[*index as usize] == u32::MAX {
+                remap[*index as usize] = vertices.len() as u32;
+                vertices.push(self.vertices[*index as usize]);
+            }
+            *index = remap[*index as usize];
+        }
+        self.vertices = vertices;
+        self.indices = order;
+    }
+
+    /// Generate normals that are only smoothed across triangles of the same
+    /// smoothing group, `groups` holding one group per triangle.
+    ///
+    /// A vertex used by triangles of several groups is split into one copy per
+    /// group, so group boundaries become hard edges. Within a group the normals
+    /// are averaged across every vertex at the same position, so seams in the
+    /// texture coordinates stay smooth. Group 0 means no smoothing, as `s off` or
+    /// `s 0` in OBJ: each of its triangles gets vertices of its own with the face
+    /// normal. Any other group is smoothed, `from_obj` puts faces before the first
+    /// `s` in one of their own.
+    pub fn weld_by_smoothing_groups(&mut self, groups: &[u32]) {
+        assert_eq!(groups.len(), self.indices.len() / 3, "expected one smoothing group per triangle");
+
+        let position_key = |v: &Vertex| [v.position.x.to_bits(), v.position.y.to_bits(), v.position.z.to_bits()];
+
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+        // (old vertex index, group) -> new vertex index.
+        let mut split: HashMap<(u32, u32), u32> = HashMap::new();
+        // (position, group) -> area weighted normal sum.
+        let mut normals: HashMap<([u32; 3], u32), Vector3<f32>> = HashMap::new();
+        // New vertices of smoothed triangles, with the key of the normal they take.
+        let mut smoothed: Vec<(u32, ([u32; 3], u32))> = Vec::new();
+
+        for (triangle, &group) in self.indices.chunks_exact_mut(3).zip(groups) {
+            let p0 = self.vertices[triangle[0] as usize].position;
+            let p1 = self.vertices[triangle[1] as usize].position;
+            let p2 = self.vertices[triangle[2] as usize].position;
+            let normal = (p1 - p0).cross(p2 - p0);
+
+            for index in triangle {
+                let mut vertex = self.vertices[*index as usize];
+                if group == 0 {
+                    vertex.normal = if normal.magnitude2() > 0.0 { normal.normalize() } else { normal };
+                    vertices.push(vertex);
+                    *index = vertices.len() as u32 - 1;
+                    continue;
+                }
+                let key = (position_key(&vertex), group);
+                *normals.entry(key).or_insert_with(Vector3::zero) += normal;
+                *index = *split.entry((*index, group)).or_insert_with(|| {
+                    vertices.push(vertex);
+                    smoothed.push((vertices.len() as u32 - 1, key));
+                    vertices.len() as u32 - 1
+                });
+            }
+        }
+
+        for (index, key) in smoothed {
+            let normal = normals[&key];
+            vertices[index as usize].normal = if normal.magnitude2() > 0.0 { normal.normalize() } else { normal };
+        }
+
+        self.vertices = vertices;
+    }
+
+    /// Each edge of the triangles once, as its two vertex indices, smaller first, in
+    /// the order they're first used. Edges are told apart by index, so where
+    /// vertices are split, e.g. along texture seams, the copies on either side
+    /// count as separate edges.
+    pub fn unique_edges(&self) -> Vec<[u32; 2]> {
+        let mut seen = HashSet::new();
+        let mut edges = Vec::new();
+        for triangle in self.indices.chunks_exact(3) {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                let edge = [a.min(b), a.max(b)];
+                if a != b && seen.insert(edge) {
+                    edges.push(edge);
+                }
+            }
+        }
+        edges
+    }
+
+    /// A line mesh of the triangles' edges, for wireframes and inspecting the
+    /// topology: the same vertices, with the indices of `unique_edges` in pairs.
+    /// Draw it with a `LineList` pipeline, the pairs don't form triangles.
+    pub fn to_wireframe(&self) -> Mesh {
+        Mesh {
+            vertices: self.vertices.clone(),
+            indices: self.unique_edges().iter().flatten().copied().collect(),
+        }
+    }
+
+    /// Convert the triangle list into triangle strips joined by primitive-restart
+    /// indices (`u32::MAX`). Draw the result with a `TriangleStrip` pipeline, see
+    /// `PipelineBuilder::topology` for the `strip_index_format` caveats.
+    ///
+    /// Strips are grown greedily through triangles sharing an edge with matching
+    /// winding, so the rendered triangles are the same as the list's. When the
+    /// strips wouldn't need fewer indices than the list, the list indices are
+    /// returned unchanged with `TriangleList`.
+    pub fn to_triangle_strip(&self) -> (Vec<u32>, wgpu::PrimitiveTopology) {
+        let num_triangle = self.indices.len() / 3;
+        let triangle = |t: usize| [self.indices[t * 3], self.indices[t * 3 + 1], self.indices[t * 3 + 2]];
+
+        // Directed edge, in the winding order of its triangle -> triangles containing it.
+        let mut edge_triangles: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+        for t in 0..num_triangle {
+            let [a, b, c] = triangle(t);
+            for &edge in &[(a, b), (b, c), (c, a)] {
+                edge_triangles.entry(edge).or_default().push(t);
+            }
+        }
+
+        // The triangle continuing a strip ending in `u, v` must contain the edge
+        // `u -> v` if it lands on an even strip position and `v -> u` otherwise,
+        // since the GPU flips the winding of every odd triangle in a strip.
+        let next_triangle = |u: u32, v: u32, even: bool, visited: &[bool], local: &HashSet<usize>| {
+            let edge = if even { (u, v) } else { (v, u) };
+            edge_triangles.get(&edge)?.iter().copied().find_map(|t| {
+                if visited[t] || local.contains(&t) {
+                    return None;
+                }
+                let [a, b, c] = triangle(t);
+                let third = if (a, b) == edge { c } else if (b, c) == edge { a } else { b };
+                Some((t, third))
+            })
+        };
+
+        let mut visited = vec![false; num_triangle];
+        let mut strips: Vec<Vec<u32>> = Vec::new();
+        for start in 0..num_triangle {
+            if visited[start] {
+                continue;
+            }
+
+            // Try the three rotations of the starting triangle and keep the longest strip.
+            let [a, b, c] = triangle(start);
+            let mut best: Option<(Vec<u32>, HashSet<usize>)> = None;
+            for &rotation in &[[a, b, c], [b, c, a], [c, a, b]] {
+                let mut strip = rotation.to_vec();
+                let mut local: HashSet<usize> = HashSet::new();
+                local.insert(start);
+                loop {
+                    let even = strip.len() % 2 == 0;
+                    let (u, v) = (strip[strip.len() - 2], strip[strip.len() - 1]);
+                    match next_triangle(u, v, even, &visited, &local) {
+                        Some((t, third)) => {
+                            local.insert(t);
+                            strip.push(third);
+                        }
+                        None => break,
+                    }
+                }
+                let longer = match &best {
+                    Some((best_strip, _)) => strip.len() > best_strip.len(),
+                    None => true,
+                };
+                if longer {
+                    best = Some((strip, local));
+                }
+            }
+
+            let (strip, local) = best.unwrap();
+            for t in local {
+                visited[t] = true;
+            }
+            strips.push(strip);
+        }
+
+        let strip_index_count = strips.iter().map(Vec::len).sum::<usize>() + strips.len().saturating_sub(1);
+        if strip_index_count >= self.indices.len() {
+            return (self.indices.clone(), wgpu::PrimitiveTopology::TriangleList);
+        }
+
+        let mut indices = Vec::with_capacity(strip_index_count);
+        for (i, strip) in strips.iter().enumerate() {
+            if i > 0 {
+                indices.push(u32::MAX);
+            }
+            indices.extend_from_slice(strip);
+        }
+        (indices, wgpu::PrimitiveTopology::TriangleStrip)
+    }
+
+    /// Split the triangles whose edges project longer than `pixel_threshold` on a
+    /// `viewport` sized in pixels, repeating until no edge is too long. The vertex
+    /// positions are taken to be in world space.
+    ///
+    /// Whether an edge is split only depends on the edge, and both triangles on it
+    /// share the new midpoint, so the result stays watertight. Edges with an end
+    /// behind the camera are left alone.
+    pub fn adaptive_subdivide(&mut self, camera: &Camera, viewport: (f32, f32), pixel_threshold: f32) {
+        // Bounds the work if the threshold is tiny compared to the mesh on screen.
+        const MAX_PASSES: u32 = 16;
+
+        let view_proj = camera.get_view_proj();
+        let half_viewport = Vector2::new(0.5 * viewport.0, 0.5 * viewport.1);
+        let to_screen = |vertex: &Vertex| {
+            let p = vertex.position;
+            let clip = view_proj * Vector4::new(p.x, p.y, p.z, 1.0);
+            if clip.w <= 0.0 {
+                return None;
+            }
+            Some(Vector2::new(clip.x / clip.w * half_viewport.x, clip.y / clip.w * half_viewport.y))
+        };
+
+        for _ in 0..MAX_PASSES {
+            let screen: Vec<_> = self.vertices.iter().map(to_screen).collect();
+            let too_long = |a: u32, b: u32| match (screen[a as usize], screen[b as usize]) {
+                (Some(a), Some(b)) => (b - a).magnitude() > pixel_threshold,
+                _ => false,
+            };
+
+            // Undirected edge -> index of its midpoint vertex.
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut indices = Vec::with_capacity(self.indices.len());
+            for triangle in self.indices.chunks_exact(3) {
+                let t = [triangle[0], triangle[1], triangle[2]];
+                let mut mids = [None; 3];
+                for (i, mid) in mids.iter_mut().enumerate() {
+                    let (a, b) = (t[i], t[(i + 1) % 3]);
+                    if !too_long(a, b) {
+                        continue;
+                    }
+                    let key = if a < b { (a, b) } else { (b, a) };
+                    let vertices = &mut self.vertices;
+                    *mid = Some(*midpoints.entry(key).or_insert_with(|| {
+                        let (va, vb) = (&vertices[a as usize], &vertices[b as usize]);
+                        let m = get_middle!(va, vb);
+                        vertices.push(m);
+                        vertices.len() as u32 - 1
+                    }));
+                }
+
+                match mids.iter().filter(|m| m.is_some()).count() {
+                    0 => indices.extend_from_slice(&t),
+                    1 => {
+                        // Halve the triangle through the split edge a-b.
+                        let i = mids.iter().position(Option::is_some).unwrap();
+                        let (a, b, c, m) = (t[i], t[(i + 1) % 3], t[(i + 2) % 3], mids[i].unwrap());
+                        indices.extend_from_slice(&[a, m, c, m, b, c]);
+                    }
+                    2 => {
+                        // Edges a-b and b-c are split, c-a is not.
+                        let i = mids.iter().position(Option::is_none).unwrap();
+                        let (c, a, b) = (t[i], t[(i + 1) % 3], t[(i + 2) % 3]);
+                        let (mab, mbc) = (mids[(i + 1) % 3].unwrap(), mids[(i + 2) % 3].unwrap());
+                        indices.extend_from_slice(&[mab, b, mbc, a, mab, mbc, a, mbc, c]);
+                    }
+                    _ => {
+                        let [m0, m1, m2] = [mids[0].unwrap(), mids[1].unwrap(), mids[2].unwrap()];
+                        indices.extend_from_slice(&[
+                            t[0], m0, m2,
+                            m0, t[1], m1,
+                            m0, m1, m2,
+                            m2, m1, t[2],
+                        ]);
+                    }
+                }
+            }
+
+            let converged = midpoints.is_empty();
+            self.indices = indices;
+            if converged {
+                break;
+            }
+        }
+    }
+
+    /// One step of Loop subdivision: every triangle splits into four, and both the
+    /// new edge vertices and the old vertices are moved to weighted averages of
+    /// their neighbours, so repeated steps converge to a smooth surface instead of
+    /// keeping the coarse facets like `subdivide` does.
+    ///
+    /// Interior edge vertices take 3/8 of each end and 1/8 of the two opposite
+    /// corners. Old interior vertices of valence n keep `1 - n * beta` of
+    /// themselves and take `beta` of each neighbour, with Loop's
+    /// `beta = (5/8 - (3/8 + cos(2 pi / n) / 4)^2) / n`. Edges with a single
+    /// triangle use the boundary rules instead, the midpoint for edge vertices and
+    /// 3/4 of the vertex plus 1/8 of its two boundary neighbours for old vertices,
+    /// so open borders stay put along their curve. Edges shared by more than two
+    /// triangles and vertices on more than two boundary edges are non-manifold;
+    /// they are treated like boundaries and corners, which keeps the split
+    /// topology valid.
+    ///
+    /// Connectivity comes from the indices, so vertices split at texture seams or
+    /// hard edges count as boundaries; `weld` first where that isn't wanted.
+    /// Texture coordinates are averaged with the same weights, and normals and
+    /// tangents are recomputed afterward.
+    pub fn subdivide_loop(&mut self) {
+        let edge_key = |a: u32, b: u32| if a < b { (a, b) } else { (b, a) };
+
+        // The corners opposite each edge, one per triangle using it. The edges are
+        // also kept in order of appearance, so the new vertices come out in the same
+        // order every time.
+        let mut edges = Vec::new();
+        let mut opposite: HashMap<(u32, u32), Vec<u32>> = HashMap::new();
+        for triangle in self.indices.chunks_exact(3) {
+            for k in 0..3 {
+                let (a, b, c) = (triangle[k], triangle[(k + 1) % 3], triangle[(k + 2) % 3]);
+                let key = edge_key(a, b);
+                opposite
+                    .entry(key)
+                    .or_insert_with(|| {
+                        edges.push(key);
+                        Vec::new()
+                    })
+                    .push(c);
+            }
+        }
+
+        let mut neighbours: Vec<HashSet<u32>> = vec![HashSet::new(); self.vertices.len()];
+        let mut boundary_neighbours: Vec<Vec<u32>> = vec![Vec::new(); self.vertices.len()];
+        for &(a, b) in &edges {
+            let corners = &opposite[&(a, b)];
+            neighbours[a as usize].insert(b);
+            neighbours[b as usize].insert(a);
+            if corners.len() != 2 {
+                boundary_neighbours[a as usize].push(b);
+                boundary_neighbours[b as usize].push(a);
+            }
+        }
+
+        let mix = |weights: &[(u32, f32)]| {
+            let mut position = Vector3::zero();
+            let mut tex_coord = Vector2::zero();
+            for &(index, weight) in weights {
+                position += self.vertices[index as usize].position * weight;
+                tex_coord += self.vertices[index as usize].tex_coord * weight;
+            }
+            Vertex { position, normal: Vector3::zero(), tangent: Vector3::zero(), tex_coord }
+        };
+
+        let mut vertices: Vec<Vertex> = (0..self.vertices.len() as u32)
+            .map(|v| {
+                let boundary = &boundary_neighbours[v as usize];
+                match boundary.len() {
+                    0 => {
+                        let n = neighbours[v as usize].len();
+                        if n == 0 {
+                            return mix(&[(v, 1.0)]);
+                        }
+                        let t = 0.375 + 0.25 * (2.0 * f32::consts::PI / n as f32).cos();
+                        let beta = (0.625 - t * t) / n as f32;
+                        let mut weights = vec![(v, 1.0 - n as f32 * beta)];
+                        weights.extend(neighbours[v as usize].iter().map(|&neighbour| (neighbour, beta)));
+                        mix(&weights)
+                    }
+                    2 => mix(&[(v, 0.75), (boundary[0], 0.125), (boundary[1], 0.125)]),
+                    // Corners where more than two boundaries meet stay where they are.
+                    _ => mix(&[(v, 1.0)]),
+                }
+            })
+            .collect();
+
+        let mut edge_vertices: HashMap<(u32, u32), u32> = HashMap::with_capacity(edges.len());
+        for &(a, b) in &edges {
+            let vertex = match opposite[&(a, b)].as_slice() {
+                &[c, d] => mix(&[(a, 0.375), (b, 0.375), (c, 0.125), (d, 0.125)]),
+                _ => mix(&[(a, 0.5), (b, 0.5)]),
+            };
+            edge_vertices.insert((a, b), vertices.len() as u32);
+            vertices.push(vertex);
+        }
+
+        let mut indices = Vec::with_capacity(self.indices.len() * 4);
+        for triangle in self.indices.chunks_exact(3) {
+            let (v0, v1, v2) = (triangle[0], triangle[1], triangle[2]);
+            let m0 = edge_vertices[&edge_key(v0, v1)];
+            let m1 = edge_vertices[&edge_key(v1, v2)];
+            let m2 = edge_vertices[&edge_key(v2, v0)];
+            indices.extend_from_slice(&[
+                v0, m0, m2,
+                m0, v1, m1,
+                m2, m1, v2,
+                m0, m1, m2,
+            ]);
+        }
+
+        self.vertices = vertices;
+        self.indices = indices;
+        self.recompute_normals();
+        self.recompute_tangents();
+    }
+
     fn subdivide(&mut self) {
         /*
          * Subdivide a mesh by subdivide each triangle.
@@ -274,4 +2036,269 @@ impl Mesh {
             ]);
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two faces of a unit cube meeting at a right angle along the edge x = 1, z = 0.
+    const CUBE_CORNER_OBJ: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 1 0 -1
+v 1 1 -1
+f 1 2 3 4
+f 2 5 6 3
+";
+
+    fn obj_with_smoothing(directive: &str) -> Mesh {
+        Mesh::from_obj(&format!("{}\n{}", directive, CUBE_CORNER_OBJ)).unwrap()
+    }
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn unsmoothed_obj_faces_get_flat_normals() {
+        for directive in &["s off", "s 0"] {
+            let mesh = obj_with_smoothing(directive);
+            // No vertex is shared between the four triangles.
+            assert_eq!(mesh.vertices.len(), 12, "with {:?}", directive);
+            for triangle in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize].position);
+                let face_normal = (b - a).cross(c - a).normalize();
+                for &index in triangle {
+                    assert_close(mesh.vertices[index as usize].normal, face_normal);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn smoothed_obj_faces_share_normals() {
+        // Without any `s`, faces are smooth as if under `s 1`.
+        for directive in &["", "s 1"] {
+            let mesh = obj_with_smoothing(directive);
+            assert_eq!(mesh.vertices.len(), 6, "with {:?}", directive);
+            // Tilted toward both faces along the shared edge.
+            for vertex in mesh.vertices.iter().filter(|v| v.position.x == 1.0 && v.position.z == 0.0) {
+                assert!(vertex.normal.x > 0.1 && vertex.normal.z > 0.1, "{:?} with {:?}", vertex.normal, directive);
+                assert!((vertex.normal.magnitude() - 1.0).abs() < 1e-5);
+            }
+        }
+    }
+
+    // Triangles of `indices`, each rotated to start at its smallest index so the
+    // same triangle compares equal whichever corner it's listed from, sorted.
+    fn canonical_triangles(triangles: impl Iterator<Item = [u32; 3]>) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = triangles
+            .map(|[a, b, c]| {
+                if a <= b && a <= c {
+                    [a, b, c]
+                } else if b <= c {
+                    [b, c, a]
+                } else {
+                    [c, a, b]
+                }
+            })
+            .collect();
+        triangles.sort_unstable();
+        triangles
+    }
+
+    // The triangles a GPU draws from strip `indices`, cut at restart indices, the
+    // winding of every odd triangle in a strip flipped back.
+    fn expand_strips(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles = Vec::new();
+        for strip in indices.split(|&i| i == u32::MAX) {
+            for (i, window) in strip.windows(3).enumerate() {
+                triangles.push(if i % 2 == 0 {
+                    [window[0], window[1], window[2]]
+                } else {
+                    [window[1], window[0], window[2]]
+                });
+            }
+        }
+        triangles
+    }
+
+    #[test]
+    fn triangle_strips_draw_the_same_triangles() {
+        for mesh in &[
+            Mesh::grid(2.0, 2.0, 8, 8, GridUvMode::Stretch),
+            Mesh::sphere(1.0, 32, 16),
+            Mesh::geo_sphere(1.0, 2),
+            Mesh::brick(1.0, 1.0, 1.0, 2),
+        ] {
+            let (indices, topology) = mesh.to_triangle_strip();
+            let expected = canonical_triangles(mesh.indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]));
+            let drawn = match topology {
+                wgpu::PrimitiveTopology::TriangleStrip => {
+                    assert!(indices.len() < mesh.indices.len());
+                    canonical_triangles(expand_strips(&indices).into_iter())
+                }
+                _ => canonical_triangles(indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]])),
+            };
+            assert_eq!(drawn, expected);
+        }
+    }
+
+    #[test]
+    fn grids_become_strips() {
+        let mesh = Mesh::grid(2.0, 2.0, 8, 8, GridUvMode::Stretch);
+        let (_, topology) = mesh.to_triangle_strip();
+        assert_eq!(topology, wgpu::PrimitiveTopology::TriangleStrip);
+    }
+
+    // Every triangle with vertices of its own, its normal flat, as if shaded with hard edges.
+    fn unweld(mesh: &Mesh) -> Mesh {
+        let mut vertices = Vec::with_capacity(mesh.indices.len());
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize]);
+            let face_normal = (b.position - a.position).cross(c.position - a.position).normalize();
+            for mut vertex in [a, b, c] {
+                vertex.normal = face_normal;
+                vertices.push(vertex);
+            }
+        }
+        Mesh {
+            indices: (0..vertices.len() as u32).collect(),
+            vertices,
+        }
+    }
+
+    #[test]
+    fn welding_keeps_uv_seams() {
+        let (slice, stack) = (16, 8);
+        let mut sphere = Mesh::sphere(1.0, slice, stack);
+        // Make the seam's two columns share positions bit for bit, as an exporter
+        // would, so only their texture coordinates tell them apart.
+        let ring_vertices = slice as usize + 1;
+        for ring in 0..stack as usize - 1 {
+            let first = 1 + ring * ring_vertices;
+            sphere.vertices[first + slice as usize].position = sphere.vertices[first].position;
+        }
+
+        let mut welded = unweld(&sphere);
+        welded.weld_preserving_seams();
+        assert_eq!(welded.vertices.len(), sphere.vertices.len());
+        let seam_vertices = |mesh: &Mesh, u: f32| mesh.vertices.iter().filter(|v| v.tex_coord.x == u).count();
+        assert_eq!(seam_vertices(&welded, 1.0), stack as usize - 1);
+        assert_eq!(seam_vertices(&welded, 0.0), seam_vertices(&sphere, 0.0));
+        assert_eq!(welded.indices.len(), sphere.indices.len());
+    }
+
+    #[test]
+    fn mirrored_uv_islands_keep_their_tangents() {
+        // Two quads side by side in the xy plane facing +z. The right one's u runs
+        // back from 1 to 0, mirroring the left one's across the shared edge x = 1.
+        let mut vertices = Vec::new();
+        for y in 0..2 {
+            for x in 0..3 {
+                let u = if x == 1 { 1.0 } else { 0.0 };
+                vertices.push(new_vertex!(x as f32, y as f32, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, u, y as f32));
+            }
+        }
+        let left = [0, 1, 4, 0, 4, 3];
+        let right = [1, 2, 5, 1, 5, 4];
+        let mut mesh = Mesh {
+            vertices,
+            indices: left.iter().chain(&right).copied().collect(),
+        };
+        mesh.recompute_tangents();
+
+        // The shared edge is split, each island's triangles using their own copies.
+        assert_eq!(mesh.vertices.len(), 8);
+        let (left_indices, right_indices) = mesh.indices.split_at(6);
+        for (indices, expected) in [(left_indices, Vector3::unit_x()), (right_indices, -Vector3::unit_x())] {
+            for &index in indices {
+                assert_close(mesh.vertices[index as usize].tangent, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn scaled_normals_follow_the_ellipsoid() {
+        let (a, b, c) = (2.0, 1.0, 0.5);
+        let mut mesh = Mesh::geo_sphere(1.0, 3);
+        mesh.scale(Vector3::new(a, b, c));
+        for vertex in &mesh.vertices {
+            let p = vertex.position;
+            // The gradient of x²/a² + y²/b² + z²/c², perpendicular to the surface.
+            let gradient = Vector3::new(p.x / (a * a), p.y / (b * b), p.z / (c * c)).normalize();
+            assert_close(vertex.normal, gradient);
+        }
+    }
+
+    #[test]
+    fn morph_blends_between_the_meshes() {
+        let base = Mesh::geo_sphere(1.0, 2);
+        let mut target = Mesh::geo_sphere(1.0, 2);
+        target.scale(Vector3::new(3.0, 1.0, 1.0));
+
+        let start = base.morph(&target, 0.0);
+        let end = base.morph(&target, 1.0);
+        let middle = base.morph(&target, 0.5);
+        assert_eq!(middle.indices, base.indices);
+        for i in 0..base.vertices.len() {
+            let (from, to) = (base.vertices[i], target.vertices[i]);
+            assert_close(start.vertices[i].position, from.position);
+            assert_close(start.vertices[i].normal, from.normal);
+            assert_close(end.vertices[i].position, to.position);
+            assert_close(end.vertices[i].normal, to.normal);
+            assert_close(middle.vertices[i].position, (from.position + to.position) / 2.0);
+            assert_close(middle.vertices[i].normal, (from.normal + to.normal).normalize());
+            assert_eq!(middle.vertices[i].tex_coord, from.tex_coord);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "morphing needs meshes with the same vertices")]
+    fn morph_needs_matching_vertex_counts() {
+        Mesh::geo_sphere(1.0, 2).morph(&Mesh::geo_sphere(1.0, 1), 0.5);
+    }
+
+    #[test]
+    fn inverted_sphere_faces_inward() {
+        let mut mesh = Mesh::sphere(1.0, 32, 16);
+        mesh.invert();
+        for vertex in &mesh.vertices {
+            assert_close(vertex.normal, -vertex.position.normalize());
+        }
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize].position);
+            assert!((b - a).cross(c - a).dot(a + b + c) < 0.0);
+        }
+    }
+
+    #[test]
+    fn sphere_sdf_gives_a_sphere() {
+        let resolution = 32;
+        let bounds = Aabb::new(Vector3::new(-1.5, -1.5, -1.5), Vector3::new(1.5, 1.5, 1.5));
+        let mut mesh = Mesh::from_sdf(bounds, resolution, |p| p.magnitude() - 1.0);
+        assert!(!mesh.indices.is_empty());
+
+        let cell = 3.0 / resolution as f32;
+        for vertex in &mesh.vertices {
+            assert!((vertex.position.magnitude() - 1.0).abs() < cell / 2.0);
+        }
+        // Closed and facing out, so the sphere's area comes out, give or take the facets.
+        let mut area = 0.0;
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize].position);
+            let face_normal = (b - a).cross(c - a);
+            assert!(face_normal.dot(a + b + c) >= 0.0);
+            area += face_normal.magnitude() / 2.0;
+        }
+        assert!((area / (4.0 * f32::consts::PI) - 1.0).abs() < 0.02, "area {}", area);
+
+        mesh.recompute_normals();
+        for vertex in &mesh.vertices {
+            assert!(vertex.normal.dot(vertex.position.normalize()) > 0.99);
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,292 @@
+use super::{Aabb, Mesh, Vertex};
+use std::collections::HashMap;
+use std::fmt;
+use cgmath::{InnerSpace, Vector3};
+
+// How far from 1 a normal or tangent's length may be and still count as unit.
+const UNIT_TOLERANCE: f32 = 1e-3;
+
+/// Diagnostics of a mesh from `Mesh::report`, printed with `Display`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshReport {
+    pub vertices: usize,
+    pub triangles: usize,
+    pub bounds: Aabb,
+    pub surface_area: f32,
+    /// Vertices with a NaN or infinite position, normal, tangent or texture coordinate.
+    pub non_finite_vertices: usize,
+    /// Triangles with zero area, two corners on the same index or position or all
+    /// three on a line. Their normals are undefined.
+    pub degenerate_triangles: usize,
+    /// Indices past the end of the vertices, `Mesh` doesn't check them on its own.
+    pub out_of_range_indices: usize,
+    /// Vertices whose normal isn't unit length, zero normals included.
+    pub non_unit_normals: usize,
+    /// Vertices whose tangent isn't unit length, zero tangents included.
+    pub non_unit_tangents: usize,
+}
+
+impl fmt::Display for MeshReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} vertices, {} triangles", self.vertices, self.triangles)?;
+        if self.bounds.is_empty() {
+            writeln!(f, "bounds: empty")?;
+        } else {
+            writeln!(
+                f,
+                "bounds: ({:.3}, {:.3}, {:.3}) to ({:.3}, {:.3}, {:.3})",
+                self.bounds.min.x, self.bounds.min.y, self.bounds.min.z,
+                self.bounds.max.x, self.bounds.max.y, self.bounds.max.z,
+            )?;
+        }
+        writeln!(f, "surface area: {:.4}", self.surface_area)?;
+        writeln!(f, "non-finite vertices: {}", self.non_finite_vertices)?;
+        writeln!(f, "degenerate triangles: {}", self.degenerate_triangles)?;
+        writeln!(f, "out of range indices: {}", self.out_of_range_indices)?;
+        writeln!(f, "non-unit normals: {}", self.non_unit_normals)?;
+        write!(f, "non-unit tangents: {}", self.non_unit_tangents)
+    }
+}
+
+/// Triangle and vertex problems of a mesh from `Mesh::diagnose`, printed with
+/// `Display`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MeshDiagnostics {
+    /// Triangles with zero area, including NaN positions.
+    pub zero_area_triangles: usize,
+    /// Triangles sharing an edge with a neighbour that runs it the same direction,
+    /// so one of the two faces the wrong way.
+    pub inconsistent_winding_triangles: usize,
+    /// Edges used by more than two triangles. Winding isn't defined across them
+    /// and they aren't counted as inconsistent.
+    pub non_manifold_edges: usize,
+    /// Vertices no triangle refers to.
+    pub orphan_vertices: usize,
+}
+
+impl MeshDiagnostics {
+    /// No problem found.
+    pub fn is_clean(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for MeshDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "zero area triangles: {}", self.zero_area_triangles)?;
+        writeln!(f, "inconsistent winding triangles: {}", self.inconsistent_winding_triangles)?;
+        writeln!(f, "non-manifold edges: {}", self.non_manifold_edges)?;
+        write!(f, "orphan vertices: {}", self.orphan_vertices)
+    }
+}
+
+/// How the triangles of a mesh from `Mesh::edge_report` meet along their edges,
+/// printed with `Display`. A closed mesh has none of the three kinds of problem
+/// edge, see `is_closed`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EdgeReport {
+    /// Distinct edges, problems included.
+    pub edges: usize,
+    /// Edges of a single triangle, the rim of a hole or an open sheet.
+    pub boundary_edges: usize,
+    /// Edges of more than two triangles.
+    pub non_manifold_edges: usize,
+    /// Edges of two triangles running them the same direction, so one of the
+    /// two faces the wrong way.
+    pub misoriented_edges: usize,
+}
+
+impl EdgeReport {
+    /// Every edge joins exactly two triangles running it in opposite directions,
+    /// so the mesh bounds a volume with a consistent inside, as `Mesh::invert` and
+    /// volume computations assume. True for a mesh without triangles.
+    pub fn is_closed(&self) -> bool {
+        self.boundary_edges == 0 && self.non_manifold_edges == 0 && self.misoriented_edges == 0
+    }
+}
+
+impl fmt::Display for EdgeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "edges: {}", self.edges)?;
+        writeln!(f, "boundary edges: {}", self.boundary_edges)?;
+        writeln!(f, "non-manifold edges: {}", self.non_manifold_edges)?;
+        write!(f, "misoriented edges: {}", self.misoriented_edges)
+    }
+}
+
+impl Mesh {
+    /// Sort the triangles' edges into the ones joining two consistently wound
+    /// triangles and the ones that keep the mesh from being closed, see
+    /// `EdgeReport`. Like `diagnose`, edges are matched by vertex position, so
+    /// vertices split along seams still connect. Triangles with an out of range
+    /// index are skipped, and edges collapsed to a point ignored.
+    pub fn edge_report(&self) -> EdgeReport {
+        let position_key = |v: &Vertex| [v.position.x.to_bits(), v.position.y.to_bits(), v.position.z.to_bits()];
+        let vertex_count = self.vertices.len();
+
+        // Undirected edge by its ordered position keys -> how many triangles run it
+        // from the smaller key to the larger, and the other way.
+        let mut edges: HashMap<([u32; 3], [u32; 3]), (usize, usize)> = HashMap::new();
+        for triangle in self.indices.chunks_exact(3) {
+            if triangle.iter().any(|&index| index as usize >= vertex_count) {
+                continue;
+            }
+            for i in 0..3 {
+                let a = position_key(&self.vertices[triangle[i] as usize]);
+                let b = position_key(&self.vertices[triangle[(i + 1) % 3] as usize]);
+                if a < b {
+                    edges.entry((a, b)).or_default().0 += 1;
+                } else if b < a {
+                    edges.entry((b, a)).or_default().1 += 1;
+                }
+            }
+        }
+
+        let mut report = EdgeReport { edges: edges.len(), ..EdgeReport::default() };
+        for &(forward, backward) in edges.values() {
+            match (forward, backward) {
+                (1, 1) => {}
+                (2, 0) | (0, 2) => report.misoriented_edges += 1,
+                (a, b) if a + b > 2 => report.non_manifold_edges += 1,
+                _ => report.boundary_edges += 1,
+            }
+        }
+        report
+    }
+
+    /// Whether the mesh is a closed manifold, see `EdgeReport::is_closed`. Use
+    /// `edge_report` to find out what's wrong with one that isn't.
+    pub fn is_closed(&self) -> bool {
+        self.edge_report().is_closed()
+    }
+
+    /// Look for the triangles that render as artifacts in imported or decimated
+    /// meshes, without changing anything. Neighbours are found by vertex position,
+    /// so vertices split along seams still connect, and triangles with an out of
+    /// range index are skipped, see `report` for those.
+    ///
+    /// A cleanup pass goes `report` for the counts, `diagnose` for the triangles,
+    /// then `weld` or `weld_preserving_seams` to drop duplicated vertices, and
+    /// `diagnose` again to confirm.
+    pub fn diagnose(&self) -> MeshDiagnostics {
+        let position_key = |v: &Vertex| [v.position.x.to_bits(), v.position.y.to_bits(), v.position.z.to_bits()];
+        let vertex_count = self.vertices.len();
+
+        let mut referenced = vec![false; vertex_count];
+        let mut zero_area_triangles = 0;
+        // Undirected edge by its ordered position keys -> triangles running it from
+        // the smaller key to the larger, and the other way.
+        let mut edges: HashMap<([u32; 3], [u32; 3]), (Vec<usize>, Vec<usize>)> = HashMap::new();
+        for (t, triangle) in self.indices.chunks_exact(3).enumerate() {
+            if triangle.iter().any(|&index| index as usize >= vertex_count) {
+                continue;
+            }
+            for &index in triangle {
+                referenced[index as usize] = true;
+            }
+
+            let corners = [
+                &self.vertices[triangle[0] as usize],
+                &self.vertices[triangle[1] as usize],
+                &self.vertices[triangle[2] as usize],
+            ];
+            let area = 0.5 * (corners[1].position - corners[0].position)
+                .cross(corners[2].position - corners[0].position)
+                .magnitude();
+            if area > 0.0 {
+                // Zero area triangles have no facing to compare.
+                for i in 0..3 {
+                    let (a, b) = (position_key(corners[i]), position_key(corners[(i + 1) % 3]));
+                    if a < b {
+                        edges.entry((a, b)).or_default().0.push(t);
+                    } else if b < a {
+                        edges.entry((b, a)).or_default().1.push(t);
+                    }
+                }
+            } else {
+                zero_area_triangles += 1;
+            }
+        }
+
+        let mut inconsistent = vec![false; self.indices.len() / 3];
+        let mut non_manifold_edges = 0;
+        for (forward, backward) in edges.values() {
+            match (forward.len(), backward.len()) {
+                (2, 0) | (0, 2) => {
+                    for &t in forward.iter().chain(backward) {
+                        inconsistent[t] = true;
+                    }
+                }
+                (a, b) if a + b > 2 => non_manifold_edges += 1,
+                _ => {}
+            }
+        }
+
+        MeshDiagnostics {
+            zero_area_triangles,
+            inconsistent_winding_triangles: inconsistent.iter().filter(|&&i| i).count(),
+            non_manifold_edges,
+            orphan_vertices: referenced.iter().filter(|&&r| !r).count(),
+        }
+    }
+
+    /// Counts, bounds, surface area and the problems worth knowing about when a
+    /// mesh renders wrong, in one pass over the vertices and one over the
+    /// triangles. Triangles using an out of range index are left out of the area.
+    pub fn report(&self) -> MeshReport {
+        let is_unit = |v: Vector3<f32>| (v.magnitude() - 1.0).abs() <= UNIT_TOLERANCE;
+
+        let mut non_finite_vertices = 0;
+        let mut non_unit_normals = 0;
+        let mut non_unit_tangents = 0;
+        for vertex in &self.vertices {
+            let finite = [vertex.position, vertex.normal, vertex.tangent]
+                .iter()
+                .all(|v| v.x.is_finite() && v.y.is_finite() && v.z.is_finite())
+                && vertex.tex_coord.x.is_finite()
+                && vertex.tex_coord.y.is_finite();
+            if !finite {
+                non_finite_vertices += 1;
+            }
+            if !is_unit(vertex.normal) {
+                non_unit_normals += 1;
+            }
+            if !is_unit(vertex.tangent) {
+                non_unit_tangents += 1;
+            }
+        }
+
+        let vertex_count = self.vertices.len() as u32;
+        let out_of_range_indices = self.indices.iter().filter(|&&index| index >= vertex_count).count();
+
+        let mut surface_area = 0.0;
+        let mut degenerate_triangles = 0;
+        for triangle in self.indices.chunks_exact(3) {
+            if triangle.iter().any(|&index| index >= vertex_count) {
+                continue;
+            }
+            let p0 = self.vertices[triangle[0] as usize].position;
+            let p1 = self.vertices[triangle[1] as usize].position;
+            let p2 = self.vertices[triangle[2] as usize].position;
+            let area = 0.5 * (p1 - p0).cross(p2 - p0).magnitude();
+            // NaN areas count as degenerate too.
+            if area > 0.0 {
+                surface_area += area;
+            } else {
+                degenerate_triangles += 1;
+            }
+        }
+
+        MeshReport {
+            vertices: self.vertices.len(),
+            triangles: self.indices.len() / 3,
+            bounds: self.bounding_box(),
+            surface_area,
+            non_finite_vertices,
+            degenerate_triangles,
+            out_of_range_indices,
+            non_unit_normals,
+            non_unit_tangents,
+        }
+    }
+}
\ No newline at end of file
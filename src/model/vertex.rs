@@ -4,6 +4,11 @@ pub trait AsVertexPrimitive {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a>;
 }
 
+/// Size in bytes of `Vertex`, and the stride of `Vertex::desc`. Update it together
+/// with the struct's fields and the attributes of `desc`, the assertion below
+/// fails to compile while they disagree.
+pub const VERTEX_SIZE: usize = 44;
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct Vertex {
@@ -13,13 +18,15 @@ pub struct Vertex {
     pub tex_coord: Vector2<f32>,
 }
 
+const _: () = assert!(std::mem::size_of::<Vertex>() == VERTEX_SIZE);
+
 unsafe impl bytemuck::Zeroable for Vertex {}
 unsafe impl bytemuck::Pod for Vertex {}
 
 impl AsVertexPrimitive for Vertex {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            array_stride: VERTEX_SIZE as wgpu::BufferAddress,
             step_mode: wgpu::InputStepMode::Vertex,
             attributes: &[
                 wgpu::VertexAttribute {
@@ -47,6 +54,72 @@ impl AsVertexPrimitive for Vertex {
     }
 }
 
+const POSITION_ATTRIBUTES: [wgpu::VertexAttribute; 1] = [
+    wgpu::VertexAttribute {
+        offset: 0,
+        shader_location: 0,
+        format: wgpu::VertexFormat::Float3,
+    },
+];
+const NORMAL_ATTRIBUTES: [wgpu::VertexAttribute; 1] = [
+    wgpu::VertexAttribute {
+        offset: 0,
+        shader_location: 1,
+        format: wgpu::VertexFormat::Float3,
+    },
+];
+const TANGENT_ATTRIBUTES: [wgpu::VertexAttribute; 1] = [
+    wgpu::VertexAttribute {
+        offset: 0,
+        shader_location: 2,
+        format: wgpu::VertexFormat::Float3,
+    },
+];
+const TEX_COORD_ATTRIBUTES: [wgpu::VertexAttribute; 1] = [
+    wgpu::VertexAttribute {
+        offset: 0,
+        shader_location: 3,
+        format: wgpu::VertexFormat::Float2,
+    },
+];
+
+impl Vertex {
+    /// Layout reading only `position` from interleaved vertices, skipping the rest
+    /// of each, for passes that only need the depth.
+    pub fn position_desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: VERTEX_SIZE as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &POSITION_ATTRIBUTES,
+        }
+    }
+
+    /// Layouts for one vertex buffer per attribute, as built by
+    /// `RenderItem::from_mesh_deinterleaved`. Buffer slot `i` feeds
+    /// `shader_location` `i`, the same locations as the interleaved `desc`:
+    ///
+    /// | slot | attribute   | format |
+    /// |------|-------------|--------|
+    /// | 0    | `position`  | Float3 |
+    /// | 1    | `normal`    | Float3 |
+    /// | 2    | `tangent`   | Float3 |
+    /// | 3    | `tex_coord` | Float2 |
+    pub fn deinterleaved_desc<'a>() -> Vec<wgpu::VertexBufferLayout<'a>> {
+        let layout = |stride: usize, attributes: &'a [wgpu::VertexAttribute]| wgpu::VertexBufferLayout {
+            array_stride: stride as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes,
+        };
+
+        vec![
+            layout(std::mem::size_of::<[f32; 3]>(), &POSITION_ATTRIBUTES),
+            layout(std::mem::size_of::<[f32; 3]>(), &NORMAL_ATTRIBUTES),
+            layout(std::mem::size_of::<[f32; 3]>(), &TANGENT_ATTRIBUTES),
+            layout(std::mem::size_of::<[f32; 2]>(), &TEX_COORD_ATTRIBUTES),
+        ]
+    }
+}
+
 macro_rules! new_vertex {
     ( 
         $px:expr, $py:expr, $pz:expr,
@@ -0,0 +1,234 @@
+use wgpu::util::DeviceExt;
+
+use crate::{model::{AsVertexPrimitive, Mesh, Vertex, VERTEX_SIZE}, pipeline::PipelineBuilder, post_process::{sampler_entry, texture_entry}, render_item::{DrawRenderItem, RenderItem}, texture::Texture};
+
+// The target's position and normal, read from its own interleaved vertex buffer
+// after the base mesh's, at the locations following `Vertex`'s.
+const TARGET_ATTRIBUTES: [wgpu::VertexAttribute; 2] = [
+    wgpu::VertexAttribute {
+        offset: 0,
+        shader_location: 4,
+        format: wgpu::VertexFormat::Float3,
+    },
+    wgpu::VertexAttribute {
+        offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+        shader_location: 5,
+        format: wgpu::VertexFormat::Float3,
+    },
+];
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct MorphUniforms {
+    weight: f32,
+    _padding: [f32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for MorphUniforms {}
+unsafe impl bytemuck::Pod for MorphUniforms {}
+
+/// `Mesh::morph` in the vertex shader: a render item drawn blended toward a
+/// target mesh by `weight`, 0 for the item as it is and 1 for the target, so
+/// animating the weight costs one uniform write a frame instead of a new mesh.
+///
+/// The target's vertices sit in a buffer of their own, read alongside the item's
+/// by vertex index, and only their positions and normals are blended. The item
+/// must be built from the `base` mesh given to `new`, and is shaded like the
+/// lit forward pass.
+pub struct MorphTarget {
+    pub weight: f32,
+
+    layout: wgpu::PipelineLayout,
+    vs_module: wgpu::ShaderModule,
+    fs_module: wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+    deinterleave_vertices: bool,
+    pipeline: wgpu::RenderPipeline,
+    target_buffer: wgpu::Buffer,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl MorphTarget {
+    /// `format`, `depth_format`, `sample_count` and `deinterleave_vertices` match
+    /// the scene pass, and the layouts are its sets 1 to 3. `diffuse` is the
+    /// texture the scene pass binds at set 0.
+    ///
+    /// Panics when `target` has a different vertex count than `base`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        base: &Mesh,
+        target: &Mesh,
+        diffuse: &Texture,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        deinterleave_vertices: bool,
+        camera_layout: &wgpu::BindGroupLayout,
+        object_layout: &wgpu::BindGroupLayout,
+        environment_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        assert_eq!(
+            base.vertices.len(),
+            target.vertices.len(),
+            "morphing needs meshes with the same vertices",
+        );
+
+        // The scene's texture and sampler, with the weight for the vertex stage.
+        let morph_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("morph_layout"),
+                entries: &[
+                    texture_entry(0),
+                    sampler_entry(1),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStage::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            }
+        );
+
+        let layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Morph Pipeline Layout"),
+                bind_group_layouts: &[&morph_layout, camera_layout, object_layout, environment_layout],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let target_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Morph Target Buffer"),
+                contents: bytemuck::cast_slice(&target.vertices),
+                usage: wgpu::BufferUsage::VERTEX,
+            }
+        );
+
+        let uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Morph Buffer"),
+                contents: bytemuck::cast_slice(&[MorphUniforms { weight: 0.0, _padding: [0.0; 3] }]),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            }
+        );
+
+        let bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("morph_bind_group"),
+                layout: &morph_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&diffuse.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&diffuse.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            }
+        );
+
+        // The lit pass's fragment stage reads the same inputs.
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/morph.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/shader.frag.spv"));
+        let pipeline = Self::build_pipeline(device, &layout, &vs_module, &fs_module, format, depth_format, sample_count, deinterleave_vertices);
+
+        Self {
+            weight: 0.0,
+            layout,
+            vs_module,
+            fs_module,
+            format,
+            depth_format,
+            deinterleave_vertices,
+            pipeline,
+            target_buffer,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    // Buffer slot of the target's vertices, after the ones of the item.
+    fn target_slot(deinterleave_vertices: bool) -> u32 {
+        if deinterleave_vertices { Vertex::deinterleaved_desc().len() as u32 } else { 1 }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        vs_module: &wgpu::ShaderModule,
+        fs_module: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        deinterleave_vertices: bool,
+    ) -> wgpu::RenderPipeline {
+        let mut vertex_layouts = if deinterleave_vertices { Vertex::deinterleaved_desc() } else { vec![Vertex::desc()] };
+        vertex_layouts.push(wgpu::VertexBufferLayout {
+            array_stride: VERTEX_SIZE as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &TARGET_ATTRIBUTES,
+        });
+        let mut builder = PipelineBuilder::new(layout, vs_module, fs_module, format)
+            .label("Morph Pipeline")
+            .depth_format(Some(depth_format))
+            .sample_count(sample_count);
+        for vertex_layout in vertex_layouts {
+            builder = builder.vertex_layout(vertex_layout);
+        }
+        builder.build(device)
+    }
+
+    /// Rebuild the pipeline for a scene pass with `sample_count` samples per pixel.
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.pipeline = Self::build_pipeline(
+            device,
+            &self.layout,
+            &self.vs_module,
+            &self.fs_module,
+            self.format,
+            self.depth_format,
+            sample_count,
+            self.deinterleave_vertices,
+        );
+    }
+
+    /// Upload `weight`.
+    pub fn update(&self, queue: &wgpu::Queue) {
+        let uniforms = MorphUniforms { weight: self.weight, _padding: [0.0; 3] };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+    }
+
+    /// Draw `item`, built from the base mesh, in the scene pass. The camera and
+    /// environment bind groups are bound again since the scene's layout differs
+    /// from the morph's in the first set.
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        environment_bind_group: &'a wgpu::BindGroup,
+        item: &'a RenderItem,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_bind_group(3, environment_bind_group, &[]);
+        render_pass.set_vertex_buffer(Self::target_slot(self.deinterleave_vertices), self.target_buffer.slice(..));
+        render_pass.draw_item(item);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,117 @@
+//! Seeded value and gradient noise for procedural meshes and textures.
+//!
+//! Everything is built from integer hashing and f32 additions and
+//! multiplications, with no table or transcendental function whose result could
+//! differ by platform, so the same seed and coordinates give the same values
+//! everywhere. Different seeds give unrelated noise.
+
+// Hash the lattice point `(x, y, z)` under `seed` into 32 well-mixed bits.
+fn hash(seed: u32, x: i32, y: i32, z: i32) -> u32 {
+    let mut h = seed
+        ^ (x as u32).wrapping_mul(0x85eb_ca6b)
+        ^ (y as u32).wrapping_mul(0xc2b2_ae35)
+        ^ (z as u32).wrapping_mul(0x27d4_eb2f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^= h >> 16;
+    h
+}
+
+// Cubic smoothstep, value noise's interpolation weight.
+fn smooth(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+// Perlin's quintic fade, also flat in its second derivative at the lattice points.
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Value noise in [-1, 1]: random values at the integer lattice points, blended
+/// smoothly in between. Blockier than `perlin_2d`, with its features on the
+/// lattice.
+pub fn value_noise_2d(seed: u32, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (i, j) = (x0 as i32, y0 as i32);
+    let (u, v) = (smooth(x - x0), smooth(y - y0));
+    let value = |di: i32, dj: i32| hash(seed, i + di, j + dj, 0) as f32 / u32::MAX as f32 * 2.0 - 1.0;
+
+    lerp(
+        lerp(value(0, 0), value(1, 0), u),
+        lerp(value(0, 1), value(1, 1), u),
+        v,
+    )
+}
+
+/// Perlin gradient noise in [-1, 1], zero at every integer lattice point. The
+/// gradients are the 8 directions to the edges and corners of a square.
+pub fn perlin_2d(seed: u32, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (i, j) = (x0 as i32, y0 as i32);
+    let (fx, fy) = (x - x0, y - y0);
+    let gradient = |di: i32, dj: i32| {
+        let (dx, dy) = (fx - di as f32, fy - dj as f32);
+        match hash(seed, i + di, j + dj, 0) & 7 {
+            0 => dx,
+            1 => -dx,
+            2 => dy,
+            3 => -dy,
+            4 => 0.5 * (dx + dy),
+            5 => 0.5 * (dx - dy),
+            6 => 0.5 * (-dx + dy),
+            _ => 0.5 * (-dx - dy),
+        }
+    };
+
+    let (u, v) = (fade(fx), fade(fy));
+    // The diagonals are halved so the extremes, in the middle of a cell, stay within 1.
+    let noise = lerp(
+        lerp(gradient(0, 0), gradient(1, 0), u),
+        lerp(gradient(0, 1), gradient(1, 1), u),
+        v,
+    );
+    noise.max(-1.0).min(1.0)
+}
+
+/// Perlin gradient noise in [-1, 1], zero at every integer lattice point, with
+/// Perlin's 12 gradients toward the edges of a cube. Its rare extremes slightly
+/// overshoot 1 and are clamped.
+pub fn perlin_3d(seed: u32, x: f32, y: f32, z: f32) -> f32 {
+    let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+    let (i, j, k) = (x0 as i32, y0 as i32, z0 as i32);
+    let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+    let gradient = |di: i32, dj: i32, dk: i32| {
+        let (dx, dy, dz) = (fx - di as f32, fy - dj as f32, fz - dk as f32);
+        // 16 cases over the 12 edges, four repeated, as in Perlin's improved noise.
+        match hash(seed, i + di, j + dj, k + dk) & 15 {
+            0 | 12 => dx + dy,
+            1 | 13 => -dx + dy,
+            2 => dx - dy,
+            3 => -dx - dy,
+            4 => dx + dz,
+            5 => -dx + dz,
+            6 => dx - dz,
+            7 => -dx - dz,
+            8 => dy + dz,
+            9 | 14 => -dy + dz,
+            10 => dy - dz,
+            _ => -dy - dz,
+        }
+    };
+
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+    let layer = |dk: i32| {
+        lerp(
+            lerp(gradient(0, 0, dk), gradient(1, 0, dk), u),
+            lerp(gradient(0, 1, dk), gradient(1, 1, dk), u),
+            v,
+        )
+    };
+    lerp(layer(0), layer(1), w).max(-1.0).min(1.0)
+}
\ No newline at end of file
@@ -0,0 +1,373 @@
+use cgmath::Vector3;
+use wgpu::util::DeviceExt;
+
+use crate::{model::{AsVertexPrimitive, Mesh, Vertex}, pipeline::PipelineBuilder, post_process::{sampler_entry, texture_entry, uniform_entry}, render_item::{DrawRenderItem, RenderItem}, texture::Texture};
+
+/// Sum of the weighted premultiplied colors in rgb and the weighted alphas in a,
+/// cleared to zero. It must be a float format that blends: the weights go up to
+/// 3000, far past what a normalized format holds, and half floats are enough.
+pub const OIT_ACCUM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Product of `1 - alpha` over the fragments in r, the share of the scene behind
+/// that still shows, cleared to one. Any blendable single channel format works.
+pub const OIT_REVEALAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+
+// Additive, for both channels of the accumulation target.
+const ACCUM_BLEND: wgpu::BlendState = wgpu::BlendState {
+    src_factor: wgpu::BlendFactor::One,
+    dst_factor: wgpu::BlendFactor::One,
+    operation: wgpu::BlendOperation::Add,
+};
+// Multiplies the revealage by one minus each fragment's alpha, written to r.
+const REVEALAGE_BLEND: wgpu::BlendState = wgpu::BlendState {
+    src_factor: wgpu::BlendFactor::Zero,
+    dst_factor: wgpu::BlendFactor::OneMinusSrcColor,
+    operation: wgpu::BlendOperation::Add,
+};
+
+/// A mesh drawn by `Oit`, e.g. one of many overlapping spheres.
+pub struct Translucent {
+    pub mesh: Mesh,
+    pub position: Vector3<f32>,
+    /// Linear color and opacity, not premultiplied.
+    pub color: [f32; 4],
+}
+
+/// Weighted blended order-independent transparency, after McGuire and Bavoil,
+/// for translucent meshes drawn over the forward scene in any order.
+///
+/// The accumulation pass draws every item into two targets at once, both faces,
+/// tested against the scene's depth without writing it. With the blend states
+/// it's set up with, `OIT_ACCUM_FORMAT` adds up the colors premultiplied by their
+/// alpha and `OIT_REVEALAGE_FORMAT` multiplies up how much of the scene still
+/// shows, each weighted to favor the nearer fragments. A fullscreen composite
+/// pass then divides the sums into an average color and alpha blends it over
+/// the scene by the total coverage.
+///
+/// No sorting is needed, at the cost of exactness: the weights only approximate
+/// the order, so layers of similar depth and opacity mix rather than occlude.
+/// With MSAA the targets are multisampled like the scene and resolved before
+/// compositing.
+pub struct Oit {
+    accum_layout: wgpu::PipelineLayout,
+    vs_module: wgpu::ShaderModule,
+    fs_module: wgpu::ShaderModule,
+    depth_format: wgpu::TextureFormat,
+    deinterleave_vertices: bool,
+    accum_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    composite_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    // With the buffer holding the color and its bind group.
+    items: Vec<(RenderItem, wgpu::Buffer, wgpu::BindGroup)>,
+
+    sample_count: u32,
+    accum_target: Texture,
+    revealage_target: Texture,
+    // The targets resolved, when they are multisampled.
+    resolved: Option<(Texture, Texture)>,
+    composite_bind_group: wgpu::BindGroup,
+}
+
+impl Oit {
+    /// `output_format`, `depth_format`, `sample_count` and `deinterleave_vertices`
+    /// match the scene pass, and the layouts are its sets 1 to 3.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        swap_chain_desc: &wgpu::SwapChainDescriptor,
+        output_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        deinterleave_vertices: bool,
+        camera_layout: &wgpu::BindGroupLayout,
+        object_layout: &wgpu::BindGroupLayout,
+        environment_layout: &wgpu::BindGroupLayout,
+        translucent: &[Translucent],
+    ) -> Self {
+        let material_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("translucent_layout"),
+                entries: &[uniform_entry(0)],
+            }
+        );
+        let accum_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("OIT Accumulation Pipeline Layout"),
+                bind_group_layouts: &[&material_layout, camera_layout, object_layout, environment_layout],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let items = translucent.iter().enumerate().map(|(i, translucent)| {
+            let label = format!("translucent_{}", i);
+            let mut item = if deinterleave_vertices {
+                RenderItem::from_mesh_deinterleaved(device, object_layout, &translucent.mesh, &label)
+            } else {
+                RenderItem::from_mesh(device, object_layout, &translucent.mesh, &label)
+            };
+            item.transform.disp = translucent.position;
+            item.write_transform(queue);
+
+            let color_buffer = device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Translucent Buffer"),
+                    contents: bytemuck::cast_slice(&[translucent.color]),
+                    usage: wgpu::BufferUsage::UNIFORM,
+                }
+            );
+            let bind_group = device.create_bind_group(
+                &wgpu::BindGroupDescriptor {
+                    label: Some("translucent_bind_group"),
+                    layout: &material_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: color_buffer.as_entire_binding(),
+                        },
+                    ],
+                }
+            );
+            (item, color_buffer, bind_group)
+        }).collect();
+
+        // The G-buffer's vertex stage passes on everything the shading needs.
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/gbuffer.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/oit_accum.frag.spv"));
+        let accum_pipeline = Self::build_accum_pipeline(device, &accum_layout, &vs_module, &fs_module, depth_format, sample_count, deinterleave_vertices);
+
+        let composite_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("oit_composite_layout"),
+                entries: &[texture_entry(0), texture_entry(1), sampler_entry(2)],
+            }
+        );
+        let composite_pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("OIT Composite Pipeline Layout"),
+                bind_group_layouts: &[&composite_layout],
+                push_constant_ranges: &[],
+            }
+        );
+        let composite_vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/fullscreen.vert.spv"));
+        let composite_fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/oit_composite.frag.spv"));
+        let composite_pipeline = PipelineBuilder::new(&composite_pipeline_layout, &composite_vs_module, &composite_fs_module, output_format)
+            .label("OIT Composite Pipeline")
+            .depth_format(None)
+            .cull_mode(wgpu::CullMode::None)
+            .alpha_blending()
+            .build(device);
+
+        // The composite reads texel centers only, so nothing is filtered.
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+
+        let (accum_target, revealage_target, resolved) = Self::create_targets(device, swap_chain_desc, sample_count);
+        let composite_bind_group = Self::create_composite_bind_group(device, &composite_layout, &sampler, &accum_target, &revealage_target, &resolved);
+
+        Self {
+            accum_layout,
+            vs_module,
+            fs_module,
+            depth_format,
+            deinterleave_vertices,
+            accum_pipeline,
+            composite_pipeline,
+            composite_layout,
+            sampler,
+            items,
+            sample_count,
+            accum_target,
+            revealage_target,
+            resolved,
+            composite_bind_group,
+        }
+    }
+
+    fn build_accum_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        vs_module: &wgpu::ShaderModule,
+        fs_module: &wgpu::ShaderModule,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        deinterleave_vertices: bool,
+    ) -> wgpu::RenderPipeline {
+        let vertex_layouts = if deinterleave_vertices { Vertex::deinterleaved_desc() } else { vec![Vertex::desc()] };
+        let mut builder = PipelineBuilder::new(layout, vs_module, fs_module, OIT_ACCUM_FORMAT)
+            .label("OIT Accumulation Pipeline")
+            .blend(ACCUM_BLEND, ACCUM_BLEND)
+            .color_target_blended(OIT_REVEALAGE_FORMAT, REVEALAGE_BLEND, REVEALAGE_BLEND)
+            .depth_format(Some(depth_format))
+            // Hidden by the opaque scene, but not by each other.
+            .depth_write(false)
+            .cull_mode(wgpu::CullMode::None)
+            .sample_count(sample_count);
+        for vertex_layout in vertex_layouts {
+            builder = builder.vertex_layout(vertex_layout);
+        }
+        builder.build(device)
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        swap_chain_desc: &wgpu::SwapChainDescriptor,
+        sample_count: u32,
+    ) -> (Texture, Texture, Option<(Texture, Texture)>) {
+        let (width, height) = (swap_chain_desc.width, swap_chain_desc.height);
+        let resolved = if sample_count > 1 {
+            Some((
+                Texture::create_render_target(device, width, height, OIT_ACCUM_FORMAT, "oit_accum_resolved"),
+                Texture::create_render_target(device, width, height, OIT_REVEALAGE_FORMAT, "oit_revealage_resolved"),
+            ))
+        } else {
+            None
+        };
+        (
+            Texture::create_multisampled_render_target(device, width, height, OIT_ACCUM_FORMAT, sample_count, "oit_accum"),
+            Texture::create_multisampled_render_target(device, width, height, OIT_REVEALAGE_FORMAT, sample_count, "oit_revealage"),
+            resolved,
+        )
+    }
+
+    fn create_composite_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        accum_target: &Texture,
+        revealage_target: &Texture,
+        resolved: &Option<(Texture, Texture)>,
+    ) -> wgpu::BindGroup {
+        let (accum, revealage) = match resolved {
+            Some((accum, revealage)) => (accum, revealage),
+            None => (accum_target, revealage_target),
+        };
+        device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("oit_composite_bind_group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&accum.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&revealage.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+            }
+        )
+    }
+
+    /// Recreate the targets at the swap chain's new size.
+    pub fn resize(&mut self, device: &wgpu::Device, swap_chain_desc: &wgpu::SwapChainDescriptor) {
+        let (accum_target, revealage_target, resolved) = Self::create_targets(device, swap_chain_desc, self.sample_count);
+        self.composite_bind_group = Self::create_composite_bind_group(device, &self.composite_layout, &self.sampler, &accum_target, &revealage_target, &resolved);
+        self.accum_target = accum_target;
+        self.revealage_target = revealage_target;
+        self.resolved = resolved;
+    }
+
+    /// Rebuild the accumulation pipeline and targets for a scene pass with
+    /// `sample_count` samples per pixel.
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, swap_chain_desc: &wgpu::SwapChainDescriptor, sample_count: u32) {
+        self.accum_pipeline = Self::build_accum_pipeline(
+            device,
+            &self.accum_layout,
+            &self.vs_module,
+            &self.fs_module,
+            self.depth_format,
+            sample_count,
+            self.deinterleave_vertices,
+        );
+        self.sample_count = sample_count;
+        self.resize(device, swap_chain_desc);
+    }
+
+    /// Record the accumulation pass, tested against `depth`, the scene pass's depth
+    /// texture, and the composite over `output`, the scene pass's resolved color.
+    /// The bind groups are the scene pass's, for the layouts given to `new`.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        depth: &wgpu::TextureView,
+        camera_bind_group: &wgpu::BindGroup,
+        environment_bind_group: &wgpu::BindGroup,
+    ) {
+        if self.items.is_empty() {
+            return;
+        }
+
+        {
+            let (accum_resolve, revealage_resolve) = match &self.resolved {
+                Some((accum, revealage)) => (Some(&accum.view), Some(&revealage.view)),
+                None => (None, None),
+            };
+            let target = |attachment, resolve_target, clear| wgpu::RenderPassColorAttachmentDescriptor {
+                attachment,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(clear),
+                    store: true,
+                },
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("OIT Accumulation Pass"),
+                color_attachments: &[
+                    target(&self.accum_target.view, accum_resolve, wgpu::Color::TRANSPARENT),
+                    target(&self.revealage_target.view, revealage_resolve, wgpu::Color::WHITE),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                    attachment: depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+
+            render_pass.set_pipeline(&self.accum_pipeline);
+            render_pass.set_bind_group(1, camera_bind_group, &[]);
+            render_pass.set_bind_group(3, environment_bind_group, &[]);
+            for (item, _, bind_group) in &self.items {
+                render_pass.set_bind_group(0, bind_group, &[]);
+                render_pass.draw_item(item);
+            }
+        }
+
+        // Blended over the scene rather than clearing it, unlike `fullscreen_pass`.
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("OIT Composite Pass"),
+            color_attachments: &[
+                wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                }
+            ],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, &self.composite_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,84 @@
+use cgmath::{Decomposed, Deg, Quaternion, Rotation3, Vector3};
+
+use crate::camera::Camera;
+use crate::controller::{Controller, ControllerUpdate};
+use crate::timer::Timer;
+
+/// Drives a `Camera` around `target` at `distance`, dragging turns it around the
+/// target and scrolling zooms. Angles are in degrees, a positive `pitch` looks
+/// down from above.
+///
+/// With `auto_rotate` on, the camera starts spinning around the target by
+/// `spin_rate` degrees a second after `idle_seconds` without any controller
+/// input, and stops as soon as there is some.
+pub struct OrbitCamera {
+    pub camera: Camera,
+    pub target: Vector3<f32>,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+
+    pub auto_rotate: bool,
+    pub idle_seconds: f32,
+    pub spin_rate: f32,
+    idle_timer: Timer,
+}
+
+impl OrbitCamera {
+    pub fn new(camera: Camera, target: Vector3<f32>, distance: f32) -> Self {
+        let mut idle_timer = Timer::new();
+        idle_timer.reset();
+
+        let mut orbit = Self {
+            camera,
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            auto_rotate: false,
+            idle_seconds: 5.0,
+            spin_rate: 10.0,
+            idle_timer,
+        };
+        orbit.update_camera();
+        orbit
+    }
+
+    /// Whether the camera is spinning on its own.
+    pub fn is_idle(&self) -> bool {
+        self.auto_rotate && self.idle_timer.total_time() >= self.idle_seconds
+    }
+
+    pub(crate) fn update_camera(&mut self) {
+        let rot = Quaternion::from_angle_y(Deg(self.yaw)) * Quaternion::from_angle_x(Deg(-self.pitch));
+        self.camera.set_transform(Decomposed {
+            scale: 1.0,
+            rot,
+            // The camera looks down its local -z, so it sits on local +z.
+            disp: self.target + rot * Vector3::unit_z() * self.distance,
+        });
+    }
+}
+
+impl ControllerUpdate for OrbitCamera {
+    fn update(&mut self, controller: &Controller, duration: f32) {
+        self.idle_timer.tick();
+        if controller.has_input() {
+            self.idle_timer.reset();
+        }
+
+        if controller.dragged {
+            self.yaw -= 0.25 * (controller.current_cursor.0 - controller.last_cursor.0) as f32;
+            self.pitch += 0.25 * (controller.current_cursor.1 - controller.last_cursor.1) as f32;
+            self.pitch = self.pitch.max(-89.0).min(89.0);
+        }
+        self.distance = (self.distance * (1.0 - 0.1 * controller.scroll_delta)).max(0.1);
+
+        if self.is_idle() {
+            self.yaw += self.spin_rate * duration;
+        }
+
+        self.update_camera();
+        self.camera.update_shake(duration);
+    }
+}
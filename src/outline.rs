@@ -0,0 +1,241 @@
+use wgpu::util::DeviceExt;
+
+use crate::{model::{AsVertexPrimitive, Vertex}, pipeline::PipelineBuilder, render_item::{DrawRenderItem, RenderItem}};
+
+// Stencil value left where the outlined item covers the frame.
+const MARKED: u32 = 1;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct OutlineUniforms {
+    color: [f32; 4],
+    viewport: [f32; 2],
+    width: f32,
+    _padding: f32,
+}
+
+unsafe impl bytemuck::Zeroable for OutlineUniforms {}
+unsafe impl bytemuck::Pod for OutlineUniforms {}
+
+/// A `width` pixel rim of `color` around a render item, drawn in the scene pass
+/// right after the scene with the depth-stencil attachment's stencil.
+///
+/// The item is drawn twice. First into the stencil only, marking every pixel it
+/// covers, hidden or not. Then pushed out along its normals in screen space,
+/// only where nothing was marked, so just the rim beyond the silhouette shows,
+/// over whatever is in front. The push follows the vertex normals, so hard edges
+/// with split normals, like a brick's corners, open small gaps in the rim.
+pub struct Outline {
+    pub color: [f32; 4],
+    pub width: f32,
+
+    layout: wgpu::PipelineLayout,
+    mark_vs_module: wgpu::ShaderModule,
+    mark_fs_module: wgpu::ShaderModule,
+    vs_module: wgpu::ShaderModule,
+    fs_module: wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+    deinterleave_vertices: bool,
+    mark_pipeline: wgpu::RenderPipeline,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+}
+
+impl Outline {
+    /// `depth_format` must have a stencil, see `Texture::has_stencil`, and
+    /// `format`, `sample_count` and `deinterleave_vertices` match the scene pass.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        deinterleave_vertices: bool,
+        camera_layout: &wgpu::BindGroupLayout,
+        object_layout: &wgpu::BindGroupLayout,
+        color: [f32; 4],
+        width: f32,
+    ) -> Self {
+        let uniform_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("outline_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            }
+        );
+
+        let layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Outline Pipeline Layout"),
+                bind_group_layouts: &[&uniform_layout, camera_layout, object_layout],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Outline Buffer"),
+                contents: bytemuck::cast_slice(&[OutlineUniforms {
+                    color,
+                    viewport: [1.0, 1.0],
+                    width,
+                    _padding: 0.0,
+                }]),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            }
+        );
+
+        let uniform_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("outline_bind_group"),
+                layout: &uniform_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            }
+        );
+
+        // The normal view's shaders read the same inputs, their color is masked out anyway.
+        let mark_vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/normal.vert.spv"));
+        let mark_fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/normal.frag.spv"));
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/outline.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/outline.frag.spv"));
+
+        let (mark_pipeline, pipeline) = Self::build_pipelines(
+            device,
+            &layout,
+            (&mark_vs_module, &mark_fs_module),
+            (&vs_module, &fs_module),
+            format,
+            depth_format,
+            sample_count,
+            deinterleave_vertices,
+        );
+
+        Self {
+            color,
+            width,
+            layout,
+            mark_vs_module,
+            mark_fs_module,
+            vs_module,
+            fs_module,
+            format,
+            depth_format,
+            deinterleave_vertices,
+            mark_pipeline,
+            pipeline,
+            uniform_buffer,
+            uniform_bind_group,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build_pipelines(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        mark_modules: (&wgpu::ShaderModule, &wgpu::ShaderModule),
+        modules: (&wgpu::ShaderModule, &wgpu::ShaderModule),
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        deinterleave_vertices: bool,
+    ) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+        let vertex_layouts = if deinterleave_vertices { Vertex::deinterleaved_desc() } else { vec![Vertex::desc()] };
+        let stencil = |compare, pass_op, write_mask| {
+            let face = wgpu::StencilFaceState {
+                compare,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: pass_op,
+                pass_op,
+            };
+            wgpu::StencilState {
+                front: face.clone(),
+                back: face,
+                read_mask: 0xff,
+                write_mask,
+            }
+        };
+        let builder = |label, (vs_module, fs_module)| {
+            let mut builder = PipelineBuilder::new(layout, vs_module, fs_module, format)
+                .label(label)
+                .depth_format(Some(depth_format))
+                // Hidden parts are marked and outlined too, the depth test would cut them off.
+                .depth_compare(wgpu::CompareFunction::Always)
+                .depth_write(false)
+                .sample_count(sample_count);
+            for vertex_layout in &vertex_layouts {
+                builder = builder.vertex_layout(vertex_layout.clone());
+            }
+            builder
+        };
+
+        let mark_pipeline = builder("Outline Mark Pipeline", mark_modules)
+            .stencil(stencil(wgpu::CompareFunction::Always, wgpu::StencilOperation::Replace, 0xff))
+            .color_write_mask(wgpu::ColorWrite::empty())
+            .build(device);
+        // The pushed out back faces fill the rim where the front faces fold away.
+        let pipeline = builder("Outline Pipeline", modules)
+            .stencil(stencil(wgpu::CompareFunction::NotEqual, wgpu::StencilOperation::Keep, 0))
+            .cull_mode(wgpu::CullMode::None)
+            .alpha_blending()
+            .build(device);
+        (mark_pipeline, pipeline)
+    }
+
+    /// Rebuild the pipelines for a scene pass with `sample_count` samples per pixel.
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        let (mark_pipeline, pipeline) = Self::build_pipelines(
+            device,
+            &self.layout,
+            (&self.mark_vs_module, &self.mark_fs_module),
+            (&self.vs_module, &self.fs_module),
+            self.format,
+            self.depth_format,
+            sample_count,
+            self.deinterleave_vertices,
+        );
+        self.mark_pipeline = mark_pipeline;
+        self.pipeline = pipeline;
+    }
+
+    /// Upload `color` and `width` for a viewport of `width` by `height` pixels.
+    pub fn update(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        let uniforms = OutlineUniforms {
+            color: self.color,
+            viewport: [width.max(1) as f32, height.max(1) as f32],
+            width: self.width,
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+    }
+
+    /// Outline `item` in `render_pass`, whose stencil must be cleared to zero.
+    /// `camera_bind_group` holds the view-projection, and is bound again since the
+    /// scene's layout differs from the outline's in the first set.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup, item: &'a RenderItem) {
+        render_pass.set_stencil_reference(MARKED);
+        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+
+        render_pass.set_pipeline(&self.mark_pipeline);
+        render_pass.draw_item(item);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.draw_item(item);
+    }
+}
\ No newline at end of file
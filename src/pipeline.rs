@@ -0,0 +1,262 @@
+use anyhow::{Context, Result};
+
+use crate::{error_scope::ErrorScopes, texture::Texture};
+
+pub struct PipelineBuilder<'a> {
+    label: Option<&'a str>,
+    layout: &'a wgpu::PipelineLayout,
+    vs_module: &'a wgpu::ShaderModule,
+    fs_module: &'a wgpu::ShaderModule,
+    vertex_layouts: Vec<wgpu::VertexBufferLayout<'a>>,
+    // One per color attachment, in fragment output location order, with the color
+    // and alpha blend of the ones that don't use `color_blend` and `alpha_blend`.
+    color_formats: Vec<(wgpu::TextureFormat, Option<(wgpu::BlendState, wgpu::BlendState)>)>,
+    depth_format: Option<wgpu::TextureFormat>,
+    depth_write: bool,
+    depth_compare: wgpu::CompareFunction,
+    depth_bias: wgpu::DepthBiasState,
+    stencil: wgpu::StencilState,
+    topology: wgpu::PrimitiveTopology,
+    front_face: wgpu::FrontFace,
+    cull_mode: wgpu::CullMode,
+    sample_count: u32,
+    color_blend: wgpu::BlendState,
+    alpha_blend: wgpu::BlendState,
+    color_write_mask: wgpu::ColorWrite,
+}
+
+impl<'a> PipelineBuilder<'a> {
+    pub fn new(
+        layout: &'a wgpu::PipelineLayout,
+        vs_module: &'a wgpu::ShaderModule,
+        fs_module: &'a wgpu::ShaderModule,
+        color_format: wgpu::TextureFormat,
+    ) -> Self {
+        Self {
+            label: None,
+            layout,
+            vs_module,
+            fs_module,
+            vertex_layouts: Vec::new(),
+            color_formats: vec![(color_format, None)],
+            depth_format: Some(Texture::DEPTH_FORMAT),
+            depth_write: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            depth_bias: wgpu::DepthBiasState::default(),
+            stencil: wgpu::StencilState::default(),
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: wgpu::CullMode::Back,
+            sample_count: 1,
+            color_blend: wgpu::BlendState::REPLACE,
+            alpha_blend: wgpu::BlendState::REPLACE,
+            color_write_mask: wgpu::ColorWrite::ALL,
+        }
+    }
+
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub fn vertex_layout(mut self, layout: wgpu::VertexBufferLayout<'a>) -> Self {
+        self.vertex_layouts.push(layout);
+        self
+    }
+
+    /// Add another color attachment after the ones already set, for passes writing
+    /// multiple render targets. Fragment output location `i` goes to the `i`th.
+    pub fn color_target(mut self, format: wgpu::TextureFormat) -> Self {
+        self.color_formats.push((format, None));
+        self
+    }
+
+    /// `color_target` blending with its own `color_blend` and `alpha_blend`
+    /// instead of the ones the other attachments share, for passes accumulating
+    /// differently into each target.
+    pub fn color_target_blended(mut self, format: wgpu::TextureFormat, color_blend: wgpu::BlendState, alpha_blend: wgpu::BlendState) -> Self {
+        self.color_formats.push((format, Some((color_blend, alpha_blend))));
+        self
+    }
+
+    pub fn depth_format(mut self, format: Option<wgpu::TextureFormat>) -> Self {
+        self.depth_format = format;
+        self
+    }
+
+    /// Whether fragments passing the depth test write their depth, on by default.
+    pub fn depth_write(mut self, enabled: bool) -> Self {
+        self.depth_write = enabled;
+        self
+    }
+
+    /// How a fragment's depth is compared with the stored one, `Less` by default.
+    /// `Always` draws over everything, e.g. for overlays that still need the
+    /// stencil.
+    pub fn depth_compare(mut self, compare: wgpu::CompareFunction) -> Self {
+        self.depth_compare = compare;
+        self
+    }
+
+    /// Stencil test and writes, off by default. Needs a `depth_format` with a
+    /// stencil aspect, see `Texture::has_stencil`, and the reference value comes
+    /// from `RenderPass::set_stencil_reference`.
+    pub fn stencil(mut self, stencil: wgpu::StencilState) -> Self {
+        self.stencil = stencil;
+        self
+    }
+
+    /// Constant added to each fragment's depth, in units of the depth format's
+    /// smallest step. Pushing shadow casters or decals away from the surfaces
+    /// they're compared against keeps them from fighting. Zero by default.
+    pub fn depth_bias(mut self, constant: i32) -> Self {
+        self.depth_bias.constant = constant;
+        self
+    }
+
+    /// Bias scaled by the triangle's depth slope, for the surfaces at grazing
+    /// angles a constant bias can't cover. Zero by default.
+    pub fn depth_bias_slope_scale(mut self, slope_scale: f32) -> Self {
+        self.depth_bias.slope_scale = slope_scale;
+        self
+    }
+
+    /// The largest total bias applied, 0 for no limit.
+    pub fn depth_bias_clamp(mut self, clamp: f32) -> Self {
+        self.depth_bias.clamp = clamp;
+        self
+    }
+
+    /// Strip topologies get `strip_index_format: Some(Uint32)` to match the index
+    /// buffers built by `RenderItem`, which makes `u32::MAX` the primitive-restart
+    /// index. The format has to agree with the one passed to `set_index_buffer`, and
+    /// Metal always has primitive restart on, so strips must only use the restart
+    /// value as a strip separator there.
+    ///
+    /// Items drawn with the pipeline must have been built for the same topology,
+    /// see `RenderItem::topology`.
+    pub fn topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// The winding of front facing triangles, which `cull_mode` culls relative to.
+    /// Meshes built by `Mesh` are counter-clockwise. For clockwise data set `Cw`
+    /// here or call `Mesh::flip_winding` on the mesh, not both, or the two cancel
+    /// out and the back faces are drawn instead.
+    pub fn front_face(mut self, front_face: wgpu::FrontFace) -> Self {
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: wgpu::CullMode) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    /// Must match the sample count of the color and depth attachments the pipeline
+    /// draws into.
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// Blend the fragment outputs over every color attachment by their alpha,
+    /// instead of replacing what's there.
+    pub fn alpha_blending(mut self) -> Self {
+        self.color_blend = wgpu::BlendState {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        };
+        self.alpha_blend = wgpu::BlendState {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        };
+        self
+    }
+
+    /// Blend the fragment outputs over the color attachments added without a blend
+    /// of their own with `color_blend` and `alpha_blend`, replacing by default.
+    pub fn blend(mut self, color_blend: wgpu::BlendState, alpha_blend: wgpu::BlendState) -> Self {
+        self.color_blend = color_blend;
+        self.alpha_blend = alpha_blend;
+        self
+    }
+
+    /// Which color channels get written, all by default. Empty for passes that
+    /// only fill the depth or stencil.
+    pub fn color_write_mask(mut self, mask: wgpu::ColorWrite) -> Self {
+        self.color_write_mask = mask;
+        self
+    }
+
+    /// `build`, but a pipeline wgpu rejects, e.g. for shader stages whose interfaces
+    /// don't match the layouts, is returned as an error instead of panicking.
+    pub fn try_build(self, device: &wgpu::Device, error_scopes: &ErrorScopes) -> Result<wgpu::RenderPipeline> {
+        let label = self.label.unwrap_or("unlabeled").to_string();
+        error_scopes
+            .scoped(|| self.build(device))
+            .with_context(|| format!("creating render pipeline {:?}", label))
+    }
+
+    pub fn build(self, device: &wgpu::Device) -> wgpu::RenderPipeline {
+        let strip_index_format = match self.topology {
+            wgpu::PrimitiveTopology::LineStrip | wgpu::PrimitiveTopology::TriangleStrip => {
+                Some(wgpu::IndexFormat::Uint32)
+            }
+            _ => None,
+        };
+
+        let color_targets: Vec<wgpu::ColorTargetState> = self.color_formats
+            .iter()
+            .map(|(format, blend)| {
+                let (color_blend, alpha_blend) = blend.clone().unwrap_or_else(|| (self.color_blend.clone(), self.alpha_blend.clone()));
+                wgpu::ColorTargetState {
+                    format: *format,
+                    alpha_blend,
+                    color_blend,
+                    write_mask: self.color_write_mask,
+                }
+            })
+            .collect();
+
+        device.create_render_pipeline(
+            &wgpu::RenderPipelineDescriptor {
+                label: self.label,
+                layout: Some(self.layout),
+                vertex: wgpu::VertexState {
+                    module: self.vs_module,
+                    entry_point: "main",
+                    buffers: &self.vertex_layouts,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: self.fs_module,
+                    entry_point: "main",
+                    targets: &color_targets,
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: self.topology,
+                    strip_index_format,
+                    front_face: self.front_face,
+                    cull_mode: self.cull_mode,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                },
+                depth_stencil: self.depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: self.depth_write,
+                    depth_compare: self.depth_compare,
+                    stencil: self.stencil.clone(),
+                    bias: self.depth_bias.clone(),
+                    clamp_depth: false,
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: self.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+            }
+        )
+    }
+}
\ No newline at end of file
@@ -0,0 +1,646 @@
+use wgpu::util::DeviceExt;
+
+use crate::{pipeline::PipelineBuilder, texture::Texture};
+
+/// Intermediate format of the bloom bright-pass and blur targets. A float format
+/// keeps the blurred highlights from banding or clipping before the composite.
+pub const BLOOM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct BloomUniforms {
+    threshold: f32,
+    intensity: f32,
+    direction: [f32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for BloomUniforms {}
+unsafe impl bytemuck::Pod for BloomUniforms {}
+
+/// Minimum local contrast, relative to the brightest neighbour, for FXAA to treat a
+/// pixel as an edge. 1/8 is the "high quality" preset, 1/4 is faster and blurrier.
+pub const FXAA_EDGE_THRESHOLD: f32 = 0.125;
+/// Absolute contrast below which FXAA skips a pixel, so dark regions aren't smeared.
+pub const FXAA_EDGE_THRESHOLD_MIN: f32 = 0.0312;
+/// How much sub-pixel aliasing is removed, 0 keeps it sharp and 1 is the softest.
+pub const FXAA_SUBPIXEL_QUALITY: f32 = 0.75;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct FxaaUniforms {
+    texel_size: [f32; 2],
+    edge_threshold: f32,
+    edge_threshold_min: f32,
+    subpixel_quality: f32,
+    // Pad to the 16 byte multiple std140 rounds the block up to.
+    _padding: [f32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for FxaaUniforms {}
+unsafe impl bytemuck::Pod for FxaaUniforms {}
+
+/// Format of the HDR scene target read by `ToneMapPass`.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapOperator {
+    /// `c / (1 + c)`, never clips but flattens the highlights.
+    Reinhard,
+    /// A fit of the ACES filmic curve, more contrast and a soft shoulder.
+    Aces,
+}
+
+impl ToneMapOperator {
+    pub fn next(self) -> Self {
+        match self {
+            ToneMapOperator::Reinhard => ToneMapOperator::Aces,
+            ToneMapOperator::Aces => ToneMapOperator::Reinhard,
+        }
+    }
+}
+
+/// How `ToneMapPass` maps HDR colors into display range, the scene color is
+/// multiplied by `exposure` before the operator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMap {
+    pub operator: ToneMapOperator,
+    pub exposure: f32,
+}
+
+impl Default for ToneMap {
+    fn default() -> Self {
+        Self {
+            operator: ToneMapOperator::Aces,
+            exposure: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct ToneMapUniforms {
+    // Matches the OPERATOR_* constants of tonemap.frag.
+    operator: u32,
+    exposure: f32,
+    // Pad to the 16 byte multiple std140 rounds the block up to.
+    _padding: [f32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for ToneMapUniforms {}
+unsafe impl bytemuck::Pod for ToneMapUniforms {}
+
+pub(crate) fn texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+pub(crate) fn sampler_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::FRAGMENT,
+        ty: wgpu::BindingType::Sampler {
+            filtering: true,
+            comparison: false,
+        },
+        count: None,
+    }
+}
+
+pub(crate) fn uniform_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn source_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, source: &Texture) -> wgpu::BindGroup {
+    device.create_bind_group(
+        &wgpu::BindGroupDescriptor {
+            label: Some("post_process_source_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&source.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&source.sampler),
+                },
+            ],
+        }
+    )
+}
+
+pub(crate) fn fullscreen_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    target: &wgpu::TextureView,
+    pipeline: &wgpu::RenderPipeline,
+    bind_groups: &[&wgpu::BindGroup],
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[
+            wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }
+        ],
+        depth_stencil_attachment: None,
+    });
+
+    render_pass.set_pipeline(pipeline);
+    for (i, bind_group) in bind_groups.iter().enumerate() {
+        render_pass.set_bind_group(i as u32, bind_group, &[]);
+    }
+    render_pass.draw(0..3, 0..1);
+}
+
+/// Bloom over a rendered scene texture.
+///
+/// The bright pass keeps the parts of the scene whose luminance is above
+/// `threshold` and writes them into a half resolution `BLOOM_FORMAT` target. It's
+/// blurred with a separable 9-tap Gaussian, horizontally into a second target and
+/// back vertically, then added onto the scene with `intensity` while writing the
+/// output.
+pub struct Bloom {
+    pub threshold: f32,
+    pub intensity: f32,
+
+    source_layout: wgpu::BindGroupLayout,
+    composite_layout: wgpu::BindGroupLayout,
+    bright_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+
+    params_buffer: wgpu::Buffer,
+    horizontal_buffer: wgpu::Buffer,
+    vertical_buffer: wgpu::Buffer,
+    params_bind_group: wgpu::BindGroup,
+    horizontal_bind_group: wgpu::BindGroup,
+    vertical_bind_group: wgpu::BindGroup,
+
+    target_size: (u32, u32),
+    bright_target: Texture,
+    blur_target: Texture,
+    scene_bind_group: wgpu::BindGroup,
+    bright_bind_group: wgpu::BindGroup,
+    blur_bind_group: wgpu::BindGroup,
+    composite_bind_group: wgpu::BindGroup,
+}
+
+impl Bloom {
+    pub fn new(
+        device: &wgpu::Device,
+        scene: &Texture,
+        output_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        threshold: f32,
+        intensity: f32,
+    ) -> Self {
+        let source_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("bloom_source_layout"),
+                entries: &[texture_entry(0), sampler_entry(1)],
+            }
+        );
+        let composite_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("bloom_composite_layout"),
+                entries: &[texture_entry(0), texture_entry(1), sampler_entry(2)],
+            }
+        );
+        let uniform_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("bloom_uniform_layout"),
+                entries: &[uniform_entry(0)],
+            }
+        );
+
+        let source_pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Bloom Source Pipeline Layout"),
+                bind_group_layouts: &[&source_layout, &uniform_layout],
+                push_constant_ranges: &[],
+            }
+        );
+        let composite_pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Bloom Composite Pipeline Layout"),
+                bind_group_layouts: &[&composite_layout, &uniform_layout],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/fullscreen.vert.spv"));
+        let bright_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/bloom_bright.frag.spv"));
+        let blur_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/bloom_blur.frag.spv"));
+        let composite_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/bloom_composite.frag.spv"));
+
+        let bright_pipeline = PipelineBuilder::new(&source_pipeline_layout, &vs_module, &bright_module, BLOOM_FORMAT)
+            .label("Bloom Bright Pipeline")
+            .depth_format(None)
+            .cull_mode(wgpu::CullMode::None)
+            .build(device);
+        let blur_pipeline = PipelineBuilder::new(&source_pipeline_layout, &vs_module, &blur_module, BLOOM_FORMAT)
+            .label("Bloom Blur Pipeline")
+            .depth_format(None)
+            .cull_mode(wgpu::CullMode::None)
+            .build(device);
+        let composite_pipeline = PipelineBuilder::new(&composite_pipeline_layout, &vs_module, &composite_module, output_format)
+            .label("Bloom Composite Pipeline")
+            .depth_format(None)
+            .cull_mode(wgpu::CullMode::None)
+            .build(device);
+
+        let create_uniform_buffer = |label: &str| {
+            device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents: bytemuck::cast_slice(&[BloomUniforms {
+                        threshold,
+                        intensity,
+                        direction: [0.0, 0.0],
+                    }]),
+                    usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                }
+            )
+        };
+        let params_buffer = create_uniform_buffer("Bloom Params Buffer");
+        let horizontal_buffer = create_uniform_buffer("Bloom Horizontal Buffer");
+        let vertical_buffer = create_uniform_buffer("Bloom Vertical Buffer");
+
+        let create_uniform_bind_group = |label: &str, buffer: &wgpu::Buffer| {
+            device.create_bind_group(
+                &wgpu::BindGroupDescriptor {
+                    label: Some(label),
+                    layout: &uniform_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: buffer.as_entire_binding(),
+                        },
+                    ],
+                }
+            )
+        };
+        let params_bind_group = create_uniform_bind_group("bloom_params_bind_group", &params_buffer);
+        let horizontal_bind_group = create_uniform_bind_group("bloom_horizontal_bind_group", &horizontal_buffer);
+        let vertical_bind_group = create_uniform_bind_group("bloom_vertical_bind_group", &vertical_buffer);
+
+        let target_size = Self::target_size(width, height);
+        let (bright_target, blur_target) = Self::create_targets(device, target_size);
+        let scene_bind_group = source_bind_group(device, &source_layout, scene);
+        let bright_bind_group = source_bind_group(device, &source_layout, &bright_target);
+        let blur_bind_group = source_bind_group(device, &source_layout, &blur_target);
+        let composite_bind_group = Self::create_composite_bind_group(device, &composite_layout, scene, &bright_target);
+
+        Self {
+            threshold,
+            intensity,
+            source_layout,
+            composite_layout,
+            bright_pipeline,
+            blur_pipeline,
+            composite_pipeline,
+            params_buffer,
+            horizontal_buffer,
+            vertical_buffer,
+            params_bind_group,
+            horizontal_bind_group,
+            vertical_bind_group,
+            target_size,
+            bright_target,
+            blur_target,
+            scene_bind_group,
+            bright_bind_group,
+            blur_bind_group,
+            composite_bind_group,
+        }
+    }
+
+    /// Recreate the intermediate targets after the scene texture was resized.
+    pub fn resize(&mut self, device: &wgpu::Device, scene: &Texture, width: u32, height: u32) {
+        self.target_size = Self::target_size(width, height);
+        let (bright_target, blur_target) = Self::create_targets(device, self.target_size);
+        self.scene_bind_group = source_bind_group(device, &self.source_layout, scene);
+        self.bright_bind_group = source_bind_group(device, &self.source_layout, &bright_target);
+        self.blur_bind_group = source_bind_group(device, &self.source_layout, &blur_target);
+        self.composite_bind_group = Self::create_composite_bind_group(device, &self.composite_layout, scene, &bright_target);
+        self.bright_target = bright_target;
+        self.blur_target = blur_target;
+    }
+
+    /// Record the bloom passes, reading the scene texture and writing the result to `output`.
+    pub fn render(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        let params = BloomUniforms {
+            threshold: self.threshold,
+            intensity: self.intensity,
+            direction: [0.0, 0.0],
+        };
+        let texel = (
+            1.0 / self.target_size.0 as f32,
+            1.0 / self.target_size.1 as f32,
+        );
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+        queue.write_buffer(&self.horizontal_buffer, 0, bytemuck::cast_slice(&[BloomUniforms {
+            direction: [texel.0, 0.0],
+            ..params
+        }]));
+        queue.write_buffer(&self.vertical_buffer, 0, bytemuck::cast_slice(&[BloomUniforms {
+            direction: [0.0, texel.1],
+            ..params
+        }]));
+
+        fullscreen_pass(encoder, "Bloom Bright Pass", &self.bright_target.view, &self.bright_pipeline, &[&self.scene_bind_group, &self.params_bind_group]);
+        fullscreen_pass(encoder, "Bloom Horizontal Blur Pass", &self.blur_target.view, &self.blur_pipeline, &[&self.bright_bind_group, &self.horizontal_bind_group]);
+        fullscreen_pass(encoder, "Bloom Vertical Blur Pass", &self.bright_target.view, &self.blur_pipeline, &[&self.blur_bind_group, &self.vertical_bind_group]);
+        fullscreen_pass(encoder, "Bloom Composite Pass", output, &self.composite_pipeline, &[&self.composite_bind_group, &self.params_bind_group]);
+    }
+
+    fn target_size(width: u32, height: u32) -> (u32, u32) {
+        ((width / 2).max(1), (height / 2).max(1))
+    }
+
+    fn create_targets(device: &wgpu::Device, (width, height): (u32, u32)) -> (Texture, Texture) {
+        (
+            Texture::create_render_target(device, width, height, BLOOM_FORMAT, "bloom_bright_target"),
+            Texture::create_render_target(device, width, height, BLOOM_FORMAT, "bloom_blur_target"),
+        )
+    }
+
+    fn create_composite_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, scene: &Texture, bloom: &Texture) -> wgpu::BindGroup {
+        device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("bloom_composite_bind_group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&scene.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&bloom.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&bloom.sampler),
+                    },
+                ],
+            }
+        )
+    }
+}
+
+/// Fast approximate anti-aliasing over a resolved, tonemapped color texture.
+///
+/// FXAA finds edges from the luma contrast of the final image, so it has to run
+/// last, after any MSAA resolve and after the colors have been brought into display
+/// range. The source is read through an sRGB view, the shader converts the
+/// returned linear color back to perceptual luma, so an sRGB target gives the
+/// intended result without any extra conversion pass.
+pub struct Fxaa {
+    pub edge_threshold: f32,
+    pub edge_threshold_min: f32,
+    pub subpixel_quality: f32,
+
+    source_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    source_size: (u32, u32),
+    source_bind_group: wgpu::BindGroup,
+}
+
+impl Fxaa {
+    pub fn new(
+        device: &wgpu::Device,
+        source: &Texture,
+        output_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let source_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("fxaa_source_layout"),
+                entries: &[texture_entry(0), sampler_entry(1)],
+            }
+        );
+        let uniform_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("fxaa_uniform_layout"),
+                entries: &[uniform_entry(0)],
+            }
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Fxaa Pipeline Layout"),
+                bind_group_layouts: &[&source_layout, &uniform_layout],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/fullscreen.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/fxaa.frag.spv"));
+
+        let pipeline = PipelineBuilder::new(&pipeline_layout, &vs_module, &fs_module, output_format)
+            .label("Fxaa Pipeline")
+            .depth_format(None)
+            .cull_mode(wgpu::CullMode::None)
+            .build(device);
+
+        let source_size = (width.max(1), height.max(1));
+        let uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Fxaa Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[Self::uniforms(
+                    source_size,
+                    FXAA_EDGE_THRESHOLD,
+                    FXAA_EDGE_THRESHOLD_MIN,
+                    FXAA_SUBPIXEL_QUALITY,
+                )]),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            }
+        );
+        let uniform_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("fxaa_uniform_bind_group"),
+                layout: &uniform_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            }
+        );
+
+        let source_bind_group = source_bind_group(device, &source_layout, source);
+
+        Self {
+            edge_threshold: FXAA_EDGE_THRESHOLD,
+            edge_threshold_min: FXAA_EDGE_THRESHOLD_MIN,
+            subpixel_quality: FXAA_SUBPIXEL_QUALITY,
+            source_layout,
+            pipeline,
+            uniform_buffer,
+            uniform_bind_group,
+            source_size,
+            source_bind_group,
+        }
+    }
+
+    /// Rebind the source texture after it was recreated at a new size.
+    pub fn resize(&mut self, device: &wgpu::Device, source: &Texture, width: u32, height: u32) {
+        self.source_size = (width.max(1), height.max(1));
+        self.source_bind_group = source_bind_group(device, &self.source_layout, source);
+    }
+
+    /// Record the FXAA pass, reading the source texture and writing the result to `output`.
+    pub fn render(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        let uniforms = Self::uniforms(
+            self.source_size,
+            self.edge_threshold,
+            self.edge_threshold_min,
+            self.subpixel_quality,
+        );
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+
+        fullscreen_pass(encoder, "Fxaa Pass", output, &self.pipeline, &[&self.source_bind_group, &self.uniform_bind_group]);
+    }
+
+    fn uniforms((width, height): (u32, u32), edge_threshold: f32, edge_threshold_min: f32, subpixel_quality: f32) -> FxaaUniforms {
+        FxaaUniforms {
+            texel_size: [1.0 / width as f32, 1.0 / height as f32],
+            edge_threshold,
+            edge_threshold_min,
+            subpixel_quality,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+/// Tonemapping from an `HDR_FORMAT` scene texture into display range.
+///
+/// This is the step between the HDR scene and everything expecting colors in
+/// 0..1, so it runs before bloom and FXAA. The output is linear, writing it to an
+/// sRGB target such as the swap chain does the gamma encoding.
+pub struct ToneMapPass {
+    pub tone_map: ToneMap,
+
+    source_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    source_bind_group: wgpu::BindGroup,
+}
+
+impl ToneMapPass {
+    pub fn new(device: &wgpu::Device, source: &Texture, output_format: wgpu::TextureFormat, tone_map: ToneMap) -> Self {
+        let source_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("tone_map_source_layout"),
+                entries: &[texture_entry(0), sampler_entry(1)],
+            }
+        );
+        let uniform_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("tone_map_uniform_layout"),
+                entries: &[uniform_entry(0)],
+            }
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Tone Map Pipeline Layout"),
+                bind_group_layouts: &[&source_layout, &uniform_layout],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/fullscreen.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/tonemap.frag.spv"));
+
+        let pipeline = PipelineBuilder::new(&pipeline_layout, &vs_module, &fs_module, output_format)
+            .label("Tone Map Pipeline")
+            .depth_format(None)
+            .cull_mode(wgpu::CullMode::None)
+            .build(device);
+
+        let uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Tone Map Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[Self::uniforms(&tone_map)]),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            }
+        );
+        let uniform_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("tone_map_uniform_bind_group"),
+                layout: &uniform_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            }
+        );
+
+        let source_bind_group = source_bind_group(device, &source_layout, source);
+
+        Self {
+            tone_map,
+            source_layout,
+            pipeline,
+            uniform_buffer,
+            uniform_bind_group,
+            source_bind_group,
+        }
+    }
+
+    /// Rebind the source texture after it was recreated at a new size.
+    pub fn resize(&mut self, device: &wgpu::Device, source: &Texture) {
+        self.source_bind_group = source_bind_group(device, &self.source_layout, source);
+    }
+
+    /// Record the tonemapping pass, reading the source texture and writing the result to `output`.
+    pub fn render(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[Self::uniforms(&self.tone_map)]));
+
+        fullscreen_pass(encoder, "Tone Map Pass", output, &self.pipeline, &[&self.source_bind_group, &self.uniform_bind_group]);
+    }
+
+    fn uniforms(tone_map: &ToneMap) -> ToneMapUniforms {
+        ToneMapUniforms {
+            operator: match tone_map.operator {
+                ToneMapOperator::Reinhard => 0,
+                ToneMapOperator::Aces => 1,
+            },
+            exposure: tone_map.exposure,
+            _padding: [0.0; 2],
+        }
+    }
+}
\ No newline at end of file
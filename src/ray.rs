@@ -0,0 +1,73 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::model::Aabb;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn at(&self, t: f32) -> Vector3<f32> {
+        self.origin + self.direction * t
+    }
+
+    /// Parameters of the closest points between the ray's line and the line through
+    /// `point` along unit `direction`, as (t on the ray, t on the line). `None` when
+    /// the lines are parallel.
+    pub fn closest_to_line(&self, point: Vector3<f32>, direction: Vector3<f32>) -> Option<(f32, f32)> {
+        let w = self.origin - point;
+        let b = self.direction.dot(direction);
+        let denom = 1.0 - b * b;
+        if denom.abs() < 1e-6 {
+            return None;
+        }
+
+        let d = self.direction.dot(w);
+        let e = direction.dot(w);
+        Some(((b * e - d) / denom, (e - b * d) / denom))
+    }
+
+    /// Shortest distance from the ray to `point`.
+    pub fn distance_to_point(&self, point: Vector3<f32>) -> f32 {
+        let t = (point - self.origin).dot(self.direction).max(0.0);
+        (self.at(t) - point).magnitude()
+    }
+
+    /// Distance along the ray to where it enters `aabb`, zero when it starts
+    /// inside. `None` when it misses or the box is behind it.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::INFINITY;
+        for axis in 0..3 {
+            // Parallel axes divide to +-infinity, which the comparisons handle,
+            // except for an origin exactly on a slab plane giving NaN.
+            let inverse = 1.0 / self.direction[axis];
+            let t0 = (aabb.min[axis] - self.origin[axis]) * inverse;
+            let t1 = (aabb.max[axis] - self.origin[axis]) * inverse;
+            let (near, far) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+            t_min = t_min.max(near);
+            t_max = t_max.min(far);
+        }
+        if t_min <= t_max { Some(t_min) } else { None }
+    }
+
+    /// Distance along the ray to the plane through `point` with `normal`. `None`
+    /// when it runs parallel to the plane or away from it.
+    pub fn intersect_plane(&self, point: Vector3<f32>, normal: Vector3<f32>) -> Option<f32> {
+        let denom = self.direction.dot(normal);
+        if denom.abs() < 1e-6 {
+            return None;
+        }
+        let t = (point - self.origin).dot(normal) / denom;
+        if t >= 0.0 { Some(t) } else { None }
+    }
+}
\ No newline at end of file
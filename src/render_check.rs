@@ -0,0 +1,263 @@
+pub mod gpu;
+
+use cgmath::{Decomposed, Deg, InnerSpace, Matrix4, Quaternion, Rotation3, Vector2, Vector3, Vector4};
+
+use crate::{camera::Camera, model::{GridUvMode, Mesh}};
+
+/// Width and height of the offscreen image, in pixels. Rows of 4 byte pixels stay
+/// a multiple of the 256 bytes buffer copies need.
+pub const RENDER_CHECK_SIZE: u32 = 128;
+/// Bits of the 256 bit hash allowed to differ, absorbing rasterization and
+/// precision differences between GPUs, drivers and the software renderer.
+pub const RENDER_HASH_TOLERANCE: u32 = 8;
+
+// Side of the grid of blocks whose brightness makes up the hash.
+const HASH_GRID: u32 = 16;
+
+/// Reference hashes of `reference_meshes`, by name. After a deliberate change to a
+/// generator, regenerate them with `print_reference_hashes`:
+///
+/// `cargo test print_reference_hashes -- --ignored --nocapture`
+///
+/// and paste its output here.
+pub const REFERENCE_HASHES: &[(&str, [u64; 4])] = &[
+    ("brick", [4445056955383283712, 4093838909170792230, 2071715529478126384, 432352161551097008]),
+    ("sphere", [252201579132747776, 2017643694473088704, 1089887740440354304, 768]),
+    ("geo_sphere", [216172782113783808, 2017643694473088704, 1089887740440354304, 768]),
+    ("grid", [0, 8759533710841217024, 864724183382242096, 3072]),
+    ("arrow", [216176080665444608, 72058693566333696, 72058693566333184, 16777472]),
+];
+
+/// The generator meshes the references cover.
+pub fn reference_meshes() -> Vec<(&'static str, Mesh)> {
+    vec![
+        ("brick", Mesh::brick(1.0, 1.0, 1.0, 0)),
+        ("sphere", Mesh::sphere(1.0, 32, 16)),
+        ("geo_sphere", Mesh::geo_sphere(1.0, 3)),
+        ("grid", Mesh::grid(2.0, 2.0, 8, 8, GridUvMode::Stretch)),
+        ("arrow", Mesh::arrow(0.05, 0.8, 0.12, 0.2, 16)),
+    ]
+}
+
+/// Render every reference mesh and print the `REFERENCE_HASHES` entries matching
+/// the current generators.
+pub fn print_reference_hashes() {
+    for (name, mesh) in reference_meshes() {
+        println!("    ({:?}, {:?}),", name, render_hash(&mesh));
+    }
+}
+
+/// Render every reference mesh and compare it against `REFERENCE_HASHES`.
+pub fn check_reference_hashes() {
+    for (name, mesh) in reference_meshes() {
+        match REFERENCE_HASHES.iter().find(|(reference_name, _)| *reference_name == name) {
+            Some((_, reference)) => assert_render_matches(&mesh, *reference),
+            None => panic!("no reference hash for {}, see print_reference_hashes", name),
+        }
+    }
+}
+
+/// Panic unless `mesh` renders to within `RENDER_HASH_TOLERANCE` bits of
+/// `reference_hash`.
+pub fn assert_render_matches(mesh: &Mesh, reference_hash: [u64; 4]) {
+    let hash = render_hash(mesh);
+    let distance = hash_distance(hash, reference_hash);
+    assert!(
+        distance <= RENDER_HASH_TOLERANCE,
+        "render differs from the reference in {} of 256 bits, got {:?}",
+        distance,
+        hash,
+    );
+}
+
+/// Bits differing between two hashes.
+pub fn hash_distance(a: [u64; 4], b: [u64; 4]) -> u32 {
+    a.iter().zip(b.iter()).map(|(a, b)| (a ^ b).count_ones()).sum()
+}
+
+/// `image_hash` of `mesh` drawn by the software renderer, the same on every
+/// machine, with or without a GPU.
+pub fn render_hash(mesh: &Mesh) -> [u64; 4] {
+    image_hash(&render(mesh, framing(mesh)))
+}
+
+/// Difference hash of an RGBA8 image `RENDER_CHECK_SIZE` pixels square: the image
+/// is split into 16 by 16 blocks and each bit is set when its block is brighter
+/// than the next one in its row, the last comparing with the first. Small
+/// rasterization differences barely move it, while changed normals, texture
+/// coordinates or triangles change the shading inside the mesh and with it
+/// several bits.
+pub fn image_hash(pixels: &[u8]) -> [u64; 4] {
+    let block = RENDER_CHECK_SIZE / HASH_GRID;
+    let mut brightness = vec![0.0f32; (HASH_GRID * HASH_GRID) as usize];
+    for y in 0..RENDER_CHECK_SIZE {
+        for x in 0..RENDER_CHECK_SIZE {
+            let i = ((y * RENDER_CHECK_SIZE + x) * 4) as usize;
+            let luma = 0.299 * pixels[i] as f32 + 0.587 * pixels[i + 1] as f32 + 0.114 * pixels[i + 2] as f32;
+            brightness[((y / block) * HASH_GRID + x / block) as usize] += luma;
+        }
+    }
+
+    let mut hash = [0u64; 4];
+    for (i, &b) in brightness.iter().enumerate() {
+        let row_start = i - i % HASH_GRID as usize;
+        let next = row_start + (i + 1 - row_start) % HASH_GRID as usize;
+        if b > brightness[next] {
+            hash[i / 64] |= 1u64 << (i % 64);
+        }
+    }
+    hash
+}
+
+/// View-projection framing `mesh` from above and to the side, as
+/// `State::show_primitive` does.
+pub fn framing(mesh: &Mesh) -> Matrix4<f32> {
+    let bounds = mesh.bounding_box();
+    let radius = 0.5 * bounds.size().magnitude();
+    let mut camera = Camera::new(1.0);
+    let half_fov = (1.0 / camera.get_proj().y.y).atan();
+    let rot = Quaternion::from_angle_y(Deg(30.0)) * Quaternion::from_angle_x(Deg(-25.0));
+    camera.set_transform(Decomposed {
+        scale: 1.0,
+        rot,
+        disp: bounds.center() + rot * Vector3::unit_z() * (radius / half_fov.sin()),
+    });
+    camera.get_view_proj()
+}
+
+/// Color of a fragment, as `render_check.frag` shades it: half the normal as a
+/// color, half a 4x4 checker over the texture coordinates.
+fn shade(normal: Vector3<f32>, tex_coord: Vector2<f32>) -> Vector3<f32> {
+    let normal_color = normal.normalize() * 0.5 + Vector3::new(0.5, 0.5, 0.5);
+    let cell = (tex_coord * 4.0).map(f32::floor);
+    // GLSL's mod, which stays positive for negative cells.
+    let sum = cell.x + cell.y;
+    let checker = sum - 2.0 * (sum / 2.0).floor();
+    normal_color * 0.5 + Vector3::new(checker, checker, checker) * 0.5
+}
+
+/// The RGBA8 pixels of `mesh` seen through `view_proj` on a black background,
+/// rasterized on the CPU like the GPU does with `render_check.vert` and
+/// `render_check.frag`: both faces drawn, a less-than depth test, pixel centers
+/// sampled and attributes interpolated perspective correctly. Triangles reaching
+/// behind the camera are left out rather than clipped, the framing keeps meshes
+/// in front of it.
+pub fn render(mesh: &Mesh, view_proj: Matrix4<f32>) -> Vec<u8> {
+    let size = RENDER_CHECK_SIZE as usize;
+    let mut color = vec![Vector3::new(0.0f32, 0.0, 0.0); size * size];
+    let mut depth = vec![1.0f32; size * size];
+
+    // Clip space positions, then window x and y in pixels with y down, depth and 1 / w.
+    let projected: Vec<Option<(Vector2<f32>, f32, f32)>> = mesh.vertices
+        .iter()
+        .map(|vertex| {
+            let p = vertex.position;
+            let clip = view_proj * Vector4::new(p.x, p.y, p.z, 1.0);
+            if clip.w <= 0.0 {
+                return None;
+            }
+            let inverse_w = 1.0 / clip.w;
+            let window = Vector2::new(
+                (clip.x * inverse_w * 0.5 + 0.5) * size as f32,
+                (0.5 - clip.y * inverse_w * 0.5) * size as f32,
+            );
+            Some((window, clip.z * inverse_w, inverse_w))
+        })
+        .collect();
+
+    for triangle in mesh.indices.chunks_exact(3) {
+        let corners = match (projected[triangle[0] as usize], projected[triangle[1] as usize], projected[triangle[2] as usize]) {
+            (Some(a), Some(b), Some(c)) => [a, b, c],
+            _ => continue,
+        };
+        let [(a, _, _), (b, _, _), (c, _, _)] = corners;
+        let area = (b - a).perp_dot(c - a);
+        if area == 0.0 {
+            continue;
+        }
+
+        let min_x = a.x.min(b.x).min(c.x).floor().max(0.0) as usize;
+        let max_x = (a.x.max(b.x).max(c.x).ceil() as usize).min(size);
+        let min_y = a.y.min(b.y).min(c.y).floor().max(0.0) as usize;
+        let max_y = (a.y.max(b.y).max(c.y).ceil() as usize).min(size);
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let center = Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+                // Barycentric weights, positive inside whichever way the triangle winds.
+                let weights = [
+                    (c - b).perp_dot(center - b) / area,
+                    (a - c).perp_dot(center - c) / area,
+                    (b - a).perp_dot(center - a) / area,
+                ];
+                if weights.iter().any(|&w| w < 0.0) {
+                    continue;
+                }
+
+                let z: f32 = weights.iter().zip(corners.iter()).map(|(w, corner)| w * corner.1).sum();
+                let i = y * size + x;
+                if !(0.0..=1.0).contains(&z) || z >= depth[i] {
+                    continue;
+                }
+
+                // Perspective correct weights, by 1 / w.
+                let perspective: Vec<f32> = weights.iter().zip(corners.iter()).map(|(w, corner)| w * corner.2).collect();
+                let total: f32 = perspective.iter().sum();
+                let mut normal = Vector3::new(0.0, 0.0, 0.0);
+                let mut tex_coord = Vector2::new(0.0, 0.0);
+                for (w, &index) in perspective.iter().zip(triangle) {
+                    let vertex = &mesh.vertices[index as usize];
+                    normal += vertex.normal * (w / total);
+                    tex_coord += vertex.tex_coord * (w / total);
+                }
+                if normal.magnitude2() == 0.0 {
+                    continue;
+                }
+
+                depth[i] = z;
+                color[i] = shade(normal, tex_coord);
+            }
+        }
+    }
+
+    let mut pixels = Vec::with_capacity(size * size * 4);
+    for c in color {
+        for &channel in &[c.x, c.y, c.z, 1.0] {
+            pixels.push((channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generators_match_reference_hashes() {
+        check_reference_hashes();
+    }
+
+    // Not a check, run on its own to regenerate `REFERENCE_HASHES`.
+    #[test]
+    #[ignore]
+    fn print_reference_hashes() {
+        super::print_reference_hashes();
+    }
+
+    #[test]
+    fn changed_attributes_fail_the_check() {
+        let reference = render_hash(&Mesh::sphere(1.0, 32, 16));
+
+        let mut shifted = Mesh::sphere(1.0, 32, 16);
+        for vertex in &mut shifted.vertices {
+            vertex.tex_coord.x += 0.125;
+        }
+        assert!(hash_distance(reference, render_hash(&shifted)) > RENDER_HASH_TOLERANCE);
+
+        let mut flipped = Mesh::sphere(1.0, 32, 16);
+        for vertex in &mut flipped.vertices {
+            vertex.normal = -vertex.normal;
+        }
+        assert!(hash_distance(reference, render_hash(&flipped)) > RENDER_HASH_TOLERANCE);
+    }
+}
\ No newline at end of file
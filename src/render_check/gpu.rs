@@ -0,0 +1,284 @@
+use cgmath::Matrix4;
+use futures::executor::block_on;
+use wgpu::util::DeviceExt;
+
+use crate::{model::{AsVertexPrimitive, Vertex}, pipeline::PipelineBuilder, render_item::{DrawRenderItem, RenderItem}, texture::Texture};
+
+use super::RENDER_CHECK_SIZE;
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// A device on an adapter of its own, without a window, or `None` on machines
+/// without one, where the GPU checks are skipped.
+pub fn headless_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+    let adapter = block_on(instance.request_adapter(
+        &wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+        },
+    ))?;
+    block_on(adapter.request_device(&Default::default(), None)).ok()
+}
+
+/// Draws `RenderItem`s offscreen with `render_check.vert` and `render_check.frag`,
+/// the GPU counterpart of `render_check::render`.
+pub struct Renderer {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    // What the items drawn must be built with.
+    pub object_layout: wgpu::BindGroupLayout,
+    // Set 0 holds the forward pass's diffuse texture, unused here.
+    empty_bind_group: wgpu::BindGroup,
+    uniform_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    // `depth_prepass.vert` laying down the depth, then `pipeline` testing for
+    // equal depth without writing it, as `Application::with_depth_prepass` draws.
+    depth_pipeline: wgpu::RenderPipeline,
+    equal_pipeline: wgpu::RenderPipeline,
+}
+
+impl Renderer {
+    /// `None` without an adapter, see `headless_device`.
+    pub fn new() -> Option<Self> {
+        let (device, queue) = headless_device()?;
+
+        let empty_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("render_check_empty_layout"),
+                entries: &[],
+            }
+        );
+        let empty_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("render_check_empty_bind_group"),
+                layout: &empty_layout,
+                entries: &[],
+            }
+        );
+        let uniform_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("render_check_uniform_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            }
+        );
+        let object_layout = RenderItem::create_object_layout(&device);
+
+        let pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Check Pipeline Layout"),
+                bind_group_layouts: &[&empty_layout, &uniform_layout, &object_layout],
+                push_constant_ranges: &[],
+            }
+        );
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("../resources/shaders/render_check.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("../resources/shaders/render_check.frag.spv"));
+        let prepass_vs_module = device.create_shader_module(&wgpu::include_spirv!("../resources/shaders/depth_prepass.vert.spv"));
+        let prepass_fs_module = device.create_shader_module(&wgpu::include_spirv!("../resources/shaders/depth_prepass.frag.spv"));
+
+        // Drawn from both sides, so wrong windings show up as wrong colors rather than holes.
+        let pipeline = PipelineBuilder::new(&pipeline_layout, &vs_module, &fs_module, FORMAT)
+            .label("Render Check Pipeline")
+            .vertex_layout(Vertex::desc())
+            .cull_mode(wgpu::CullMode::None)
+            .build(&device);
+        let depth_pipeline = PipelineBuilder::new(&pipeline_layout, &prepass_vs_module, &prepass_fs_module, FORMAT)
+            .label("Render Check Depth Prepass Pipeline")
+            .vertex_layout(Vertex::position_desc())
+            .color_write_mask(wgpu::ColorWrite::empty())
+            .cull_mode(wgpu::CullMode::None)
+            .build(&device);
+        let equal_pipeline = PipelineBuilder::new(&pipeline_layout, &vs_module, &fs_module, FORMAT)
+            .label("Render Check Equal Depth Pipeline")
+            .vertex_layout(Vertex::desc())
+            .cull_mode(wgpu::CullMode::None)
+            .depth_compare(wgpu::CompareFunction::Equal)
+            .depth_write(false)
+            .build(&device);
+
+        Some(Self {
+            device,
+            queue,
+            object_layout,
+            empty_bind_group,
+            uniform_layout,
+            pipeline,
+            depth_pipeline,
+            equal_pipeline,
+        })
+    }
+
+    /// The RGBA8 pixels of `item` seen through `view_proj` on a black background,
+    /// after a depth prepass when `depth_prepass` is set.
+    pub fn render(&self, item: &RenderItem, view_proj: Matrix4<f32>, depth_prepass: bool) -> Vec<u8> {
+        let device = &self.device;
+
+        // Only the size is used, to match the depth texture to the target.
+        let target_desc = wgpu::SwapChainDescriptor {
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+            format: FORMAT,
+            width: RENDER_CHECK_SIZE,
+            height: RENDER_CHECK_SIZE,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        let target = Texture::create_render_target(device, RENDER_CHECK_SIZE, RENDER_CHECK_SIZE, FORMAT, "render_check_target");
+        let depth_texture = Texture::create_depth_texture(device, &target_desc, 1, "render_check_depth");
+
+        let view_proj: [[f32; 4]; 4] = view_proj.into();
+        let uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Render Check Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[view_proj]),
+                usage: wgpu::BufferUsage::UNIFORM,
+            }
+        );
+        let uniform_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("render_check_uniform_bind_group"),
+                layout: &self.uniform_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            }
+        );
+
+        let bytes_per_row = RENDER_CHECK_SIZE * 4;
+        let readback_buffer = device.create_buffer(
+            &wgpu::BufferDescriptor {
+                label: Some("Render Check Readback Buffer"),
+                size: (bytes_per_row * RENDER_CHECK_SIZE) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+                mapped_at_creation: false,
+            }
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Check Encoder"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Check Pass"),
+                color_attachments: &[
+                    wgpu::RenderPassColorAttachmentDescriptor {
+                        attachment: &target.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: true,
+                        },
+                    }
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                    attachment: &depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+
+            render_pass.set_bind_group(0, &self.empty_bind_group, &[]);
+            render_pass.set_bind_group(1, &uniform_bind_group, &[]);
+            if depth_prepass {
+                render_pass.set_pipeline(&self.depth_pipeline);
+                render_pass.draw_item(item);
+                render_pass.set_pipeline(&self.equal_pipeline);
+            } else {
+                render_pass.set_pipeline(&self.pipeline);
+            }
+            render_pass.draw_item(item);
+        }
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture: &target.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::BufferCopyView {
+                buffer: &readback_buffer,
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row,
+                    rows_per_image: RENDER_CHECK_SIZE,
+                },
+            },
+            wgpu::Extent3d {
+                width: RENDER_CHECK_SIZE,
+                height: RENDER_CHECK_SIZE,
+                depth: 1,
+            },
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = readback_buffer.slice(..);
+        let mapped = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        block_on(mapped).expect("couldn't read the render back");
+        let pixels = slice.get_mapped_range().to_vec();
+        readback_buffer.unmap();
+        pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_check::{framing, hash_distance, image_hash, reference_meshes, render_hash, RENDER_HASH_TOLERANCE};
+
+    #[test]
+    fn gpu_renders_match_the_software_renderer() {
+        let renderer = match Renderer::new() {
+            Some(renderer) => renderer,
+            None => return eprintln!("no adapter, skipping the GPU render check"),
+        };
+        for (name, mesh) in reference_meshes() {
+            let item = RenderItem::from_mesh(&renderer.device, &renderer.object_layout, &mesh, name);
+            let pixels = renderer.render(&item, framing(&mesh), false);
+            let distance = hash_distance(image_hash(&pixels), render_hash(&mesh));
+            assert!(distance <= RENDER_HASH_TOLERANCE, "{} differs from the software render in {} bits", name, distance);
+        }
+    }
+
+    #[test]
+    fn u16_and_u32_indices_render_the_same() {
+        let renderer = match Renderer::new() {
+            Some(renderer) => renderer,
+            None => return eprintln!("no adapter, skipping the GPU render check"),
+        };
+        for (name, mesh) in reference_meshes() {
+            let short = RenderItem::from_mesh_with_index::<u16>(&renderer.device, &renderer.object_layout, &mesh, name);
+            let long = RenderItem::from_mesh_with_index::<u32>(&renderer.device, &renderer.object_layout, &mesh, name);
+            let view_proj = framing(&mesh);
+            assert!(renderer.render(&short, view_proj, false) == renderer.render(&long, view_proj, false), "{} renders differently with u16 indices", name);
+        }
+    }
+
+    #[test]
+    fn depth_prepass_renders_the_same() {
+        let renderer = match Renderer::new() {
+            Some(renderer) => renderer,
+            None => return eprintln!("no adapter, skipping the GPU render check"),
+        };
+        for (name, mesh) in reference_meshes() {
+            let item = RenderItem::from_mesh(&renderer.device, &renderer.object_layout, &mesh, name);
+            let view_proj = framing(&mesh);
+            // Any fragment failing the equal test leaves black, or a farther face, behind.
+            assert!(renderer.render(&item, view_proj, false) == renderer.render(&item, view_proj, true), "{} renders differently after the depth prepass", name);
+        }
+    }
+}
\ No newline at end of file
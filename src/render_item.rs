@@ -0,0 +1,808 @@
+use std::mem::size_of_val;
+use std::ops::Range;
+use std::rc::Rc;
+
+use cgmath::{Decomposed, Matrix4, One, Quaternion, Vector3};
+use wgpu::util::DeviceExt;
+
+use crate::{gpu_memory::Allocation, model::{AsVertexPrimitive, Mesh, Vertex}};
+
+/// Size of each staging buffer used by `RenderItem::from_mesh_staged`. wgpu 0.7
+/// doesn't report a maximum buffer or mapping size in `Limits`, so this is kept at
+/// a size every backend accepts, well under the 256 MiB a Vulkan driver must allow.
+pub const STAGING_CHUNK_SIZE: wgpu::BufferAddress = 16 << 20;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct ObjectUniforms {
+    model: [[f32; 4]; 4],
+    // Nonzero to light back faces with the normal flipped, see `RenderItem::double_sided`.
+    double_sided: u32,
+    _padding: [u32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for ObjectUniforms {}
+unsafe impl bytemuck::Pod for ObjectUniforms {}
+
+/// Panic unless the vertices uploaded as `Vertex` match the stride pipelines read
+/// them with, catching a field added to `Vertex` without `Vertex::desc` following
+/// before it draws garbage.
+fn check_vertex_stride() {
+    let stride = Vertex::desc().array_stride;
+    assert_eq!(
+        std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+        stride,
+        "Vertex doesn't match the stride of Vertex::desc",
+    );
+}
+
+/// Per-instance data read from a storage buffer, see `RenderItem::with_instance_storage`.
+/// Matches `struct Instance` in `instanced.vert`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Instance {
+    pub model: [[f32; 4]; 4],
+}
+
+unsafe impl bytemuck::Zeroable for Instance {}
+unsafe impl bytemuck::Pod for Instance {}
+
+impl Instance {
+    pub fn new(transform: &Decomposed<Vector3<f32>, Quaternion<f32>>) -> Self {
+        let model: Matrix4<f32> = (*transform).into();
+        Self {
+            model: model.into(),
+        }
+    }
+}
+
+/// Per-instance tint read as an instance-stepped vertex attribute, see
+/// `RenderItem::with_instance_colors`. Kept apart from `Instance` so items
+/// without colors don't carry them in their storage buffer.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct InstanceColor {
+    pub color: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for InstanceColor {}
+unsafe impl bytemuck::Pod for InstanceColor {}
+
+impl InstanceColor {
+    /// Shader location of the color in `instanced_colored.vert`, after the ones of `Vertex`.
+    pub const SHADER_LOCATION: u32 = 4;
+
+    /// The layout to add after the item's vertex layouts, the colors are bound
+    /// to the slot after its vertex buffers.
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceColor>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: Self::SHADER_LOCATION,
+                    format: wgpu::VertexFormat::Float4,
+                },
+            ]
+        }
+    }
+}
+
+/// An integer type index buffers can hold, choosing the `IndexFormat` for
+/// `RenderItem::from_mesh_with_index`.
+pub trait IndexInt: bytemuck::Pod {
+    const FORMAT: wgpu::IndexFormat;
+
+    /// `None` when `index` doesn't fit.
+    fn from_u32(index: u32) -> Option<Self>;
+}
+
+impl IndexInt for u16 {
+    const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint16;
+
+    fn from_u32(index: u32) -> Option<Self> {
+        use std::convert::TryFrom;
+        u16::try_from(index).ok()
+    }
+}
+
+impl IndexInt for u32 {
+    const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint32;
+
+    fn from_u32(index: u32) -> Option<Self> {
+        Some(index)
+    }
+}
+
+/// How a `RenderItem`'s vertices are assembled into primitives.
+pub enum IndexData {
+    /// Drawn with `draw_indexed` from `count` indices of the buffer.
+    Indexed(wgpu::Buffer, wgpu::IndexFormat, u32),
+    /// Drawn with `draw`, the vertices taken in order, e.g. for point clouds.
+    NonIndexed(u32),
+}
+
+/// The vertex and index buffers of a mesh on the GPU, shared by every item made
+/// from them with `RenderItem::from_buffers`, see `MeshCache`.
+pub struct MeshBuffers {
+    // Bound to consecutive slots from 0, a single interleaved buffer unless built
+    // by `from_mesh_deinterleaved`.
+    pub vertex_buffers: Vec<wgpu::Buffer>,
+    pub index_data: IndexData,
+
+    // Only held to count the buffers in `GpuReport` while they're alive.
+    _allocation: Allocation,
+}
+
+pub struct RenderItem {
+    // Shared with the other items drawing the same mesh, see `from_buffers`.
+    pub buffers: Rc<MeshBuffers>,
+    // What the indices, or the vertices in order, make up, set by `with_topology`.
+    // Pipelines drawing the item have to be built with the same
+    // `PipelineBuilder::topology`.
+    pub topology: wgpu::PrimitiveTopology,
+
+    // Placement in the world, uploaded by `write_transform`.
+    pub transform: Decomposed<Vector3<f32>, Quaternion<f32>>,
+    // Draw back faces too, lit from their side, for thin geometry like leaves or
+    // a grid seen from below. Uploaded by `write_transform`, set by `with_double_sided`.
+    pub double_sided: bool,
+    // Name of the `Application::with_pipeline` vertex shader to draw with in the
+    // lit view, `None` for the default one. Set by `with_pipeline`.
+    pub pipeline: Option<String>,
+    // Bound at set 0 in place of the scene's diffuse texture, set by `with_material`.
+    pub material: Option<Rc<wgpu::BindGroup>>,
+    object_buffer: wgpu::Buffer,
+    object_bind_group: wgpu::BindGroup,
+
+    // Set by `with_instance_storage`, `draw_item` draws this many instances.
+    instance_buffer: Option<wgpu::Buffer>,
+    instance_count: u32,
+    // Set by `with_instance_colors`, bound after the vertex buffers.
+    instance_color_buffer: Option<wgpu::Buffer>,
+
+    // Only held to count the buffers in `GpuReport` while the item is alive.
+    allocation: Allocation,
+}
+
+impl RenderItem {
+    /// Bytes `draw_item_pushed` pushes, the object uniform's size. Within the 128
+    /// every device offering push constants allows.
+    pub const PUSH_CONSTANT_SIZE: u32 = std::mem::size_of::<ObjectUniforms>() as u32;
+
+    /// The push constant range of pipelines drawing with `draw_item_pushed`.
+    pub fn push_constant_range() -> wgpu::PushConstantRange {
+        wgpu::PushConstantRange {
+            stages: wgpu::ShaderStage::VERTEX,
+            range: 0..Self::PUSH_CONSTANT_SIZE,
+        }
+    }
+
+    /// Layout of the per-object uniform, the model matrix and double-sided flag for
+    /// the vertex stage. Pipelines drawing render items need it at bind group 2,
+    /// unless they take the same data from push constants, see `draw_item_pushed`.
+    pub fn create_object_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("object_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            }
+        )
+    }
+
+    /// Layout of the per-object uniform plus the instance storage buffer, for items
+    /// built `with_instance_storage`, at bind group 2 in place of `create_object_layout`:
+    ///
+    /// | Binding | Stage  | Resource                                       |
+    /// |---------|--------|------------------------------------------------|
+    /// | 0       | vertex | uniform, the item's model matrix               |
+    /// | 1       | vertex | read-only storage buffer, `Instance` per draw  |
+    ///
+    /// wgpu 0.7 lets vertex shaders read storage buffers without any feature, only
+    /// writing them from the vertex stage would need one. Up to
+    /// `Limits::max_storage_buffers_per_shader_stage`, 4 by default, fit per stage.
+    pub fn create_instanced_object_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("instanced_object_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStage::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            }
+        )
+    }
+
+    pub fn from_mesh(device: &wgpu::Device, object_layout: &wgpu::BindGroupLayout, mesh: &Mesh, label: &str) -> Self {
+        Self::new(device, object_layout, &mesh.vertices, &mesh.indices, label)
+    }
+
+    /// Like `from_mesh`, but with the indices stored as `I`, e.g. `u16` to halve the
+    /// index buffer of a mesh with at most 65536 vertices. Meant for list
+    /// topologies, `PipelineBuilder` sets up strips for `u32` restart indices.
+    ///
+    /// Panics if an index doesn't fit in `I`.
+    pub fn from_mesh_with_index<I: IndexInt>(
+        device: &wgpu::Device,
+        object_layout: &wgpu::BindGroupLayout,
+        mesh: &Mesh,
+        label: &str,
+    ) -> Self {
+        check_vertex_stride();
+        let indices: Vec<I> = mesh.indices
+            .iter()
+            .map(|&index| I::from_u32(index).unwrap_or_else(|| panic!("{}: index {} doesn't fit the index type", label, index)))
+            .collect();
+
+        let vertex_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Vertex Buffer", label)),
+                contents: bytemuck::cast_slice(&mesh.vertices),
+                usage: wgpu::BufferUsage::VERTEX,
+            }
+        );
+
+        // create_buffer_init pads the size up to COPY_BUFFER_ALIGNMENT, so odd u16 counts are fine.
+        let index_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Index Buffer", label)),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsage::INDEX,
+            }
+        );
+
+        let index_data = IndexData::Indexed(index_buffer, I::FORMAT, indices.len() as u32);
+        let bytes = size_of_val(&mesh.vertices[..]) + size_of_val(&indices[..]);
+        Self::with_buffers(device, object_layout, vec![vertex_buffer], index_data, bytes, label)
+    }
+
+    /// Like `from_mesh`, but with a separate vertex buffer per attribute, see
+    /// `Vertex::deinterleaved_desc` for the slots and the matching layouts.
+    pub fn from_mesh_deinterleaved(device: &wgpu::Device, object_layout: &wgpu::BindGroupLayout, mesh: &Mesh, label: &str) -> Self {
+        let create_vertex_buffer = |name: &str, contents: &[u8]| {
+            device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} {} Buffer", label, name)),
+                    contents,
+                    usage: wgpu::BufferUsage::VERTEX,
+                }
+            )
+        };
+
+        let positions: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| v.position.into()).collect();
+        let normals: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| v.normal.into()).collect();
+        let tangents: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| v.tangent.into()).collect();
+        let tex_coords: Vec<[f32; 2]> = mesh.vertices.iter().map(|v| v.tex_coord.into()).collect();
+        let vertex_buffers = vec![
+            create_vertex_buffer("Position", bytemuck::cast_slice(&positions)),
+            create_vertex_buffer("Normal", bytemuck::cast_slice(&normals)),
+            create_vertex_buffer("Tangent", bytemuck::cast_slice(&tangents)),
+            create_vertex_buffer("Tex Coord", bytemuck::cast_slice(&tex_coords)),
+        ];
+
+        let index_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Index Buffer", label)),
+                contents: bytemuck::cast_slice(&mesh.indices),
+                usage: wgpu::BufferUsage::INDEX,
+            }
+        );
+
+        let index_data = IndexData::Indexed(index_buffer, wgpu::IndexFormat::Uint32, mesh.indices.len() as u32);
+        let bytes = size_of_val(&positions[..])
+            + size_of_val(&normals[..])
+            + size_of_val(&tangents[..])
+            + size_of_val(&tex_coords[..])
+            + size_of_val(&mesh.indices[..]);
+        Self::with_buffers(device, object_layout, vertex_buffers, index_data, bytes, label)
+    }
+
+    /// Like `from_mesh`, but uploads through fixed size staging buffers instead of
+    /// one `create_buffer_init` sized to the whole mesh, for meshes too large to be
+    /// staged at once. Each chunk is submitted and waited on before the next one is
+    /// staged, so at most `STAGING_CHUNK_SIZE` bytes of staging memory are alive.
+    pub fn from_mesh_staged(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        object_layout: &wgpu::BindGroupLayout,
+        mesh: &Mesh,
+        label: &str,
+    ) -> Self {
+        check_vertex_stride();
+        let vertex_buffer = Self::create_buffer_staged(
+            device,
+            queue,
+            bytemuck::cast_slice(&mesh.vertices),
+            wgpu::BufferUsage::VERTEX,
+            &format!("{} Vertex Buffer", label),
+        );
+        let index_buffer = Self::create_buffer_staged(
+            device,
+            queue,
+            bytemuck::cast_slice(&mesh.indices),
+            wgpu::BufferUsage::INDEX,
+            &format!("{} Index Buffer", label),
+        );
+
+        let index_data = IndexData::Indexed(index_buffer, wgpu::IndexFormat::Uint32, mesh.indices.len() as u32);
+        let bytes = size_of_val(&mesh.vertices[..]) + size_of_val(&mesh.indices[..]);
+        Self::with_buffers(device, object_layout, vec![vertex_buffer], index_data, bytes, label)
+    }
+
+    fn create_buffer_staged(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        contents: &[u8],
+        usage: wgpu::BufferUsage,
+        label: &str,
+    ) -> wgpu::Buffer {
+        // Copies have to be a multiple of COPY_BUFFER_ALIGNMENT, vertices and u32 indices already are.
+        assert_eq!(contents.len() as wgpu::BufferAddress % wgpu::COPY_BUFFER_ALIGNMENT, 0);
+
+        let buffer = device.create_buffer(
+            &wgpu::BufferDescriptor {
+                label: Some(label),
+                size: contents.len() as wgpu::BufferAddress,
+                usage: usage | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            }
+        );
+
+        for (i, chunk) in contents.chunks(STAGING_CHUNK_SIZE as usize).enumerate() {
+            let staging_buffer = device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} Staging Buffer", label)),
+                    contents: chunk,
+                    usage: wgpu::BufferUsage::COPY_SRC,
+                }
+            );
+
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Staging Encoder"),
+            });
+            encoder.copy_buffer_to_buffer(
+                &staging_buffer,
+                0,
+                &buffer,
+                i as wgpu::BufferAddress * STAGING_CHUNK_SIZE,
+                chunk.len() as wgpu::BufferAddress,
+            );
+            queue.submit(std::iter::once(encoder.finish()));
+            device.poll(wgpu::Maintain::Wait);
+        }
+
+        buffer
+    }
+
+    /// Build from explicit index data, e.g. the strips from `Mesh::to_triangle_strip`.
+    pub fn new(
+        device: &wgpu::Device,
+        object_layout: &wgpu::BindGroupLayout,
+        vertices: &[Vertex],
+        indices: &[u32],
+        label: &str,
+    ) -> Self {
+        check_vertex_stride();
+        let vertex_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Vertex Buffer", label)),
+                contents: bytemuck::cast_slice(vertices),
+                usage: wgpu::BufferUsage::VERTEX,
+            }
+        );
+
+        let index_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Index Buffer", label)),
+                contents: bytemuck::cast_slice(indices),
+                usage: wgpu::BufferUsage::INDEX,
+            }
+        );
+
+        let index_data = IndexData::Indexed(index_buffer, wgpu::IndexFormat::Uint32, indices.len() as u32);
+        let bytes = size_of_val(vertices) + size_of_val(indices);
+        Self::with_buffers(device, object_layout, vec![vertex_buffer], index_data, bytes, label)
+    }
+
+    /// Line segments between each pair of `indices`, e.g. grid edges, to draw with
+    /// a `LineList` pipeline.
+    pub fn line_list(
+        device: &wgpu::Device,
+        object_layout: &wgpu::BindGroupLayout,
+        vertices: &[Vertex],
+        indices: &[u32],
+        label: &str,
+    ) -> Self {
+        Self::new(device, object_layout, vertices, indices, label).with_topology(wgpu::PrimitiveTopology::LineList)
+    }
+
+    /// A point per vertex, e.g. a point cloud, to draw with a `PointList` pipeline.
+    pub fn point_list(
+        device: &wgpu::Device,
+        object_layout: &wgpu::BindGroupLayout,
+        vertices: &[Vertex],
+        label: &str,
+    ) -> Self {
+        Self::non_indexed(device, object_layout, vertices, label).with_topology(wgpu::PrimitiveTopology::PointList)
+    }
+
+    /// Build without an index buffer, drawing `vertices` in order with the
+    /// pipeline's topology.
+    pub fn non_indexed(
+        device: &wgpu::Device,
+        object_layout: &wgpu::BindGroupLayout,
+        vertices: &[Vertex],
+        label: &str,
+    ) -> Self {
+        check_vertex_stride();
+        let vertex_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Vertex Buffer", label)),
+                contents: bytemuck::cast_slice(vertices),
+                usage: wgpu::BufferUsage::VERTEX,
+            }
+        );
+
+        let index_data = IndexData::NonIndexed(vertices.len() as u32);
+        Self::with_buffers(device, object_layout, vec![vertex_buffer], index_data, size_of_val(vertices), label)
+    }
+
+    /// `bytes` is the size of the vertex buffers and index buffer together.
+    fn with_buffers(
+        device: &wgpu::Device,
+        object_layout: &wgpu::BindGroupLayout,
+        vertex_buffers: Vec<wgpu::Buffer>,
+        index_data: IndexData,
+        bytes: usize,
+        label: &str,
+    ) -> Self {
+        let index_buffers = match index_data {
+            IndexData::Indexed(..) => 1,
+            IndexData::NonIndexed(_) => 0,
+        };
+        let allocation = Allocation::buffers((vertex_buffers.len() + index_buffers) as u64, bytes as u64);
+        let buffers = MeshBuffers {
+            vertex_buffers,
+            index_data,
+            _allocation: allocation,
+        };
+        Self::from_buffers(device, object_layout, Rc::new(buffers), label)
+    }
+
+    /// Another item drawing `buffers`, e.g. those of an item already uploaded,
+    /// placed by a transform and object uniform of its own. Nothing is copied,
+    /// the buffers live as long as any item drawing them.
+    pub fn from_buffers(
+        device: &wgpu::Device,
+        object_layout: &wgpu::BindGroupLayout,
+        buffers: Rc<MeshBuffers>,
+        label: &str,
+    ) -> Self {
+        let transform = Decomposed::one();
+        let object_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Object Buffer", label)),
+                contents: bytemuck::cast_slice(&[Self::object_uniforms(&transform, false)]),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            }
+        );
+
+        let object_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some(&format!("{} Object Bind Group", label)),
+                layout: object_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: object_buffer.as_entire_binding(),
+                    },
+                ],
+            }
+        );
+
+        let allocation = Allocation::buffers(1, std::mem::size_of::<ObjectUniforms>() as u64);
+
+        Self {
+            buffers,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            transform,
+            double_sided: false,
+            pipeline: None,
+            material: None,
+            object_buffer,
+            object_bind_group,
+            instance_buffer: None,
+            instance_count: 1,
+            instance_color_buffer: None,
+            allocation,
+        }
+    }
+
+    /// Take the item's indices, or its vertices in order when it has none, as
+    /// `topology` instead of a triangle list, e.g. `TriangleStrip` for the output
+    /// of `Mesh::to_triangle_strip`.
+    ///
+    /// Panics if the count doesn't make whole primitives, like an odd count for a
+    /// line list, or if strip indices aren't `u32`, the restart format
+    /// `PipelineBuilder` sets up for strips.
+    pub fn with_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        let count = match &self.buffers.index_data {
+            IndexData::Indexed(_, format, count) => {
+                let strip = match topology {
+                    wgpu::PrimitiveTopology::LineStrip | wgpu::PrimitiveTopology::TriangleStrip => true,
+                    _ => false,
+                };
+                assert!(!strip || *format == wgpu::IndexFormat::Uint32, "strip indices must be u32");
+                *count
+            }
+            IndexData::NonIndexed(count) => *count,
+        };
+        // Strips may hold restart indices, so only rule out the lone leftovers.
+        let whole = match topology {
+            wgpu::PrimitiveTopology::PointList => true,
+            wgpu::PrimitiveTopology::LineList => count % 2 == 0,
+            wgpu::PrimitiveTopology::LineStrip => count != 1,
+            wgpu::PrimitiveTopology::TriangleList => count % 3 == 0,
+            wgpu::PrimitiveTopology::TriangleStrip => count == 0 || count >= 3,
+        };
+        assert!(whole, "{} indices or vertices don't make whole {:?} primitives", count, topology);
+
+        self.topology = topology;
+        self
+    }
+
+    /// Draw the back faces as well, with their normals flipped to face the camera
+    /// so they aren't lit from behind. Only the lit forward pipeline honors it,
+    /// the debug views and the deferred path still cull back faces. Takes effect
+    /// on the next `write_transform`.
+    pub fn with_double_sided(mut self, double_sided: bool) -> Self {
+        self.double_sided = double_sided;
+        self
+    }
+
+    /// Draw the item in the lit view with the pipeline registered as `name` by
+    /// `Application::with_pipeline` instead of the default one.
+    pub fn with_pipeline(mut self, name: &str) -> Self {
+        self.pipeline = Some(name.to_string());
+        self
+    }
+
+    /// Shade the item with `bind_group`, built with the scene's texture layout,
+    /// instead of the diffuse texture. Items sharing one are drawn together, and
+    /// instancing leaves them out.
+    pub fn with_material(mut self, bind_group: Rc<wgpu::BindGroup>) -> Self {
+        self.material = Some(bind_group);
+        self
+    }
+
+    /// Draw the item once per element of `instances`, the vertex shader reading
+    /// them from a storage buffer by instance index rather than from a per-instance
+    /// vertex buffer, as `instanced.vert` does with `instances[gl_InstanceIndex].model`.
+    /// Each instance's matrix is applied before the item's `transform`.
+    ///
+    /// The object bind group is rebuilt for `instanced_layout`, from
+    /// `create_instanced_object_layout`, so the item must be drawn with a pipeline
+    /// using that layout at bind group 2.
+    pub fn with_instance_storage(
+        mut self,
+        device: &wgpu::Device,
+        instanced_layout: &wgpu::BindGroupLayout,
+        instances: &[Instance],
+    ) -> Self {
+        // A zero sized binding is invalid.
+        assert!(!instances.is_empty(), "instance storage needs at least one instance");
+
+        let instance_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Instance Storage Buffer"),
+                contents: bytemuck::cast_slice(instances),
+                usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+            }
+        );
+
+        self.object_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("Instanced Object Bind Group"),
+                layout: instanced_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.object_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: instance_buffer.as_entire_binding(),
+                    },
+                ],
+            }
+        );
+        self.instance_buffer = Some(instance_buffer);
+        self.instance_count = instances.len() as u32;
+        self.allocation.add_buffers(1, size_of_val(instances) as u64);
+        self
+    }
+
+    /// Tint each instance by its element of `colors`, one per instance the item
+    /// draws, e.g. after `with_instance_storage`. The colors are bound as an
+    /// instance-stepped vertex buffer after the item's own, so the pipeline needs
+    /// `InstanceColor::desc` as its last vertex layout, as `instanced_colored.vert`
+    /// expects.
+    pub fn with_instance_colors(mut self, device: &wgpu::Device, colors: &[InstanceColor]) -> Self {
+        assert_eq!(colors.len() as u32, self.instance_count, "need one color per instance");
+
+        let instance_color_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Instance Color Buffer"),
+                contents: bytemuck::cast_slice(colors),
+                usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+            }
+        );
+        self.instance_color_buffer = Some(instance_color_buffer);
+        self.allocation.add_buffers(1, size_of_val(colors) as u64);
+        self
+    }
+
+    /// Overwrite the instance colors from the first one on.
+    pub fn write_instance_colors(&self, queue: &wgpu::Queue, colors: &[InstanceColor]) {
+        let instance_color_buffer = self.instance_color_buffer.as_ref().expect("item has no instance colors");
+        assert!(colors.len() as u32 <= self.instance_count, "more colors than instances");
+        queue.write_buffer(instance_color_buffer, 0, bytemuck::cast_slice(colors));
+    }
+
+    /// Overwrite the instances from the first one on, e.g. to animate them. There
+    /// can't be more than the item was built with.
+    pub fn write_instances(&self, queue: &wgpu::Queue, instances: &[Instance]) {
+        let instance_buffer = self.instance_buffer.as_ref().expect("item has no instance storage");
+        assert!(instances.len() as u32 <= self.instance_count, "more instances than the storage holds");
+        queue.write_buffer(instance_buffer, 0, bytemuck::cast_slice(instances));
+    }
+
+    /// Upload `transform` as the model matrix, along with `double_sided`. The
+    /// shader applies the camera's view-projection on top of it.
+    pub fn write_transform(&self, queue: &wgpu::Queue) {
+        let uniforms = Self::object_uniforms(&self.transform, self.double_sided);
+        queue.write_buffer(&self.object_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+    }
+
+    fn object_uniforms(transform: &Decomposed<Vector3<f32>, Quaternion<f32>>, double_sided: bool) -> ObjectUniforms {
+        let model: Matrix4<f32> = (*transform).into();
+        ObjectUniforms {
+            model: model.into(),
+            double_sided: double_sided as u32,
+            _padding: [0; 3],
+        }
+    }
+}
+
+pub trait DrawRenderItem<'a, 'b>
+where
+    'b: 'a,
+{
+    fn draw_item(&mut self, item: &'b RenderItem);
+    fn draw_item_instanced(&mut self, item: &'b RenderItem, instances: Range<u32>);
+    /// `draw_item`, with the model matrix and double-sided flag pushed as
+    /// `RenderItem::push_constant_range` instead of bound at group 2, so
+    /// `write_transform` isn't needed. Items with instance storage need their
+    /// bind group and can't be drawn this way.
+    fn draw_item_pushed(&mut self, item: &'b RenderItem);
+}
+
+impl<'a, 'b> DrawRenderItem<'a, 'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_item(&mut self, item: &'b RenderItem) {
+        self.draw_item_instanced(item, 0..item.instance_count);
+    }
+
+    fn draw_item_instanced(&mut self, item: &'b RenderItem, instances: Range<u32>) {
+        self.set_bind_group(2, &item.object_bind_group, &[]);
+        draw_buffers(self, item, instances);
+    }
+
+    fn draw_item_pushed(&mut self, item: &'b RenderItem) {
+        debug_assert!(item.instance_buffer.is_none(), "items with instance storage can't be drawn with push constants");
+        let uniforms = RenderItem::object_uniforms(&item.transform, item.double_sided);
+        self.set_push_constants(wgpu::ShaderStage::VERTEX, 0, bytemuck::bytes_of(&uniforms));
+        draw_buffers(self, item, 0..item.instance_count);
+    }
+}
+
+// Bind `item`'s vertex, instance color and index buffers and draw `instances` of it.
+fn draw_buffers<'a>(render_pass: &mut wgpu::RenderPass<'a>, item: &'a RenderItem, instances: Range<u32>) {
+    for (slot, vertex_buffer) in item.buffers.vertex_buffers.iter().enumerate() {
+        render_pass.set_vertex_buffer(slot as u32, vertex_buffer.slice(..));
+    }
+    if let Some(instance_color_buffer) = &item.instance_color_buffer {
+        render_pass.set_vertex_buffer(item.buffers.vertex_buffers.len() as u32, instance_color_buffer.slice(..));
+    }
+    match &item.buffers.index_data {
+        IndexData::Indexed(index_buffer, format, count) => {
+            render_pass.set_index_buffer(index_buffer.slice(..), *format);
+            render_pass.draw_indexed(0..*count, 0, instances);
+        }
+        IndexData::NonIndexed(count) => render_pass.draw(0..*count, instances),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::{model::GridUvMode, render_check::gpu::headless_device};
+
+    // Copies `buffer` into a mappable one and reads it back.
+    fn read_buffer(device: &wgpu::Device, queue: &wgpu::Queue, buffer: &wgpu::Buffer, size: usize) -> Vec<u8> {
+        let readback_buffer = device.create_buffer(
+            &wgpu::BufferDescriptor {
+                label: Some("Staging Test Readback Buffer"),
+                size: size as wgpu::BufferAddress,
+                usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+                mapped_at_creation: false,
+            }
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Staging Test Encoder"),
+        });
+        encoder.copy_buffer_to_buffer(buffer, 0, &readback_buffer, 0, size as wgpu::BufferAddress);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = readback_buffer.slice(..);
+        let mapped = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        block_on(mapped).expect("couldn't read the buffer back");
+        let bytes = slice.get_mapped_range().to_vec();
+        readback_buffer.unmap();
+        bytes
+    }
+
+    #[test]
+    fn staged_buffers_hold_every_chunk() {
+        let (device, queue) = match headless_device() {
+            Some(device) => device,
+            None => return eprintln!("no adapter, skipping the staging check"),
+        };
+        // Over a million vertices, a few chunks of vertices and of indices, the last ones partial.
+        let mesh = Mesh::grid(100.0, 100.0, 1000, 1000, GridUvMode::Stretch);
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(&mesh.vertices);
+        let index_bytes: &[u8] = bytemuck::cast_slice(&mesh.indices);
+        assert!(vertex_bytes.len() as wgpu::BufferAddress > 2 * STAGING_CHUNK_SIZE);
+        assert!(index_bytes.len() as wgpu::BufferAddress > STAGING_CHUNK_SIZE);
+
+        for (contents, usage) in [(vertex_bytes, wgpu::BufferUsage::VERTEX), (index_bytes, wgpu::BufferUsage::INDEX)].iter() {
+            let buffer = RenderItem::create_buffer_staged(&device, &queue, contents, *usage | wgpu::BufferUsage::COPY_SRC, "staging_test");
+            assert!(read_buffer(&device, &queue, &buffer, contents.len()) == *contents);
+        }
+    }
+}
\ No newline at end of file
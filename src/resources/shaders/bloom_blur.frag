@@ -0,0 +1,27 @@
+# version 450
+
+layout(location = 0) in vec2 texCoordsIn;
+layout(location = 0) out vec4 targetColor;
+
+layout(set = 0, binding = 0) uniform texture2D t_source;
+layout(set = 0, binding = 1) uniform sampler s_source;
+
+// `direction` is one texel along the blur axis.
+layout(set = 1, binding = 0)
+uniform BloomUniforms {
+    float threshold;
+    float intensity;
+    vec2 direction;
+};
+
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+    vec3 result = texture(sampler2D(t_source, s_source), texCoordsIn).rgb * weights[0];
+    for (int i = 1; i < 5; ++i) {
+        vec2 offset = direction * float(i);
+        result += texture(sampler2D(t_source, s_source), texCoordsIn + offset).rgb * weights[i];
+        result += texture(sampler2D(t_source, s_source), texCoordsIn - offset).rgb * weights[i];
+    }
+    targetColor = vec4(result, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,22 @@
+# version 450
+
+layout(location = 0) in vec2 texCoordsIn;
+layout(location = 0) out vec4 targetColor;
+
+layout(set = 0, binding = 0) uniform texture2D t_source;
+layout(set = 0, binding = 1) uniform sampler s_source;
+
+layout(set = 1, binding = 0)
+uniform BloomUniforms {
+    float threshold;
+    float intensity;
+    vec2 direction;
+};
+
+void main() {
+    vec3 color = texture(sampler2D(t_source, s_source), texCoordsIn).rgb;
+    float luma = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    // Keep only the part of the pixel brighter than the threshold.
+    float weight = max(luma - threshold, 0.0) / max(luma, 0.0001);
+    targetColor = vec4(color * weight, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,21 @@
+# version 450
+
+layout(location = 0) in vec2 texCoordsIn;
+layout(location = 0) out vec4 targetColor;
+
+layout(set = 0, binding = 0) uniform texture2D t_scene;
+layout(set = 0, binding = 1) uniform texture2D t_bloom;
+layout(set = 0, binding = 2) uniform sampler s_linear;
+
+layout(set = 1, binding = 0)
+uniform BloomUniforms {
+    float threshold;
+    float intensity;
+    vec2 direction;
+};
+
+void main() {
+    vec3 scene = texture(sampler2D(t_scene, s_linear), texCoordsIn).rgb;
+    vec3 bloom = texture(sampler2D(t_bloom, s_linear), texCoordsIn).rgb;
+    targetColor = vec4(scene + bloom * intensity, 1.0);
+}
\ No newline at end of file
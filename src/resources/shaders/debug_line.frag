@@ -0,0 +1,8 @@
+# version 450
+
+layout(location = 0) in vec4 colorIn;
+layout(location = 0) out vec4 targetColor;
+
+void main() {
+    targetColor = colorIn;
+}
\ No newline at end of file
@@ -0,0 +1,16 @@
+# version 450
+
+layout(location = 0) in vec3 positionIn;
+layout(location = 1) in vec4 colorIn;
+
+layout(set = 0, binding = 0)
+uniform Uniforms {
+    mat4 viewProj;
+};
+
+layout(location = 0) out vec4 colorOut;
+
+void main() {
+    colorOut = colorIn;
+    gl_Position = viewProj * vec4(positionIn, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,63 @@
+# version 450
+#extension GL_GOOGLE_include_directive : require
+
+layout(location = 0) in vec2 texCoordsIn;
+layout(location = 0) out vec4 targetColor;
+
+layout(set = 0, binding = 0) uniform texture2D t_position;
+layout(set = 0, binding = 1) uniform texture2D t_normal;
+layout(set = 0, binding = 2) uniform texture2D t_albedo;
+layout(set = 0, binding = 3) uniform sampler s_gbuffer;
+
+const uint MAX_POINT_LIGHTS = 32;
+
+layout(set = 1, binding = 0)
+uniform Lights {
+    // xyz position, w radius the light reaches.
+    vec4 positionRadius[MAX_POINT_LIGHTS];
+    // rgb color scaled by intensity.
+    vec4 color[MAX_POINT_LIGHTS];
+    uint lightCount;
+};
+
+// Environment radiance as 9 SH coefficients, see sh.rs for the order.
+layout(set = 2, binding = 0)
+uniform Environment {
+    vec4 sh[9];
+};
+
+// The forward pass's clear color, for pixels without geometry.
+const vec3 CLEAR_COLOR = vec3(0.1, 0.2, 0.3);
+
+#include "sh_irradiance.glsl"
+
+void main() {
+    vec4 position = texture(sampler2D(t_position, s_gbuffer), texCoordsIn);
+    vec4 normal = texture(sampler2D(t_normal, s_gbuffer), texCoordsIn);
+    vec4 albedo = texture(sampler2D(t_albedo, s_gbuffer), texCoordsIn);
+
+    if (normal.w == 0.0) {
+        targetColor = vec4(CLEAR_COLOR, 1.0);
+        return;
+    }
+    // Meshes without normals get the unshaded albedo, as in the forward pass.
+    if (dot(normal.xyz, normal.xyz) == 0.0) {
+        targetColor = albedo;
+        return;
+    }
+
+    vec3 n = normal.xyz;
+    vec3 lit = albedo.rgb * irradiance(n) / PI;
+    for (uint i = 0; i < min(lightCount, MAX_POINT_LIGHTS); i++) {
+        vec3 toLight = positionRadius[i].xyz - position.xyz;
+        float lightDistance = length(toLight);
+        float radius = positionRadius[i].w;
+        // Inverse square falloff, windowed to reach zero at the radius.
+        float window = clamp(1.0 - pow(lightDistance / radius, 4.0), 0.0, 1.0);
+        float attenuation = window * window / (lightDistance * lightDistance + 1.0);
+        float lambert = max(dot(n, toLight / max(lightDistance, 1e-4)), 0.0);
+        lit += albedo.rgb / PI * color[i].rgb * lambert * attenuation;
+    }
+
+    targetColor = vec4(lit, albedo.a);
+}
\ No newline at end of file
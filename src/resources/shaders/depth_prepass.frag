@@ -0,0 +1,8 @@
+# version 450
+
+// Nothing to shade, the prepass pipeline doesn't write color.
+layout(location = 0) out vec4 targetColor;
+
+void main() {
+    targetColor = vec4(0.0);
+}
\ No newline at end of file
@@ -0,0 +1,24 @@
+# version 450
+
+// shader.vert's position alone, for the depth prepass. The lit pass then tests
+// for equal depth, so both declare gl_Position invariant and compute it with the
+// same expression from the same inputs, to get the same depth bit for bit.
+
+layout(location = 0) in vec3 positionIn;
+
+layout(set = 1, binding = 0)
+uniform Uniforms {
+    mat4 viewProj;
+};
+
+layout(set = 2, binding = 0)
+uniform Object {
+    mat4 model;
+    uint doubleSided;
+};
+
+invariant gl_Position;
+
+void main() {
+    gl_Position = viewProj * model * vec4(positionIn, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,40 @@
+# version 450
+
+layout(location = 0) in vec2 texCoordsIn;
+layout(location = 0) out vec4 targetColor;
+
+layout(set = 0, binding = 0) uniform texture2D t_equirect;
+layout(set = 0, binding = 1) uniform sampler s_equirect;
+
+layout(set = 1, binding = 0)
+uniform CubeFaceUniforms {
+    uint face;
+};
+
+const float PI = 3.14159265359;
+
+// Direction through the face texel, in the +x, -x, +y, -y, +z, -z layer order
+// with t growing down each face, the usual cube map convention.
+vec3 faceDirection(uint face, vec2 uv) {
+    float s = 2.0 * uv.x - 1.0;
+    float t = 2.0 * uv.y - 1.0;
+    if (face == 0) return vec3(1.0, -t, -s);
+    if (face == 1) return vec3(-1.0, -t, s);
+    if (face == 2) return vec3(s, 1.0, t);
+    if (face == 3) return vec3(s, -1.0, -t);
+    if (face == 4) return vec3(s, -t, 1.0);
+    return vec3(-s, -t, -1.0);
+}
+
+void main() {
+    vec3 direction = normalize(faceDirection(face, texCoordsIn));
+    // Rows from +y down to -y, columns once around y starting at +x, as in
+    // `sh::project_equirect`.
+    float phi = atan(direction.z, direction.x);
+    if (phi < 0.0) {
+        phi += 2.0 * PI;
+    }
+    float theta = acos(clamp(direction.y, -1.0, 1.0));
+    vec2 uv = vec2(phi / (2.0 * PI), theta / PI);
+    targetColor = vec4(texture(sampler2D(t_equirect, s_equirect), uv).rgb, 1.0);
+}
\ No newline at end of file
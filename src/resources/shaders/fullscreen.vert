@@ -0,0 +1,10 @@
+# version 450
+
+// Oversized triangle covering the whole screen, drawn with 3 vertices and no buffers.
+layout(location = 0) out vec2 texCoordsOut;
+
+void main() {
+    vec2 uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    texCoordsOut = vec2(uv.x, 1.0 - uv.y);
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
\ No newline at end of file
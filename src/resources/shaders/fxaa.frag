@@ -0,0 +1,142 @@
+# version 450
+
+layout(location = 0) in vec2 texCoordsIn;
+layout(location = 0) out vec4 targetColor;
+
+layout(set = 0, binding = 0) uniform texture2D t_source;
+layout(set = 0, binding = 1) uniform sampler s_source;
+
+layout(set = 1, binding = 0)
+uniform FxaaUniforms {
+    vec2 texelSize;
+    float edgeThreshold;
+    float edgeThresholdMin;
+    float subpixelQuality;
+};
+
+const int EDGE_STEP_COUNT = 10;
+const float EDGE_STEPS[EDGE_STEP_COUNT] = float[](1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 2.0, 2.0, 4.0);
+const float EDGE_GUESS = 8.0;
+
+// The source is sampled through an sRGB view and comes back linear, FXAA expects
+// perceptual luma so take the square root as a cheap gamma approximation.
+float luma(vec2 uv) {
+    vec3 color = texture(sampler2D(t_source, s_source), uv).rgb;
+    return sqrt(dot(color, vec3(0.299, 0.587, 0.114)));
+}
+
+void main() {
+    vec2 uv = texCoordsIn;
+
+    float m = luma(uv);
+    float n = luma(uv + vec2(0.0, texelSize.y));
+    float s = luma(uv - vec2(0.0, texelSize.y));
+    float e = luma(uv + vec2(texelSize.x, 0.0));
+    float w = luma(uv - vec2(texelSize.x, 0.0));
+
+    float highest = max(max(max(max(n, e), s), w), m);
+    float lowest = min(min(min(min(n, e), s), w), m);
+    float contrast = highest - lowest;
+    if (contrast < max(edgeThresholdMin, edgeThreshold * highest)) {
+        targetColor = texture(sampler2D(t_source, s_source), uv);
+        return;
+    }
+
+    float ne = luma(uv + vec2(texelSize.x, texelSize.y));
+    float nw = luma(uv + vec2(-texelSize.x, texelSize.y));
+    float se = luma(uv + vec2(texelSize.x, -texelSize.y));
+    float sw = luma(uv + vec2(-texelSize.x, -texelSize.y));
+
+    // Sub-pixel aliasing, from how much the center differs from its neighborhood.
+    float neighborhood = (2.0 * (n + e + s + w) + ne + nw + se + sw) / 12.0;
+    float subpixel = clamp(abs(neighborhood - m) / contrast, 0.0, 1.0);
+    subpixel = smoothstep(0.0, 1.0, subpixel);
+    float subpixelBlend = subpixel * subpixel * subpixelQuality;
+
+    float horizontal =
+        2.0 * abs(n + s - 2.0 * m) +
+        abs(ne + se - 2.0 * e) +
+        abs(nw + sw - 2.0 * w);
+    float vertical =
+        2.0 * abs(e + w - 2.0 * m) +
+        abs(ne + nw - 2.0 * n) +
+        abs(se + sw - 2.0 * s);
+    bool isHorizontal = horizontal >= vertical;
+
+    // Pick the side of the edge with the larger gradient.
+    float positiveLuma = isHorizontal ? n : e;
+    float negativeLuma = isHorizontal ? s : w;
+    float positiveGradient = abs(positiveLuma - m);
+    float negativeGradient = abs(negativeLuma - m);
+
+    float pixelStep = isHorizontal ? texelSize.y : texelSize.x;
+    float oppositeLuma = positiveLuma;
+    float gradient = positiveGradient;
+    if (positiveGradient < negativeGradient) {
+        pixelStep = -pixelStep;
+        oppositeLuma = negativeLuma;
+        gradient = negativeGradient;
+    }
+
+    // Walk along the edge in both directions until its luma changes.
+    vec2 edgeUv = uv;
+    vec2 edgeStep;
+    if (isHorizontal) {
+        edgeUv.y += 0.5 * pixelStep;
+        edgeStep = vec2(texelSize.x, 0.0);
+    } else {
+        edgeUv.x += 0.5 * pixelStep;
+        edgeStep = vec2(0.0, texelSize.y);
+    }
+
+    float edgeLuma = 0.5 * (m + oppositeLuma);
+    float gradientThreshold = 0.25 * gradient;
+
+    vec2 positiveUv = edgeUv + edgeStep;
+    float positiveDelta = luma(positiveUv) - edgeLuma;
+    bool positiveAtEnd = abs(positiveDelta) >= gradientThreshold;
+    for (int i = 1; i < EDGE_STEP_COUNT && !positiveAtEnd; ++i) {
+        positiveUv += edgeStep * EDGE_STEPS[i];
+        positiveDelta = luma(positiveUv) - edgeLuma;
+        positiveAtEnd = abs(positiveDelta) >= gradientThreshold;
+    }
+    if (!positiveAtEnd) {
+        positiveUv += edgeStep * EDGE_GUESS;
+    }
+
+    vec2 negativeUv = edgeUv - edgeStep;
+    float negativeDelta = luma(negativeUv) - edgeLuma;
+    bool negativeAtEnd = abs(negativeDelta) >= gradientThreshold;
+    for (int i = 1; i < EDGE_STEP_COUNT && !negativeAtEnd; ++i) {
+        negativeUv -= edgeStep * EDGE_STEPS[i];
+        negativeDelta = luma(negativeUv) - edgeLuma;
+        negativeAtEnd = abs(negativeDelta) >= gradientThreshold;
+    }
+    if (!negativeAtEnd) {
+        negativeUv -= edgeStep * EDGE_GUESS;
+    }
+
+    float positiveDistance = isHorizontal ? positiveUv.x - uv.x : positiveUv.y - uv.y;
+    float negativeDistance = isHorizontal ? uv.x - negativeUv.x : uv.y - negativeUv.y;
+
+    float shortestDistance = positiveDistance;
+    bool deltaSign = positiveDelta >= 0.0;
+    if (negativeDistance < positiveDistance) {
+        shortestDistance = negativeDistance;
+        deltaSign = negativeDelta >= 0.0;
+    }
+
+    // Only blend when moving towards the end of the edge that differs from the center.
+    float edgeBlend = 0.0;
+    if (deltaSign != (m - edgeLuma >= 0.0)) {
+        edgeBlend = 0.5 - shortestDistance / (positiveDistance + negativeDistance);
+    }
+
+    float finalBlend = max(subpixelBlend, edgeBlend);
+    if (isHorizontal) {
+        uv.y += pixelStep * finalBlend;
+    } else {
+        uv.x += pixelStep * finalBlend;
+    }
+    targetColor = texture(sampler2D(t_source, s_source), uv);
+}
\ No newline at end of file
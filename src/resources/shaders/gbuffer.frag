@@ -0,0 +1,21 @@
+# version 450
+
+layout(location = 0) in vec2 texCoordsIn;
+layout(location = 1) in vec3 normalIn;
+layout(location = 2) in vec3 positionIn;
+
+// See deferred.rs for the formats. The w of position and normal is 1 where there
+// is geometry, the lighting pass keeps the clear color where it's 0.
+layout(location = 0) out vec4 positionOut;
+layout(location = 1) out vec4 normalOut;
+layout(location = 2) out vec4 albedoOut;
+
+layout(set = 0, binding = 0) uniform texture2D t_diffuse;
+layout(set = 0, binding = 1) uniform sampler s_diffuse;
+
+void main() {
+    positionOut = vec4(positionIn, 1.0);
+    // Zero length normals are kept, the lighting pass leaves those meshes unshaded.
+    normalOut = vec4(dot(normalIn, normalIn) > 0.0 ? normalize(normalIn) : vec3(0.0), 1.0);
+    albedoOut = texture(sampler2D(t_diffuse, s_diffuse), texCoordsIn);
+}
\ No newline at end of file
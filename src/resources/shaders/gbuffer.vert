@@ -0,0 +1,28 @@
+# version 450
+
+layout(location = 0) in vec3 positionIn;
+layout(location = 1) in vec3 normalIn;
+layout(location = 2) in vec3 tangentIn;
+layout(location = 3) in vec2 texCoordsIn;
+
+layout(set = 1, binding = 0)
+uniform Uniforms {
+    mat4 viewProj;
+};
+
+layout(set = 2, binding = 0)
+uniform Object {
+    mat4 model;
+};
+
+layout(location = 0) out vec2 texCoordsOut;
+layout(location = 1) out vec3 normalOut;
+layout(location = 2) out vec3 positionOut;
+
+void main() {
+    vec4 worldPosition = model * vec4(positionIn, 1.0);
+    texCoordsOut = texCoordsIn;
+    normalOut = transpose(inverse(mat3(model))) * normalIn;
+    positionOut = worldPosition.xyz;
+    gl_Position = viewProj * worldPosition;
+}
\ No newline at end of file
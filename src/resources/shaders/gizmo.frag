@@ -0,0 +1,16 @@
+# version 450
+
+layout(location = 0) in vec3 normalIn;
+layout(location = 0) out vec4 targetColor;
+
+layout(set = 1, binding = 0)
+uniform Handle {
+    vec4 color;
+};
+
+const vec3 LIGHT_DIRECTION = vec3(0.3, 0.8, 0.5);
+
+void main() {
+    float diffuse = max(dot(normalize(normalIn), normalize(LIGHT_DIRECTION)), 0.0);
+    targetColor = vec4(color.rgb * (0.6 + 0.4 * diffuse), color.a);
+}
\ No newline at end of file
@@ -0,0 +1,22 @@
+# version 450
+
+layout(location = 0) in vec3 positionIn;
+layout(location = 1) in vec3 normalIn;
+
+layout(set = 0, binding = 0)
+uniform Uniforms {
+    mat4 viewProj;
+};
+
+layout(set = 2, binding = 0)
+uniform Object {
+    mat4 model;
+};
+
+layout(location = 0) out vec3 normalOut;
+
+void main() {
+    // Handles are only uniformly scaled, so the model matrix can transform normals.
+    normalOut = mat3(model) * normalIn;
+    gl_Position = viewProj * model * vec4(positionIn, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,31 @@
+# version 450
+
+layout(location = 0) in vec3 positionIn;
+layout(location = 1) in vec3 normalIn;
+layout(location = 2) in vec3 tangentIn;
+layout(location = 3) in vec2 texCoordsIn;
+layout(location = 0) out vec4 targetColor;
+
+const vec3 RIGHT_HANDED = vec3(1.0, 0.1, 0.1);
+const vec3 LEFT_HANDED = vec3(0.1, 0.2, 1.0);
+// No tangent or no usable texture coordinates.
+const vec3 UNDEFINED = vec3(0.5);
+
+void main() {
+    // The direction v grows in across the triangle, from the screen space
+    // derivatives: dp = tangent * du + bitangent * dv, solved for the bitangent.
+    vec3 dp1 = dFdx(positionIn);
+    vec3 dp2 = dFdy(positionIn);
+    vec2 duv1 = dFdx(texCoordsIn);
+    vec2 duv2 = dFdy(texCoordsIn);
+    float det = duv1.x * duv2.y - duv1.y * duv2.x;
+    vec3 bitangent = (duv1.x * dp2 - duv2.x * dp1) / det;
+
+    // Mirrored UVs flip v against the normal and tangent, which flips the sign.
+    float handedness = dot(cross(normalIn, tangentIn), bitangent);
+    vec3 color = UNDEFINED;
+    if (dot(tangentIn, tangentIn) > 0.0 && abs(det) > 0.0) {
+        color = handedness >= 0.0 ? RIGHT_HANDED : LEFT_HANDED;
+    }
+    targetColor = vec4(color, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,31 @@
+# version 450
+
+layout(location = 0) in vec3 positionIn;
+layout(location = 1) in vec3 normalIn;
+layout(location = 2) in vec3 tangentIn;
+layout(location = 3) in vec2 texCoordsIn;
+
+layout(set = 1, binding = 0)
+uniform Uniforms {
+    mat4 viewProj;
+};
+
+layout(set = 2, binding = 0)
+uniform Object {
+    mat4 model;
+};
+
+layout(location = 0) out vec3 positionOut;
+layout(location = 1) out vec3 normalOut;
+layout(location = 2) out vec3 tangentOut;
+layout(location = 3) out vec2 texCoordsOut;
+
+void main() {
+    vec4 position = model * vec4(positionIn, 1.0);
+    positionOut = position.xyz;
+    normalOut = transpose(inverse(mat3(model))) * normalIn;
+    // Tangents follow the surface, so they take the model matrix itself.
+    tangentOut = mat3(model) * tangentIn;
+    texCoordsOut = texCoordsIn;
+    gl_Position = viewProj * position;
+}
\ No newline at end of file
@@ -0,0 +1,41 @@
+# version 450
+
+// shader.vert with the model matrix of each instance read from a storage buffer,
+// for items built with RenderItem::with_instance_storage. Pairs with shader.frag.
+
+layout(location = 0) in vec3 positionIn;
+layout(location = 1) in vec3 normalIn;
+layout(location = 2) in vec3 tangentIn;
+layout(location = 3) in vec2 texCoordsIn;
+
+layout(set = 1, binding = 0)
+uniform Uniforms {
+    mat4 viewProj;
+};
+
+layout(set = 2, binding = 0)
+uniform Object {
+    mat4 model;
+    uint doubleSided;
+};
+
+struct Instance {
+    mat4 model;
+};
+
+layout(set = 2, binding = 1)
+readonly buffer Instances {
+    Instance instances[];
+};
+
+layout(location = 0) out vec2 texCoordsOut;
+layout(location = 1) out vec3 normalOut;
+layout(location = 2) flat out uint doubleSidedOut;
+
+void main() {
+    mat4 world = model * instances[gl_InstanceIndex].model;
+    texCoordsOut = texCoordsIn;
+    normalOut = transpose(inverse(mat3(world))) * normalIn;
+    doubleSidedOut = doubleSided;
+    gl_Position = viewProj * world * vec4(positionIn, 1.0);
+}
\ No newline at end of file
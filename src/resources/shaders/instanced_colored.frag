@@ -0,0 +1,27 @@
+# version 450
+#extension GL_GOOGLE_include_directive : require
+
+// shader.frag tinted by the instance color from instanced_colored.vert.
+
+layout(location = 0) in vec2 texCoordsIn;
+layout(location = 1) in vec3 normalIn;
+layout(location = 2) in vec4 colorIn;
+layout(location = 0) out vec4 targetColor;
+
+layout(set = 0, binding = 0) uniform texture2D t_diffuse;
+layout(set = 0, binding = 1) uniform sampler s_diffuse;
+
+// Environment radiance as 9 SH coefficients, see sh.rs for the order.
+layout(set = 3, binding = 0)
+uniform Environment {
+    vec4 sh[9];
+};
+
+#include "sh_irradiance.glsl"
+
+void main() {
+    vec4 albedo = texture(sampler2D(t_diffuse, s_diffuse), texCoordsIn) * colorIn;
+    // Meshes without normals get the unshaded albedo.
+    vec3 ambient = dot(normalIn, normalIn) > 0.0 ? irradiance(normalize(normalIn)) / PI : vec3(1.0);
+    targetColor = vec4(albedo.rgb * ambient, albedo.a);
+}
\ No newline at end of file
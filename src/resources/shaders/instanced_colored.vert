@@ -0,0 +1,42 @@
+# version 450
+
+// instanced.vert with a color per instance, for items built with
+// RenderItem::with_instance_colors. Pairs with instanced_colored.frag.
+
+layout(location = 0) in vec3 positionIn;
+layout(location = 1) in vec3 normalIn;
+layout(location = 2) in vec3 tangentIn;
+layout(location = 3) in vec2 texCoordsIn;
+// Stepped per instance.
+layout(location = 4) in vec4 instanceColorIn;
+
+layout(set = 1, binding = 0)
+uniform Uniforms {
+    mat4 viewProj;
+};
+
+layout(set = 2, binding = 0)
+uniform Object {
+    mat4 model;
+};
+
+struct Instance {
+    mat4 model;
+};
+
+layout(set = 2, binding = 1)
+readonly buffer Instances {
+    Instance instances[];
+};
+
+layout(location = 0) out vec2 texCoordsOut;
+layout(location = 1) out vec3 normalOut;
+layout(location = 2) out vec4 colorOut;
+
+void main() {
+    mat4 world = model * instances[gl_InstanceIndex].model;
+    texCoordsOut = texCoordsIn;
+    colorOut = instanceColorIn;
+    normalOut = transpose(inverse(mat3(world))) * normalIn;
+    gl_Position = viewProj * world * vec4(positionIn, 1.0);
+}
\ No newline at end of file
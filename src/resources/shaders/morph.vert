@@ -0,0 +1,40 @@
+# version 450
+
+layout(location = 0) in vec3 positionIn;
+layout(location = 1) in vec3 normalIn;
+layout(location = 2) in vec3 tangentIn;
+layout(location = 3) in vec2 texCoordsIn;
+// The same vertex of the morph target, see morph.rs.
+layout(location = 4) in vec3 targetPositionIn;
+layout(location = 5) in vec3 targetNormalIn;
+
+layout(set = 0, binding = 2)
+uniform Morph {
+    // 0 for the base mesh, 1 for the target.
+    float weight;
+};
+
+layout(set = 1, binding = 0)
+uniform Uniforms {
+    mat4 viewProj;
+};
+
+layout(set = 2, binding = 0)
+uniform Object {
+    mat4 model;
+    uint doubleSided;
+};
+
+layout(location = 0) out vec2 texCoordsOut;
+layout(location = 1) out vec3 normalOut;
+layout(location = 2) flat out uint doubleSidedOut;
+
+void main() {
+    vec3 position = mix(positionIn, targetPositionIn, weight);
+    // The fragment stage normalizes after interpolating anyway.
+    vec3 normal = mix(normalIn, targetNormalIn, weight);
+    texCoordsOut = texCoordsIn;
+    normalOut = transpose(inverse(mat3(model))) * normal;
+    doubleSidedOut = doubleSided;
+    gl_Position = viewProj * model * vec4(position, 1.0);
+}
\ No newline at end of file
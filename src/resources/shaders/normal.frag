@@ -0,0 +1,9 @@
+# version 450
+
+layout(location = 0) in vec3 normalIn;
+layout(location = 0) out vec4 targetColor;
+
+void main() {
+    // Map the world space normal from [-1, 1] to [0, 1].
+    targetColor = vec4(normalize(normalIn) * 0.5 + 0.5, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,38 @@
+# version 450
+#extension GL_GOOGLE_include_directive : require
+
+layout(location = 0) in vec2 texCoordsIn;
+layout(location = 1) in vec3 normalIn;
+layout(location = 2) in vec3 positionIn;
+
+// See oit.rs for the formats and the blend states that add these up.
+layout(location = 0) out vec4 accumOut;
+layout(location = 1) out float revealageOut;
+
+layout(set = 0, binding = 0)
+uniform Translucent {
+    // Linear color and opacity, not premultiplied.
+    vec4 color;
+};
+
+// Environment radiance as 9 SH coefficients, see sh.rs for the order.
+layout(set = 3, binding = 0)
+uniform Environment {
+    vec4 sh[9];
+};
+
+#include "sh_irradiance.glsl"
+
+void main() {
+    // Both faces are drawn, the back ones lit from the side the camera sees.
+    vec3 normal = gl_FrontFacing ? normalIn : -normalIn;
+    vec3 ambient = dot(normal, normal) > 0.0 ? irradiance(normalize(normal)) / PI : vec3(1.0);
+    vec3 lit = color.rgb * ambient;
+    float alpha = color.a;
+
+    // McGuire and Bavoil's depth weight, equation 10: nearer and more opaque
+    // fragments count for more, within a range half floats hold.
+    float weight = clamp(pow(min(1.0, alpha * 10.0) + 0.01, 3.0) * 1e8 * pow(1.0 - gl_FragCoord.z * 0.9, 3.0), 1e-2, 3e3);
+    accumOut = vec4(lit * alpha, alpha) * weight;
+    revealageOut = alpha;
+}
\ No newline at end of file
@@ -0,0 +1,20 @@
+# version 450
+
+layout(location = 0) in vec2 texCoordsIn;
+layout(location = 0) out vec4 targetColor;
+
+layout(set = 0, binding = 0) uniform texture2D t_accum;
+layout(set = 0, binding = 1) uniform texture2D t_revealage;
+layout(set = 0, binding = 2) uniform sampler s_oit;
+
+void main() {
+    float revealage = texture(sampler2D(t_revealage, s_oit), texCoordsIn).r;
+    // Nothing translucent here, leave the scene as it is.
+    if (revealage >= 1.0) {
+        discard;
+    }
+    vec4 accum = texture(sampler2D(t_accum, s_oit), texCoordsIn);
+    // The weighted average color, blended over the scene by the total coverage.
+    vec3 average = accum.rgb / max(accum.a, 1e-5);
+    targetColor = vec4(average, 1.0 - revealage);
+}
\ No newline at end of file
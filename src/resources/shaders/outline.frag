@@ -0,0 +1,14 @@
+# version 450
+
+layout(location = 0) out vec4 targetColor;
+
+layout(set = 0, binding = 0)
+uniform OutlineUniforms {
+    vec4 color;
+    vec2 viewport;
+    float width;
+};
+
+void main() {
+    targetColor = color;
+}
\ No newline at end of file
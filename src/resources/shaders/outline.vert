@@ -0,0 +1,34 @@
+# version 450
+
+layout(location = 0) in vec3 positionIn;
+layout(location = 1) in vec3 normalIn;
+
+layout(set = 0, binding = 0)
+uniform OutlineUniforms {
+    vec4 color;
+    vec2 viewport;
+    float width;
+};
+
+layout(set = 1, binding = 0)
+uniform Uniforms {
+    mat4 viewProj;
+};
+
+layout(set = 2, binding = 0)
+uniform Object {
+    mat4 model;
+};
+
+void main() {
+    vec4 position = viewProj * model * vec4(positionIn, 1.0);
+    vec3 normal = transpose(inverse(mat3(model))) * normalIn;
+    vec2 screenNormal = (viewProj * vec4(normal, 0.0)).xy;
+
+    // Push the vertex `width` pixels out along its normal on screen. NDC spans 2
+    // units over the viewport, and the offset is scaled by w to survive the divide.
+    if (dot(screenNormal, screenNormal) > 0.0) {
+        position.xy += normalize(screenNormal) * 2.0 * width / viewport * position.w;
+    }
+    gl_Position = position;
+}
\ No newline at end of file
@@ -0,0 +1,11 @@
+# version 450
+
+layout(location = 0) in vec2 positionIn;
+layout(location = 1) in vec4 colorIn;
+
+layout(location = 0) out vec4 colorOut;
+
+void main() {
+    colorOut = colorIn;
+    gl_Position = vec4(positionIn, 0.0, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+# version 450
+
+layout(location = 0) in vec3 normalIn;
+layout(location = 1) in vec2 texCoordsIn;
+layout(location = 0) out vec4 targetColor;
+
+// Half normal color, half a 4x4 checker over the texture coordinates, so changes
+// to either show up in the image.
+void main() {
+    vec3 normalColor = normalize(normalIn) * 0.5 + 0.5;
+    vec2 cell = floor(texCoordsIn * 4.0);
+    float checker = mod(cell.x + cell.y, 2.0);
+    targetColor = vec4(mix(normalColor, vec3(checker), 0.5), 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,28 @@
+# version 450
+
+layout(location = 0) in vec3 positionIn;
+layout(location = 1) in vec3 normalIn;
+layout(location = 2) in vec3 tangentIn;
+layout(location = 3) in vec2 texCoordsIn;
+
+layout(set = 1, binding = 0)
+uniform Uniforms {
+    mat4 viewProj;
+};
+
+layout(set = 2, binding = 0)
+uniform Object {
+    mat4 model;
+};
+
+layout(location = 0) out vec3 normalOut;
+layout(location = 1) out vec2 texCoordsOut;
+
+// Equal to depth_prepass.vert's bit for bit, for the prepass check.
+invariant gl_Position;
+
+void main() {
+    normalOut = transpose(inverse(mat3(model))) * normalIn;
+    texCoordsOut = texCoordsIn;
+    gl_Position = viewProj * model * vec4(positionIn, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,21 @@
+// Lighting from the environment's SH coefficients, shared by the shaders that
+// use it. Include it after declaring the Environment block with `vec4 sh[9]`,
+// see sh.rs for the order.
+
+const float PI = 3.14159265;
+
+// Ramamoorthi and Hanrahan's irradiance, the same as sh::irradiance.
+vec3 irradiance(vec3 n) {
+    const float c1 = 0.429043;
+    const float c2 = 0.511664;
+    const float c3 = 0.743125;
+    const float c4 = 0.886227;
+    const float c5 = 0.247708;
+
+    return c1 * sh[8].rgb * (n.x * n.x - n.y * n.y)
+        + c3 * sh[6].rgb * n.z * n.z
+        + c4 * sh[0].rgb
+        - c5 * sh[6].rgb
+        + 2.0 * c1 * (sh[4].rgb * n.x * n.y + sh[7].rgb * n.x * n.z + sh[5].rgb * n.y * n.z)
+        + 2.0 * c2 * (sh[3].rgb * n.x + sh[1].rgb * n.y + sh[2].rgb * n.z);
+}
\ No newline at end of file
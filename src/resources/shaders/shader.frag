@@ -1,11 +1,28 @@
 # version 450
+#extension GL_GOOGLE_include_directive : require
 
 layout(location = 0) in vec2 texCoordsIn;
+layout(location = 1) in vec3 normalIn;
+// Passed on by the vertex stage, from the object uniform or the push constants.
+layout(location = 2) flat in uint doubleSided;
 layout(location = 0) out vec4 targetColor;
 
 layout(set = 0, binding = 0) uniform texture2D t_diffuse;
 layout(set = 0, binding = 1) uniform sampler s_diffuse;
 
+// Environment radiance as 9 SH coefficients, see sh.rs for the order.
+layout(set = 3, binding = 0)
+uniform Environment {
+    vec4 sh[9];
+};
+
+#include "sh_irradiance.glsl"
+
 void main() {
-    targetColor = texture(sampler2D(t_diffuse, s_diffuse), texCoordsIn);
+    vec4 albedo = texture(sampler2D(t_diffuse, s_diffuse), texCoordsIn);
+    // Back faces of double-sided items are lit from the side the camera sees.
+    vec3 normal = doubleSided != 0 && !gl_FrontFacing ? -normalIn : normalIn;
+    // Meshes without normals get the unshaded albedo.
+    vec3 ambient = dot(normal, normal) > 0.0 ? irradiance(normalize(normal)) / PI : vec3(1.0);
+    targetColor = vec4(albedo.rgb * ambient, albedo.a);
 }
\ No newline at end of file
@@ -10,9 +10,22 @@ uniform Uniforms {
     mat4 viewProj;
 };
 
+layout(set = 2, binding = 0)
+uniform Object {
+    mat4 model;
+    uint doubleSided;
+};
+
 layout(location = 0) out vec2 texCoordsOut;
+layout(location = 1) out vec3 normalOut;
+layout(location = 2) flat out uint doubleSidedOut;
+
+// Matches depth_prepass.vert's, for the lit pass's equal depth test after it.
+invariant gl_Position;
 
 void main() {
     texCoordsOut = texCoordsIn;
-    gl_Position = viewProj * vec4(positionIn, 1.0);
+    normalOut = transpose(inverse(mat3(model))) * normalIn;
+    doubleSidedOut = doubleSided;
+    gl_Position = viewProj * model * vec4(positionIn, 1.0);
 }
\ No newline at end of file
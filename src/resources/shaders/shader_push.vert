@@ -0,0 +1,30 @@
+# version 450
+
+layout(location = 0) in vec3 positionIn;
+layout(location = 1) in vec3 normalIn;
+layout(location = 2) in vec3 tangentIn;
+layout(location = 3) in vec2 texCoordsIn;
+
+layout(set = 1, binding = 0)
+uniform Uniforms {
+    mat4 viewProj;
+};
+
+// The object uniform's layout, pushed with each draw instead of bound, see
+// `DrawRenderItem::draw_item_pushed`.
+layout(push_constant)
+uniform Object {
+    mat4 model;
+    uint doubleSided;
+};
+
+layout(location = 0) out vec2 texCoordsOut;
+layout(location = 1) out vec3 normalOut;
+layout(location = 2) flat out uint doubleSidedOut;
+
+void main() {
+    texCoordsOut = texCoordsIn;
+    normalOut = transpose(inverse(mat3(model))) * normalIn;
+    doubleSidedOut = doubleSided;
+    gl_Position = viewProj * model * vec4(positionIn, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,44 @@
+# version 450
+#extension GL_GOOGLE_include_directive : require
+
+layout(location = 0) in vec2 texCoordsIn;
+layout(location = 1) in vec3 normalIn;
+layout(location = 2) in vec3 positionIn;
+layout(location = 0) out vec4 targetColor;
+
+// Layers in the order ground, rock, snow, see `terrain.rs`.
+layout(set = 0, binding = 0) uniform texture2DArray t_layers;
+layout(set = 0, binding = 1) uniform sampler s_layers;
+
+layout(set = 0, binding = 2)
+uniform Terrain {
+    // Steepness, 1 - normal.y, over which rock takes over.
+    vec2 slopeRange;
+    // World heights over which snow takes over what isn't rock.
+    vec2 heightRange;
+    float tiling;
+    float mipBias;
+};
+
+// Environment radiance as 9 SH coefficients, see sh.rs for the order.
+layout(set = 3, binding = 0)
+uniform Environment {
+    vec4 sh[9];
+};
+
+#include "sh_irradiance.glsl"
+
+vec3 layer(float index) {
+    return texture(sampler2DArray(t_layers, s_layers), vec3(texCoordsIn * tiling, index), mipBias).rgb;
+}
+
+void main() {
+    // Weights from the interpolated normal, so they blend smoothly across triangles.
+    vec3 normal = normalize(normalIn);
+    float rock = smoothstep(slopeRange.x, slopeRange.y, 1.0 - normal.y);
+    float snow = smoothstep(heightRange.x, heightRange.y, positionIn.y) * (1.0 - rock);
+    float ground = 1.0 - rock - snow;
+
+    vec3 albedo = ground * layer(0.0) + rock * layer(1.0) + snow * layer(2.0);
+    targetColor = vec4(albedo * irradiance(normal) / PI, 1.0);
+}
\ No newline at end of file
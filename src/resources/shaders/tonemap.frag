@@ -0,0 +1,35 @@
+# version 450
+
+layout(location = 0) in vec2 texCoordsIn;
+layout(location = 0) out vec4 targetColor;
+
+layout(set = 0, binding = 0) uniform texture2D t_source;
+layout(set = 0, binding = 1) uniform sampler s_source;
+
+layout(set = 1, binding = 0)
+uniform ToneMapUniforms {
+    uint operator;
+    float exposure;
+};
+
+const uint OPERATOR_REINHARD = 0;
+const uint OPERATOR_ACES = 1;
+
+// Narkowicz's fit of the ACES filmic curve.
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec4 hdr = texture(sampler2D(t_source, s_source), texCoordsIn);
+    vec3 color = hdr.rgb * exposure;
+
+    if (operator == OPERATOR_REINHARD) {
+        color = color / (1.0 + color);
+    } else {
+        color = aces(color);
+    }
+
+    // Still linear, the sRGB target applies the gamma on write.
+    targetColor = vec4(color, hdr.a);
+}
\ No newline at end of file
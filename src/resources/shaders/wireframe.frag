@@ -0,0 +1,13 @@
+# version 450
+
+layout(location = 0) out vec4 targetColor;
+
+layout(set = 0, binding = 0)
+uniform Wireframe {
+    vec4 color;
+    float depthBias;
+};
+
+void main() {
+    targetColor = color;
+}
\ No newline at end of file
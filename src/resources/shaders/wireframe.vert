@@ -0,0 +1,27 @@
+# version 450
+
+layout(location = 0) in vec3 positionIn;
+
+layout(set = 0, binding = 0)
+uniform Wireframe {
+    vec4 color;
+    // Of the depth range, see wireframe.rs.
+    float depthBias;
+};
+
+layout(set = 1, binding = 0)
+uniform Uniforms {
+    mat4 viewProj;
+};
+
+layout(set = 2, binding = 0)
+uniform Object {
+    mat4 model;
+};
+
+void main() {
+    gl_Position = viewProj * model * vec4(positionIn, 1.0);
+    // Pulled toward the camera by a constant share of the depth range. Scaled by w
+    // so it survives the perspective divide.
+    gl_Position.z -= depthBias * gl_Position.w;
+}
\ No newline at end of file
@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use cgmath::Vector3;
+
+use crate::{model::{Aabb, Mesh}, render_item::RenderItem};
+
+// Gap left between neighbouring meshes, as a fraction of the widest of them.
+const SPACING: f32 = 0.25;
+
+/// Render items laid out together, e.g. from `from_directory`.
+pub struct Scene {
+    pub items: Vec<RenderItem>,
+    // Where each item came from, in the same order.
+    pub labels: Vec<String>,
+    // The mesh of each item, in the same order, in its own space.
+    pub meshes: Vec<Mesh>,
+    // World bounds of every item, empty without any.
+    pub bounds: Aabb,
+}
+
+impl Scene {
+    /// Load every `.obj` file directly in `dir` and lay them out on the ground
+    /// in rows, about as many as there are columns, in file name order so the
+    /// layout is the same every time. Each mesh sits on y = 0 and takes the room
+    /// of its bounding box, with a gap to its neighbours.
+    ///
+    /// Other files are ignored, and OBJs that fail to read or parse, or have no
+    /// vertices, are skipped with a warning. Only not being able to list `dir` is
+    /// an error.
+    ///
+    /// With `deinterleave_vertices` the items are built by
+    /// `RenderItem::from_mesh_deinterleaved`, for pipelines set up that way.
+    pub fn from_directory(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        object_layout: &wgpu::BindGroupLayout,
+        dir: impl AsRef<Path>,
+        deinterleave_vertices: bool,
+    ) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .with_context(|| format!("listing {}", dir.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.is_file() && path.extension().map_or(false, |extension| extension.eq_ignore_ascii_case("obj"))
+            })
+            .collect();
+        paths.sort();
+
+        let mut meshes = Vec::with_capacity(paths.len());
+        for path in paths {
+            let mesh = std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|source| Mesh::from_obj(&source));
+            match mesh {
+                Ok(mesh) if mesh.vertices.is_empty() => log::warn!("Skipping {}, it has no vertices", path.display()),
+                Ok(mesh) => meshes.push((path, mesh)),
+                Err(e) => log::warn!("Skipping {}: {:#}", path.display(), e),
+            }
+        }
+
+        let columns = (meshes.len() as f32).sqrt().ceil().max(1.0) as usize;
+        let widest = meshes
+            .iter()
+            .map(|(_, mesh)| {
+                let size = mesh.bounding_box().size();
+                size.x.max(size.z)
+            })
+            .fold(0.0, f32::max);
+        let gap = SPACING * widest;
+
+        let mut items = Vec::with_capacity(meshes.len());
+        let mut labels = Vec::with_capacity(meshes.len());
+        let mut scene_bounds = Aabb::empty();
+        let mut z = 0.0;
+        for row in meshes.chunks(columns) {
+            let mut x = 0.0;
+            let mut row_depth: f32 = 0.0;
+            for (path, mesh) in row {
+                let label = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                let bounds = mesh.bounding_box();
+                let size = bounds.size();
+
+                let mut item = if deinterleave_vertices {
+                    RenderItem::from_mesh_deinterleaved(device, object_layout, mesh, &label)
+                } else {
+                    RenderItem::from_mesh(device, object_layout, mesh, &label)
+                };
+                // The box's minimum corner goes to (x, 0, z).
+                let corner = Vector3::new(x, 0.0, z);
+                item.transform.disp = corner - bounds.min;
+                scene_bounds.extend(corner);
+                scene_bounds.extend(corner + size);
+                item.write_transform(queue);
+                items.push(item);
+                labels.push(label);
+
+                x += size.x + gap;
+                row_depth = row_depth.max(size.z);
+            }
+            z += row_depth + gap;
+        }
+
+        Ok(Self {
+            items,
+            labels,
+            meshes: meshes.into_iter().map(|(_, mesh)| mesh).collect(),
+            bounds: scene_bounds,
+        })
+    }
+}
\ No newline at end of file
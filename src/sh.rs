@@ -0,0 +1,82 @@
+use cgmath::{InnerSpace, Vector3, Zero};
+
+// Real spherical harmonics bands 0 to 2, coefficients ordered
+// L00, L1-1, L10, L11, L2-2, L2-1, L20, L21, L22.
+
+fn basis(d: Vector3<f32>) -> [f32; 9] {
+    [
+        0.282095,
+        0.488603 * d.y,
+        0.488603 * d.z,
+        0.488603 * d.x,
+        1.092548 * d.x * d.y,
+        1.092548 * d.y * d.z,
+        0.315392 * (3.0 * d.z * d.z - 1.0),
+        1.092548 * d.x * d.z,
+        0.546274 * (d.x * d.x - d.y * d.y),
+    ]
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+/// Project an equirectangular environment map onto 9 SH coefficients of its
+/// radiance, weighting each pixel by the solid angle it covers.
+///
+/// Rows go from +y at the top to -y at the bottom, columns once around y starting
+/// at +x, the layout `Mesh::sphere` maps its texture with. The pixels are taken
+/// to be sRGB encoded.
+pub fn project_equirect(image: &image::DynamicImage) -> [Vector3<f32>; 9] {
+    let pixels = image.to_rgba8();
+    let (width, height) = pixels.dimensions();
+
+    let mut coefficients = [Vector3::zero(); 9];
+    let pixel_angle = (std::f32::consts::TAU / width as f32) * (std::f32::consts::PI / height as f32);
+    for y in 0..height {
+        let theta = (y as f32 + 0.5) / height as f32 * std::f32::consts::PI;
+        let solid_angle = pixel_angle * theta.sin();
+        for x in 0..width {
+            let phi = (x as f32 + 0.5) / width as f32 * std::f32::consts::TAU;
+            let direction = Vector3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+
+            let p = pixels.get_pixel(x, y);
+            let radiance = Vector3::new(srgb_to_linear(p[0]), srgb_to_linear(p[1]), srgb_to_linear(p[2]));
+
+            for (coefficient, basis) in coefficients.iter_mut().zip(basis(direction).iter()) {
+                *coefficient += radiance * (basis * solid_angle);
+            }
+        }
+    }
+    coefficients
+}
+
+/// Coefficients of an environment of the same `radiance` in every direction.
+pub fn uniform(radiance: Vector3<f32>) -> [Vector3<f32>; 9] {
+    let mut coefficients = [Vector3::zero(); 9];
+    // The integral of Y00 over the sphere, 4 pi * 0.282095.
+    coefficients[0] = radiance * 3.544908;
+    coefficients
+}
+
+/// Irradiance arriving at a surface facing `normal`, after Ramamoorthi and
+/// Hanrahan's "An Efficient Representation for Irradiance Environment Maps".
+/// A white diffuse surface reflects this divided by pi. The shaders evaluate the
+/// same formula, from sh_irradiance.glsl.
+pub fn irradiance(coefficients: &[Vector3<f32>; 9], normal: Vector3<f32>) -> Vector3<f32> {
+    const C1: f32 = 0.429043;
+    const C2: f32 = 0.511664;
+    const C3: f32 = 0.743125;
+    const C4: f32 = 0.886227;
+    const C5: f32 = 0.247708;
+
+    let n = normal.normalize();
+    let l = coefficients;
+    l[8] * (C1 * (n.x * n.x - n.y * n.y))
+        + l[6] * (C3 * n.z * n.z)
+        + l[0] * C4
+        - l[6] * C5
+        + (l[4] * (n.x * n.y) + l[7] * (n.x * n.z) + l[5] * (n.y * n.z)) * (2.0 * C1)
+        + (l[3] * n.x + l[1] * n.y + l[2] * n.z) * (2.0 * C2)
+}
\ No newline at end of file
@@ -0,0 +1,86 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, MetricSpace, Point3, Transform, Vector3};
+
+use crate::{camera::Camera, pipeline::PipelineBuilder};
+
+/// A directional light whose orthographic shadow projection follows the camera.
+pub struct DirectionalLight {
+    pub direction: Vector3<f32>,
+    pub shadow_map_size: u32,
+    // Depth bias for the pipeline drawing the shadow map, passed to
+    // `PipelineBuilder::depth_bias` and friends to fight shadow acne.
+    pub depth_bias: i32,
+    pub depth_bias_slope_scale: f32,
+    pub depth_bias_clamp: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vector3<f32>, shadow_map_size: u32) -> Self {
+        Self {
+            direction: direction.normalize(),
+            shadow_map_size,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            depth_bias_clamp: 0.0,
+        }
+    }
+
+    /// Apply the light's depth bias to the pipeline drawing its shadow map.
+    pub fn shadow_pipeline<'a>(&self, builder: PipelineBuilder<'a>) -> PipelineBuilder<'a> {
+        builder
+            .depth_bias(self.depth_bias)
+            .depth_bias_slope_scale(self.depth_bias_slope_scale)
+            .depth_bias_clamp(self.depth_bias_clamp)
+    }
+
+    /// Light view-projection covering the camera's view frustum.
+    ///
+    /// The frustum corners are bounded by a sphere, so the projection keeps its size
+    /// while the camera turns, and the sphere center is snapped to whole shadow map
+    /// texels in light space, so moving the camera shifts the projection by whole
+    /// texels and the shadow edges don't shimmer. The depth range is pushed back
+    /// towards the light by the sphere's diameter to keep casters that sit outside
+    /// the view frustum.
+    pub fn fit_view_proj(&self, camera: &Camera) -> Matrix4<f32> {
+        fit_orthographic(self.direction, &camera.frustum_corners(), self.shadow_map_size)
+    }
+}
+
+pub fn fit_orthographic(direction: Vector3<f32>, corners: &[Point3<f32>; 8], shadow_map_size: u32) -> Matrix4<f32> {
+    let direction = direction.normalize();
+    let up = if direction.y.abs() > 0.99 { Vector3::unit_z() } else { Vector3::unit_y() };
+    // Anchored at the origin rather than the frustum, a translating light view would undo the snapping.
+    let light_view = Matrix4::look_to_rh(Point3::origin(), direction, up);
+
+    let center = Point3::centroid(corners);
+    let radius = corners
+        .iter()
+        .map(|corner| corner.distance(center))
+        .fold(0.0, f32::max)
+        .max(f32::EPSILON);
+
+    let texel_size = 2.0 * radius / shadow_map_size.max(1) as f32;
+    let light_center = light_view.transform_point(center);
+    let x = (light_center.x / texel_size).floor() * texel_size;
+    let y = (light_center.y / texel_size).floor() * texel_size;
+
+    // The light looks down -z in its view space.
+    let near = -light_center.z - 3.0 * radius;
+    let far = -light_center.z + radius;
+
+    orthographic(x - radius, x + radius, y - radius, y + radius, near, far) * light_view
+}
+
+/// Like `cgmath::ortho` but maps depth to the 0..1 range wgpu clips against.
+#[rustfmt::skip]
+fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Matrix4<f32> {
+    let width = right - left;
+    let height = top - bottom;
+    let depth = far - near;
+
+    Matrix4::new(
+        2.0 / width, 0.0, 0.0, 0.0,
+        0.0, 2.0 / height, 0.0, 0.0,
+        0.0, 0.0, -1.0 / depth, 0.0,
+        -(right + left) / width, -(top + bottom) / height, -near / depth, 1.0,
+    )
+}
\ No newline at end of file
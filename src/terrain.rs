@@ -0,0 +1,262 @@
+use anyhow::{bail, Result};
+use wgpu::util::DeviceExt;
+
+use crate::{model::{AsVertexPrimitive, Mesh, Vertex}, pipeline::PipelineBuilder, post_process::{sampler_entry, uniform_entry}, render_item::{DrawRenderItem, RenderItem}, texture::Texture};
+
+/// Texture layers a `TerrainMaterial` blends, in this order.
+pub const LAYERS: [&str; 3] = ["ground", "rock", "snow"];
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct TerrainUniforms {
+    slope_range: [f32; 2],
+    height_range: [f32; 2],
+    tiling: f32,
+    mip_bias: f32,
+    _padding: [f32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for TerrainUniforms {}
+unsafe impl bytemuck::Pod for TerrainUniforms {}
+
+/// How a `TerrainMaterial` blends and samples its layers, uploaded by `update`.
+/// Each range's start must be below its end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainSettings {
+    /// Steepness, 0 for flat to 1 for vertical, over which rock takes over.
+    pub slope_start: f32,
+    pub slope_end: f32,
+    /// World heights over which snow takes over the ground that isn't rock.
+    pub height_start: f32,
+    pub height_end: f32,
+    /// Repeats of the layers across the mesh's 0..1 texture coordinates.
+    pub tiling: f32,
+    /// Added to the mip level the hardware picks, below 0 for crisper but more
+    /// shimmering ground at a distance.
+    pub mip_bias: f32,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            slope_start: 0.25,
+            slope_end: 0.45,
+            height_start: 1.0,
+            height_end: 1.5,
+            tiling: 16.0,
+            mip_bias: -0.5,
+        }
+    }
+}
+
+/// A mesh to draw with a `TerrainMaterial`, see `Application::with_terrain`.
+pub struct Terrain {
+    pub mesh: Mesh,
+    /// One image per entry of `LAYERS`, all the same size.
+    pub layers: Vec<image::DynamicImage>,
+    pub settings: TerrainSettings,
+    /// Anisotropic filtering samples, see `Texture::array_from_images`.
+    pub anisotropy: u8,
+}
+
+impl Terrain {
+    pub fn new(mesh: Mesh, layers: Vec<image::DynamicImage>) -> Self {
+        Self {
+            mesh,
+            layers,
+            settings: TerrainSettings::default(),
+            anisotropy: 16,
+        }
+    }
+}
+
+/// Ground, rock and snow blended per pixel by the steepness and height of the
+/// surface, for a displaced grid like `Mesh::grid` after `Mesh::displace_noise`.
+///
+/// The weights come from the interpolated world space normal and height in the
+/// fragment shader, so the transitions stay smooth however coarse the mesh is.
+/// Steep slopes are rock, what isn't turns to snow with height, and the rest is
+/// ground. The layers are sampled trilinearly and anisotropically from a mipped
+/// texture array, tiled `tiling` times, and lit by the scene's environment like
+/// the rest of the forward pass.
+pub struct TerrainMaterial {
+    pub settings: TerrainSettings,
+
+    layout: wgpu::PipelineLayout,
+    vs_module: wgpu::ShaderModule,
+    fs_module: wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+    deinterleave_vertices: bool,
+    pipeline: wgpu::RenderPipeline,
+    _layers: Texture,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl TerrainMaterial {
+    /// `format`, `depth_format`, `sample_count` and `deinterleave_vertices` match
+    /// the scene pass, and the layouts are its sets 1 to 3. Fails when the layer
+    /// images can't make a texture array.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        terrain: &Terrain,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        deinterleave_vertices: bool,
+        camera_layout: &wgpu::BindGroupLayout,
+        object_layout: &wgpu::BindGroupLayout,
+        environment_layout: &wgpu::BindGroupLayout,
+    ) -> Result<Self> {
+        if terrain.layers.len() != LAYERS.len() {
+            bail!("terrain needs {} layers ({}), got {}", LAYERS.len(), LAYERS.join(", "), terrain.layers.len());
+        }
+        let layers = Texture::array_from_images(device, queue, &terrain.layers, terrain.anisotropy, "terrain_layers")?;
+
+        let material_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("terrain_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    sampler_entry(1),
+                    uniform_entry(2),
+                ],
+            }
+        );
+
+        let layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Terrain Pipeline Layout"),
+                bind_group_layouts: &[&material_layout, camera_layout, object_layout, environment_layout],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Terrain Buffer"),
+                contents: bytemuck::cast_slice(&[Self::uniforms(&terrain.settings)]),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            }
+        );
+
+        let bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("terrain_bind_group"),
+                layout: &material_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&layers.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&layers.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            }
+        );
+
+        // The G-buffer's vertex stage already passes on the world position the blend needs.
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/gbuffer.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/terrain.frag.spv"));
+        let pipeline = Self::build_pipeline(device, &layout, &vs_module, &fs_module, format, depth_format, sample_count, deinterleave_vertices);
+
+        Ok(Self {
+            settings: terrain.settings,
+            layout,
+            vs_module,
+            fs_module,
+            format,
+            depth_format,
+            deinterleave_vertices,
+            pipeline,
+            _layers: layers,
+            uniform_buffer,
+            bind_group,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        vs_module: &wgpu::ShaderModule,
+        fs_module: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        deinterleave_vertices: bool,
+    ) -> wgpu::RenderPipeline {
+        let vertex_layouts = if deinterleave_vertices { Vertex::deinterleaved_desc() } else { vec![Vertex::desc()] };
+        let mut builder = PipelineBuilder::new(layout, vs_module, fs_module, format)
+            .label("Terrain Pipeline")
+            .depth_format(Some(depth_format))
+            .sample_count(sample_count);
+        for vertex_layout in vertex_layouts {
+            builder = builder.vertex_layout(vertex_layout);
+        }
+        builder.build(device)
+    }
+
+    fn uniforms(settings: &TerrainSettings) -> TerrainUniforms {
+        TerrainUniforms {
+            slope_range: [settings.slope_start, settings.slope_end],
+            height_range: [settings.height_start, settings.height_end],
+            tiling: settings.tiling,
+            mip_bias: settings.mip_bias,
+            _padding: [0.0; 2],
+        }
+    }
+
+    /// Rebuild the pipeline for a scene pass with `sample_count` samples per pixel.
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.pipeline = Self::build_pipeline(
+            device,
+            &self.layout,
+            &self.vs_module,
+            &self.fs_module,
+            self.format,
+            self.depth_format,
+            sample_count,
+            self.deinterleave_vertices,
+        );
+    }
+
+    /// Upload `settings`.
+    pub fn update(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[Self::uniforms(&self.settings)]));
+    }
+
+    /// Draw `item` in the scene pass. The camera and environment bind groups are
+    /// bound again since the scene's layout differs from the terrain's in the first set.
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        environment_bind_group: &'a wgpu::BindGroup,
+        item: &'a RenderItem,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_bind_group(3, environment_bind_group, &[]);
+        render_pass.draw_item(item);
+    }
+}
\ No newline at end of file
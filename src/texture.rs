@@ -1,17 +1,49 @@
+use std::num::{NonZeroU32, NonZeroU8};
+
 use anyhow::*;
+use wgpu::util::DeviceExt;
+
+use crate::gpu_memory::Allocation;
+use crate::pipeline::PipelineBuilder;
+use crate::post_process::{fullscreen_pass, sampler_entry, texture_entry, uniform_entry};
 
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    // Only held to count the texture in `GpuReport` while it's alive.
+    _allocation: Allocation,
 }
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// Depth with an 8 bit stencil, for passes that mask by stencil like the
+    /// selection outline.
+    pub const DEPTH_STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+    /// Format of `equirect_to_cubemap`'s cube maps, half floats keep HDR radiance
+    /// and can still be filtered.
+    pub const CUBE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    /// Whether depth attachments of `format` have a stencil aspect.
+    pub fn has_stencil(format: wgpu::TextureFormat) -> bool {
+        matches!(format, wgpu::TextureFormat::Depth24PlusStencil8)
+    }
 
     pub fn create_depth_texture(
         device: &wgpu::Device,
         swap_chain_desc: &wgpu::SwapChainDescriptor,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        Self::create_depth_texture_with_format(device, swap_chain_desc, sample_count, Self::DEPTH_FORMAT, label)
+    }
+
+    /// `create_depth_texture` in `format`, e.g. `DEPTH_STENCIL_FORMAT`.
+    pub fn create_depth_texture_with_format(
+        device: &wgpu::Device,
+        swap_chain_desc: &wgpu::SwapChainDescriptor,
+        sample_count: u32,
+        format: wgpu::TextureFormat,
         label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
@@ -24,13 +56,14 @@ impl Texture {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
+            format,
             usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
         };
 
         let texture = device.create_texture(&desc);
+        let allocation = Allocation::texture(&desc);
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(
             &wgpu::SamplerDescriptor {
@@ -46,7 +79,63 @@ impl Texture {
             }
         );
 
-        Self { texture, sampler, view}
+        Self { texture, sampler, view, _allocation: allocation }
+    }
+
+    /// A color texture that can be rendered into and then sampled by a later pass.
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        Self::create_multisampled_render_target(device, width, height, format, 1, label)
+    }
+
+    /// A render target with `sample_count` samples per pixel, to be resolved into a
+    /// single sampled target at the end of the pass that draws it.
+    pub fn create_multisampled_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+
+        let desc = wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // COPY_SRC so the result can be read back, e.g. by `render_check`.
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_SRC,
+        };
+
+        let texture = device.create_texture(&desc);
+        let allocation = Allocation::texture(&desc);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+
+        Self { texture, view, sampler, _allocation: allocation }
     }
 
     pub fn from_bytes(
@@ -74,17 +163,17 @@ impl Texture {
             depth: 1,
         };
 
-        let texture = device.create_texture(
-            &wgpu::TextureDescriptor {
-                label,
-                size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
-            }
-        );
+        let desc = wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        };
+        let texture = device.create_texture(&desc);
+        let allocation = Allocation::texture(&desc);
 
         queue.write_texture(
             wgpu::TextureCopyView {
@@ -114,6 +203,241 @@ impl Texture {
             }
         );
 
-        Ok(Self { texture, view, sampler })
+        Ok(Self { texture, view, sampler, _allocation: allocation })
+    }
+
+    /// A 2D array texture with one layer per image, all the same size, for
+    /// materials blending several textures like `TerrainMaterial`.
+    ///
+    /// Unlike `from_image`, a full mip chain is built on the CPU, each level
+    /// filtered down from the one above, and the sampler repeats and filters
+    /// trilinearly, with up to `anisotropy` samples along the direction of most
+    /// stretch: 1, 2, 4, 8 or 16, 1 for none. Adapters without anisotropic
+    /// filtering quietly ignore it.
+    pub fn array_from_images(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[image::DynamicImage],
+        anisotropy: u8,
+        label: &str,
+    ) -> Result<Self> {
+        use image::GenericImageView;
+
+        let (width, height) = images.first().context("a texture array needs at least one image")?.dimensions();
+        if let Some(i) = images.iter().position(|image| image.dimensions() != (width, height)) {
+            bail!("layer {} of {} is {:?}, not {}x{} like the first", i, label, images[i].dimensions(), width, height);
+        }
+        if !anisotropy.is_power_of_two() || anisotropy > 16 {
+            bail!("anisotropy must be 1, 2, 4, 8 or 16, not {}", anisotropy);
+        }
+
+        let mip_level_count = 32 - width.max(height).leading_zeros();
+        let desc = wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth: images.len() as u32,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        };
+        let texture = device.create_texture(&desc);
+        let allocation = Allocation::texture(&desc);
+
+        for (layer, image) in images.iter().enumerate() {
+            let mut level_image = image.to_rgba8();
+            for level in 0..mip_level_count {
+                if level > 0 {
+                    let (level_width, level_height) = ((width >> level).max(1), (height >> level).max(1));
+                    level_image = image::imageops::resize(&level_image, level_width, level_height, image::imageops::FilterType::Triangle);
+                }
+                let (level_width, level_height) = level_image.dimensions();
+                queue.write_texture(
+                    wgpu::TextureCopyView {
+                        texture: &texture,
+                        mip_level: level,
+                        origin: wgpu::Origin3d { x: 0, y: 0, z: layer as u32 },
+                    },
+                    &level_image,
+                    wgpu::TextureDataLayout {
+                        offset: 0,
+                        bytes_per_row: 4 * level_width,
+                        rows_per_image: level_height,
+                    },
+                    wgpu::Extent3d {
+                        width: level_width,
+                        height: level_height,
+                        depth: 1,
+                    },
+                );
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                anisotropy_clamp: NonZeroU8::new(anisotropy).filter(|anisotropy| anisotropy.get() > 1),
+                ..Default::default()
+            }
+        );
+
+        Ok(Self { texture, view, sampler, _allocation: allocation })
+    }
+
+    /// Render an equirectangular environment into the six faces of a `face_size`
+    /// square `CUBE_FORMAT` cube map, whose `view` is a cube view for skyboxes or
+    /// image based lighting.
+    ///
+    /// `equirect` is laid out as for `sh::project_equirect`, rows from +y at the top
+    /// to -y at the bottom and columns once around y starting at +x, and must have
+    /// a filterable format, which rules out 32 bit floats. Its values are copied as
+    /// they are, so an HDR source stays HDR. The faces are the array layers in the
+    /// order +x, -x, +y, -y, +z, -z, each seen from the center with its t
+    /// coordinate going down, the convention cube views sample with. Mip levels
+    /// aren't generated.
+    pub fn equirect_to_cubemap(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        equirect: &Texture,
+        face_size: u32,
+    ) -> Self {
+        let desc = wgpu::TextureDescriptor {
+            label: Some("equirect_cubemap"),
+            size: wgpu::Extent3d {
+                width: face_size,
+                height: face_size,
+                depth: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::CUBE_FORMAT,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+        };
+        let texture = device.create_texture(&desc);
+        let allocation = Allocation::texture(&desc);
+
+        let source_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("equirect_source_layout"),
+                entries: &[texture_entry(0), sampler_entry(1)],
+            }
+        );
+        let face_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("equirect_face_layout"),
+                entries: &[uniform_entry(0)],
+            }
+        );
+        let pipeline_layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Equirect To Cube Pipeline Layout"),
+                bind_group_layouts: &[&source_layout, &face_layout],
+                push_constant_ranges: &[],
+            }
+        );
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/fullscreen.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/equirect_to_cube.frag.spv"));
+        let pipeline = PipelineBuilder::new(&pipeline_layout, &vs_module, &fs_module, Self::CUBE_FORMAT)
+            .label("Equirect To Cube Pipeline")
+            .depth_format(None)
+            .cull_mode(wgpu::CullMode::None)
+            .build(device);
+
+        // Wraps around horizontally so the seam at +x blends both edges.
+        let equirect_sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+        let source_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("equirect_source_bind_group"),
+                layout: &source_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&equirect.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&equirect_sampler),
+                    },
+                ],
+            }
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Equirect To Cube Encoder"),
+        });
+        for face in 0..6u32 {
+            // The face index, padded to the 16 bytes of a uniform block.
+            let face_buffer = device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Equirect Face Buffer"),
+                    contents: bytemuck::cast_slice(&[face, 0, 0, 0]),
+                    usage: wgpu::BufferUsage::UNIFORM,
+                }
+            );
+            let face_bind_group = device.create_bind_group(
+                &wgpu::BindGroupDescriptor {
+                    label: Some("equirect_face_bind_group"),
+                    layout: &face_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: face_buffer.as_entire_binding(),
+                        },
+                    ],
+                }
+            );
+            let face_view = texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("equirect_cubemap_face"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: face,
+                array_layer_count: NonZeroU32::new(1),
+                ..Default::default()
+            });
+            fullscreen_pass(&mut encoder, "Equirect To Cube Pass", &face_view, &pipeline, &[&source_bind_group, &face_bind_group]);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("equirect_cubemap_view"),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+
+        Self { texture, view, sampler, _allocation: allocation }
     }
 }
\ No newline at end of file
@@ -0,0 +1,178 @@
+use wgpu::util::DeviceExt;
+
+use crate::{model::{AsVertexPrimitive, Mesh, Vertex}, pipeline::PipelineBuilder, render_item::{DrawRenderItem, RenderItem}};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct WireframeUniforms {
+    color: [f32; 4],
+    depth_bias: f32,
+    _padding: [f32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for WireframeUniforms {}
+unsafe impl bytemuck::Pod for WireframeUniforms {}
+
+/// The edges of render items in `color`, drawn in the scene pass over the
+/// shaded surface for inspecting the topology.
+///
+/// The edges come from `edges`, one line per unique edge, see `Mesh::to_wireframe`. They sit exactly on the
+/// triangles they outline, so they're pulled toward the camera by `depth_bias`,
+/// a share of the depth range, to win the depth test without showing through
+/// the surface. The pull is done in the vertex shader, fixed function depth bias
+/// only applies to triangles on most backends. Lines are one pixel wide.
+pub struct Wireframe {
+    pub color: [f32; 4],
+    pub depth_bias: f32,
+
+    layout: wgpu::PipelineLayout,
+    vs_module: wgpu::ShaderModule,
+    fs_module: wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    depth_format: wgpu::TextureFormat,
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+}
+
+impl Wireframe {
+    /// `format`, `depth_format` and `sample_count` match the scene pass.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        camera_layout: &wgpu::BindGroupLayout,
+        object_layout: &wgpu::BindGroupLayout,
+        color: [f32; 4],
+        depth_bias: f32,
+    ) -> Self {
+        let uniform_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("wireframe_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            }
+        );
+
+        let layout = device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Wireframe Pipeline Layout"),
+                bind_group_layouts: &[&uniform_layout, camera_layout, object_layout],
+                push_constant_ranges: &[],
+            }
+        );
+
+        let uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Wireframe Buffer"),
+                contents: bytemuck::cast_slice(&[WireframeUniforms { color, depth_bias, _padding: [0.0; 3] }]),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            }
+        );
+
+        let uniform_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("wireframe_bind_group"),
+                layout: &uniform_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            }
+        );
+
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/wireframe.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("resources/shaders/wireframe.frag.spv"));
+        let pipeline = Self::build_pipeline(device, &layout, &vs_module, &fs_module, format, depth_format, sample_count);
+
+        Self {
+            color,
+            depth_bias,
+            layout,
+            vs_module,
+            fs_module,
+            format,
+            depth_format,
+            pipeline,
+            uniform_buffer,
+            uniform_bind_group,
+        }
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        vs_module: &wgpu::ShaderModule,
+        fs_module: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        PipelineBuilder::new(layout, vs_module, fs_module, format)
+            .label("Wireframe Pipeline")
+            .vertex_layout(Vertex::desc())
+            .topology(wgpu::PrimitiveTopology::LineList)
+            .depth_format(Some(depth_format))
+            .depth_compare(wgpu::CompareFunction::LessEqual)
+            .depth_write(false)
+            .cull_mode(wgpu::CullMode::None)
+            .sample_count(sample_count)
+            .build(device)
+    }
+
+    /// A `LineList` item of the unique edges of `mesh`, for `draw`. Its transform
+    /// starts at the identity, copy the one of the item it outlines.
+    pub fn edges(device: &wgpu::Device, object_layout: &wgpu::BindGroupLayout, mesh: &Mesh, label: &str) -> RenderItem {
+        let lines = mesh.to_wireframe();
+        RenderItem::line_list(device, object_layout, &lines.vertices, &lines.indices, label)
+    }
+
+    /// Rebuild the pipeline for a scene pass with `sample_count` samples per pixel.
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.pipeline = Self::build_pipeline(
+            device,
+            &self.layout,
+            &self.vs_module,
+            &self.fs_module,
+            self.format,
+            self.depth_format,
+            sample_count,
+        );
+    }
+
+    /// Upload `color` and `depth_bias`.
+    pub fn update(&self, queue: &wgpu::Queue) {
+        let uniforms = WireframeUniforms {
+            color: self.color,
+            depth_bias: self.depth_bias,
+            _padding: [0.0; 3],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+    }
+
+    /// Draw `edges`, made by `Wireframe::edges`, in `render_pass` after the scene.
+    /// `camera_bind_group` holds the view-projection, and is bound again since the
+    /// scene's layout differs from the wireframe's in the first set.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup, edges: &'a [RenderItem]) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        for item in edges {
+            render_pass.draw_item(item);
+        }
+    }
+}
\ No newline at end of file